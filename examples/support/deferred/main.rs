@@ -178,14 +178,14 @@ impl gfx::format::Formatted for DepthFormat {
     #[cfg(feature="metal")]
     type Surface = gfx::format::D32;
     #[cfg(not(feature="metal"))]
-    type Surface = gfx::format::D24;
+    type Surface = gfx::format::X8_D24;
 
     type Channel = gfx::format::Unorm;
     type View = [f32; 4];
 
     fn get_format() -> gfx::format::Format {
         use gfx::format as f;
-        f::Format(f::SurfaceType::D24, f::ChannelType::Unorm)
+        f::Format(f::SurfaceType::X8_D24, f::ChannelType::Unorm)
     }
 }
 
@@ -308,6 +308,28 @@ impl<P: 'static + Parser> command::Buffer<Resources> for RawCommandBuffer<P> {
         self.parser.parse(Command::BindIndex(buf, format));
     }
 
+    fn bind_stream_output(&mut self, buf: Option<Buffer>) {
+        if buf.is_some() {
+            error!("Stream output is not supported yet");
+        }
+    }
+
+    fn set_viewports(&mut self, viewports: &[command::Viewport]) {
+        if viewports.len() > 1 {
+            error!("Viewport arrays are not supported yet, only the first viewport is used");
+        }
+        if let Some(vp) = viewports.first() {
+            self.parser.parse(Command::SetViewport(D3D11_VIEWPORT {
+                TopLeftX: vp.rect.x as FLOAT,
+                TopLeftY: vp.rect.y as FLOAT,
+                Width: vp.rect.w as FLOAT,
+                Height: vp.rect.h as FLOAT,
+                MinDepth: vp.min_depth,
+                MaxDepth: vp.max_depth,
+            }));
+        }
+    }
+
     fn set_scissor(&mut self, rect: target::Rect) {
         self.parser.parse(Command::SetScissor(D3D11_RECT {
             left: rect.x as INT,
@@ -389,6 +411,16 @@ impl<P: 'static + Parser> command::Buffer<Resources> for RawCommandBuffer<P> {
         ));
     }
 
+    fn begin_query(&mut self, _: ()) {}
+
+    fn end_query(&mut self, _: ()) {}
+
+    fn begin_conditional(&mut self, _: (), _: command::ConditionalMode) {
+        // the draw calls are executed unconditionally
+    }
+
+    fn end_conditional(&mut self) {}
+
     fn call_draw(&mut self, start: VertexCount, count: VertexCount, instances: Option<command::InstanceParams>) {
         self.flush();
         self.parser.parse(match instances {
@@ -126,7 +126,7 @@ pub fn map_format(format: Format, is_target: bool) -> Option<DXGI_FORMAT> {
             (false, Unorm) => DXGI_FORMAT_R16_UNORM,
             _ => return None,
         },
-        D24 => match (is_target, format.1) {
+        X8_D24 => match (is_target, format.1) {
             (true, _)      => DXGI_FORMAT_D24_UNORM_S8_UINT,
             (false, Unorm) => DXGI_FORMAT_R24_UNORM_X8_TYPELESS,
             _ => return None,
@@ -148,6 +148,7 @@ pub fn map_format(format: Format, is_target: bool) -> Option<DXGI_FORMAT> {
             (false, Uint)  => DXGI_FORMAT_X32_TYPELESS_G8X24_UINT,
             _ => return None,
         },
+        S8 => return None,
     })
 }
 
@@ -170,9 +171,10 @@ pub fn map_surface(surface: SurfaceType) -> Option<DXGI_FORMAT> {
         R32_G32_B32_A32 => DXGI_FORMAT_R32G32B32A32_TYPELESS,
         B8_G8_R8_A8     => DXGI_FORMAT_B8G8R8A8_TYPELESS,
         D16             => DXGI_FORMAT_R16_TYPELESS,
-        D24 | D24_S8    => DXGI_FORMAT_R24G8_TYPELESS,
+        X8_D24 | D24_S8    => DXGI_FORMAT_R24G8_TYPELESS,
         D32             => DXGI_FORMAT_R32_TYPELESS,
         D32_S8          => DXGI_FORMAT_R32G8X24_TYPELESS,
+        S8              => return None,
     })
 }
 
@@ -207,6 +209,9 @@ pub fn map_bind(bind: Bind) -> D3D11_BIND_FLAG {
     if bind.contains(memory::UNORDERED_ACCESS) {
         flags = flags | D3D11_BIND_UNORDERED_ACCESS;
     }
+    if bind.contains(memory::STREAM_OUTPUT) {
+        flags = flags | D3D11_BIND_STREAM_OUTPUT;
+    }
     flags
 }
 
@@ -340,6 +340,32 @@ impl Device {
     pub fn cleanup(&mut self) {
         self.frame_handles.clear();
     }
+
+    /// Get the device for the `Check*` queries, which take `&mut self` in the
+    /// bindings while leaving the device unchanged.
+    ///
+    /// Unsafe, as the returned reference must not outlive the query.
+    unsafe fn query_device(&self) -> &mut winapi::ID3D11Device {
+        &mut *self.device.as_raw()
+    }
+
+    fn check_format_support(&self, format: winapi::DXGI_FORMAT) -> winapi::UINT {
+        let mut support = 0;
+        let hr = unsafe { self.query_device().CheckFormatSupport(format, &mut support) };
+        if winapi::SUCCEEDED(hr) { support } else { 0 }
+    }
+
+    fn check_sample_counts(&self, format: winapi::DXGI_FORMAT) -> texture::NumSamples {
+        let mut counts = 1;
+        for &count in &[2, 4, 8, 16, 32] {
+            let mut levels = 0;
+            let hr = unsafe { self.query_device().CheckMultisampleQualityLevels(format, count, &mut levels) };
+            if winapi::SUCCEEDED(hr) && levels != 0 {
+                counts |= count as texture::NumSamples;
+            }
+        }
+        counts
+    }
 }
 
 impl core::Device<R> for Device {
@@ -347,6 +373,43 @@ impl core::Device<R> for Device {
         &self.share.capabilities
     }
 
+    fn get_format_properties(&self, format: core::format::Format) -> core::format::FormatProperties {
+        use core::format::FormatProperties;
+        use data::map_format;
+        use winapi::d3d11::*;
+
+        let mut props = FormatProperties::unsupported();
+        let has = |support: winapi::UINT, flag: D3D11_FORMAT_SUPPORT| support & flag.0 != 0;
+        if let Some(view_format) = map_format(format, false) {
+            let support = self.check_format_support(view_format);
+            if has(support, D3D11_FORMAT_SUPPORT_SHADER_LOAD) {
+                props.bind.insert(memory::SHADER_RESOURCE | memory::TRANSFER_SRC | memory::TRANSFER_DST);
+            }
+            if has(support, D3D11_FORMAT_SUPPORT_RENDER_TARGET) {
+                props.bind.insert(memory::RENDER_TARGET);
+            }
+            if has(support, D3D11_FORMAT_SUPPORT_TYPED_UNORDERED_ACCESS_VIEW) {
+                props.bind.insert(memory::UNORDERED_ACCESS);
+            }
+            props.filterable = has(support, D3D11_FORMAT_SUPPORT_SHADER_SAMPLE);
+            props.blendable = has(support, D3D11_FORMAT_SUPPORT_BLENDABLE);
+            if props.bind.contains(memory::RENDER_TARGET) {
+                props.sample_counts = self.check_sample_counts(view_format);
+            }
+        }
+        if let Some(target_format) = map_format(format, true) {
+            let support = self.check_format_support(target_format);
+            if has(support, D3D11_FORMAT_SUPPORT_DEPTH_STENCIL) {
+                props.bind.insert(memory::DEPTH_STENCIL);
+                props.sample_counts = self.check_sample_counts(target_format);
+            }
+        }
+        if props.is_supported() {
+            props.sample_counts |= 1;
+        }
+        props
+    }
+
     fn create_buffer_raw(&mut self, info: buffer::Info) -> Result<h::RawBuffer<R>, buffer::CreationError> {
         self.create_buffer_internal(info, None)
     }
@@ -436,7 +499,7 @@ impl core::Device<R> for Device {
     fn create_program(&mut self, shader_set: &core::ShaderSet<R>)
                       -> Result<h::Program<R>, core::shade::CreateProgramError> {
         use winapi::{ID3D11VertexShader, ID3D11HullShader, ID3D11DomainShader, ID3D11GeometryShader, ID3D11PixelShader};
-        use core::shade::{ProgramInfo, Stage};
+        use core::shade::{ProgramInfo, SlotAssignment, Stage};
         use mirror::populate_info;
 
         let mut info = ProgramInfo {
@@ -449,6 +512,7 @@ impl core::Device<R> for Device {
             outputs: Vec::new(),
             output_depth: false,
             knows_outputs: true,
+            slot_assignment: SlotAssignment::Explicit,
         };
         let fh = &mut self.frame_handles;
         let prog = match shader_set {
@@ -509,6 +573,10 @@ impl core::Device<R> for Device {
         use data::map_format;
         use state;
 
+        if desc.stream_output.is_some() {
+            error!("Stream output is not supported yet");
+            return Err(core::pso::CreationError);
+        }
         let mut layouts = Vec::new();
         let mut charbuf = [0; 256];
         let mut charpos = 0;
@@ -692,7 +760,7 @@ impl core::Device<R> for Device {
                                        -> Result<h::RawShaderResourceView<R>, d::ResourceViewError> {
         use winapi::UINT;
         use core::texture::{AaMode, Kind};
-        use data::map_format;
+        use data::{map_format, map_surface};
         //TODO: support desc.layer parsing
 
         let (dim, layers, has_levels) = match htex.get_info().kind {
@@ -716,7 +784,14 @@ impl core::Device<R> for Device {
                 (winapi::D3D11_SRV_DIMENSION_TEXTURECUBEARRAY, d, true),
         };
 
-        let format = core::format::Format(htex.get_info().format, desc.channel);
+        let surface = match desc.surface {
+            // the typeless format of the texture is only shared within its family
+            Some(surface) if map_surface(surface) != map_surface(htex.get_info().format) =>
+                return Err(d::ResourceViewError::Surface(surface)),
+            Some(surface) => surface,
+            None => htex.get_info().format,
+        };
+        let format = core::format::Format(surface, desc.channel);
         let native_desc = winapi::D3D11_SHADER_RESOURCE_VIEW_DESC {
             Format: match map_format(format, false) {
                 Some(fm) => fm,
@@ -918,6 +993,15 @@ impl core::Device<R> for Device {
         self.share.handles.borrow_mut().make_fence(Fence)
     }
 
+    fn create_query(&mut self) -> h::Query<R> {
+        error!("Queries are not supported yet");
+        self.share.handles.borrow_mut().make_query(())
+    }
+
+    fn get_query_result(&mut self, _: &h::Query<R>, _: bool) -> Option<u64> {
+        Some(1)
+    }
+
     fn reset_fences(&mut self, fences: &[&h::Fence<R>]) {
         // TODO: noop?
     }
@@ -226,6 +226,7 @@ impl core::Resources for Resources {
     type ShaderResourceView  = native::Srv;
     type UnorderedAccessView = ();
     type Sampler             = native::Sampler;
+    type Query               = (); // TODO
     type Fence               = Fence;
     type Semaphore           = (); // TODO
     type Mapping             = device::MappingGate;
@@ -347,6 +348,14 @@ impl core::Adapter<Backend> for Adapter {
             capabilities: core::Capabilities {
                 max_texture_size: 0,
                 max_patch_size: 32, //hard-coded in D3D11
+                max_viewports: 1, //TODO: viewport arrays
+                max_color_targets: 8, //hard-coded in D3D11
+                max_vertex_attributes: 32,
+                max_constant_buffer_size: 4096 * 16,
+                max_texture_3d_size: 2048,
+                max_texture_array_layers: 2048,
+                max_anisotropy: 16,
+                max_samples: 8, //TODO: query per format
                 instance_base_supported: false,
                 instance_call_supported: false,
                 instance_rate_supported: false,
@@ -355,7 +364,19 @@ impl core::Adapter<Backend> for Adapter {
                 constant_buffer_supported: true,
                 unordered_access_view_supported: false,
                 separate_blending_slots_supported: false,
+                dual_source_blending_supported: false,
+                logic_op_supported: false,
+                stream_output_supported: false,
+                occlusion_query_supported: false,
+                conditional_render_supported: false,
                 copy_buffer_supported: true,
+                geometry_shader_supported: true,
+                tessellation_supported: feature_level.0 >= winapi::D3D_FEATURE_LEVEL_11_0.0,
+                compute_supported: false, //TODO
+                indirect_draw_supported: false, //TODO
+                srgb_decode_supported: true,
+                depth_clamp_supported: true,
+                seamless_cube_supported: true,
             },
             handles: RefCell::new(h::Manager::new()),
         });
@@ -377,7 +398,7 @@ impl core::Adapter<Backend> for Adapter {
                 context: context.clone(),
                 share: share.clone(),
                 frame_handles: handle::Manager::new(),
-                max_resource_count: Some(999999),
+                max_resource_count: Some(handle::MAX_FRAME_RESOURCES),
             }
         };
 
@@ -426,7 +447,7 @@ impl CommandQueue {
     pub fn before_submit<'a>(&mut self, gpu_access: &'a AccessInfo<Resources>)
                              -> core::SubmissionResult<AccessGuard<'a, Resources>> {
         let mut gpu_access = try!(gpu_access.take_accesses());
-        for (buffer, mut mapping) in gpu_access.access_mapped() {
+        for (buffer, _, mut mapping) in gpu_access.access_mapped() {
             device::ensure_unmapped(&mut mapping, buffer, &mut self.context);
         }
         Ok(gpu_access)
@@ -457,7 +478,8 @@ impl core::CommandQueue<Backend> for CommandQueue {
         self.frame_handles.extend(man);
         match self.max_resource_count {
             Some(c) if self.frame_handles.count() > c => {
-                error!("Way too many resources in the current frame. Did you call Device::cleanup()?");
+                error!("Way too many resources in the current frame ({}). Did you call Device::cleanup()?",
+                       self.frame_handles.counts());
                 self.max_resource_count = None;
             },
             _ => (),
@@ -504,6 +526,7 @@ impl core::CommandQueue<Backend> for CommandQueue {
             |_, v| unsafe { (*v.0).Release(); }, //sampler
             |_, _fence| {},
             |_, _| {}, // Semaphore
+            |_, _| {}, // Query
         );
     }
 }
@@ -80,6 +80,14 @@ impl command::Buffer<Resources> for CommandBuffer {
         unimplemented!()
     }
 
+    fn bind_stream_output(&mut self, _: Option<()>) {
+        unimplemented!()
+    }
+
+    fn set_viewports(&mut self, _: &[command::Viewport]) {
+        unimplemented!()
+    }
+
     fn set_scissor(&mut self, _: target::Rect) {
         unimplemented!()
     }
@@ -133,6 +141,22 @@ impl command::Buffer<Resources> for CommandBuffer {
         unimplemented!()
     }
 
+    fn begin_query(&mut self, _: ()) {
+        unimplemented!()
+    }
+
+    fn end_query(&mut self, _: ()) {
+        unimplemented!()
+    }
+
+    fn begin_conditional(&mut self, _: (), _: command::ConditionalMode) {
+        unimplemented!()
+    }
+
+    fn end_conditional(&mut self) {
+        unimplemented!()
+    }
+
     fn call_draw(&mut self, start: VertexCount, count: VertexCount, instances: Option<command::InstanceParams>) {
         unimplemented!();
     }
@@ -109,7 +109,7 @@ pub fn map_format(format: Format, is_target: bool) -> Option<DXGI_FORMAT> {
             (false, Unorm) => DXGI_FORMAT_R16_UNORM,
             _ => return None,
         },
-        D24 => match (is_target, format.1) {
+        X8_D24 => match (is_target, format.1) {
             (true, _)      => DXGI_FORMAT_D24_UNORM_S8_UINT,
             (false, Unorm) => DXGI_FORMAT_R24_UNORM_X8_TYPELESS,
             _ => return None,
@@ -131,5 +131,6 @@ pub fn map_format(format: Format, is_target: bool) -> Option<DXGI_FORMAT> {
             (false, Uint)  => DXGI_FORMAT_X32_TYPELESS_G8X24_UINT,
             _ => return None,
         },
+        S8 => return None,
     })
 }
@@ -22,6 +22,8 @@ use {Resources as R, Device};
 impl d::Device<R> for Device {
     fn get_capabilities(&self) -> &c::Capabilities { unimplemented!() }
 
+    fn get_format_properties(&self, _: format::Format) -> format::FormatProperties { unimplemented!() }
+
     fn create_buffer_raw(&mut self, _: buffer::Info) -> Result<handle::RawBuffer<R>, buffer::CreationError> { unimplemented!() }
     fn create_buffer_immutable_raw(&mut self, data: &[u8], stride: usize, _: buffer::Role, _: Bind)
                                    -> Result<handle::RawBuffer<R>, buffer::CreationError> { unimplemented!() }
@@ -44,6 +46,14 @@ impl d::Device<R> for Device {
         unimplemented!()
     }
 
+    fn create_query(&mut self) -> handle::Query<R> {
+        unimplemented!()
+    }
+
+    fn get_query_result(&mut self, _: &handle::Query<R>, _: bool) -> Option<u64> {
+        unimplemented!()
+    }
+
     fn reset_fences(&mut self, fences: &[&handle::Fence<R>]) {
         unimplemented!()
     }
@@ -99,7 +99,7 @@ impl core::Adapter<Backend> for Adapter {
                             device: device.clone(),
                             list_type: winapi::D3D12_COMMAND_LIST_TYPE_DIRECT, // TODO
                             frame_handles: handle::Manager::new(),
-                            max_resource_count: Some(999999),
+                            max_resource_count: Some(handle::MAX_FRAME_RESOURCES),
                         }
                     )
                 }
@@ -152,7 +152,8 @@ impl core::CommandQueue<Backend> for CommandQueue {
         self.frame_handles.extend(man);
         match self.max_resource_count {
             Some(c) if self.frame_handles.count() > c => {
-                error!("Way too many resources in the current frame. Did you call Device::cleanup()?");
+                error!("Way too many resources in the current frame ({}). Did you call Device::cleanup()?",
+                       self.frame_handles.counts());
                 self.max_resource_count = None;
             },
             _ => (),
@@ -213,6 +214,7 @@ impl core::Resources for Resources {
     type RenderTargetView = ();
     type DepthStencilView = ();
     type Sampler = ();
+    type Query = ();
     type Fence = ();
     type Semaphore = ();
     type Mapping = Mapping;
@@ -17,8 +17,9 @@
 use gl;
 use core::{self as c, command, state as s};
 use core::target::{ColorValue, Depth, Mirror, Rect, Stencil};
-use {Backend, Buffer, BufferElement, Program, FrameBuffer, Texture,
-     NewTexture, Resources, PipelineState, ResourceView, TargetView};
+use {Backend, Buffer, BufferElement, Program, FrameBuffer, Query, Texture,
+     NewTexture, Resources, PipelineState, ResourceView, StreamOutput, TargetView,
+     UnorderedView};
 
 
 fn primitive_to_gl(primitive: c::Primitive) -> gl::types::GLenum {
@@ -40,6 +41,17 @@ fn primitive_to_gl(primitive: c::Primitive) -> gl::types::GLenum {
 
 pub type Access = gl::types::GLenum;
 
+/// Barriers after writing a storage buffer, for every way of reading it back.
+const BUFFER_BARRIER_BITS: gl::types::GLbitfield =
+    gl::SHADER_STORAGE_BARRIER_BIT | gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT |
+    gl::ELEMENT_ARRAY_BARRIER_BIT | gl::UNIFORM_BARRIER_BIT | gl::COMMAND_BARRIER_BIT |
+    gl::BUFFER_UPDATE_BARRIER_BIT | gl::PIXEL_BUFFER_BARRIER_BIT |
+    gl::TRANSFORM_FEEDBACK_BARRIER_BIT;
+/// Barriers after writing an image, for every way of reading the texture back.
+const IMAGE_BARRIER_BITS: gl::types::GLbitfield =
+    gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT |
+    gl::TEXTURE_UPDATE_BARRIER_BIT | gl::FRAMEBUFFER_BARRIER_BIT;
+
 #[derive(Clone, Copy, Debug)]
 pub struct RawOffset(pub *const gl::types::GLvoid);
 unsafe impl Send for RawOffset {}
@@ -51,24 +63,56 @@ pub struct DataPointer {
     size: u32,
 }
 
+/// The buffer and the element fetched by each attribute slot,
+/// identifying a vertex array object.
+pub type VertexArray = [Option<(Buffer, BufferElement)>; c::MAX_VERTEX_ATTRIBUTES];
+
+/// The offsets of the vertex buffers fetched by each attribute slot,
+/// which are left out of the identity of a vertex array object.
+pub type VertexOffsets = [c::pso::BufferOffset; c::MAX_VERTEX_ATTRIBUTES];
+
+/// The place of a vertex array in the data buffer.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VertexArrayPointer(u32);
+
 #[derive(Clone)]
-pub struct DataBuffer(Vec<u8>);
+pub struct DataBuffer {
+    bytes: Vec<u8>,
+    vertex_arrays: Vec<(VertexArray, VertexOffsets)>,
+}
 impl DataBuffer {
     /// Create a new empty data buffer.
     pub fn new() -> DataBuffer {
-        DataBuffer(Vec::new())
+        DataBuffer {
+            bytes: Vec::new(),
+            vertex_arrays: Vec::new(),
+        }
     }
     /// Copy a given vector slice into the buffer.
     fn add(&mut self, data: &[u8]) -> DataPointer {
-        self.0.extend_from_slice(data);
+        self.bytes.extend_from_slice(data);
         DataPointer {
-            offset: (self.0.len() - data.len()) as u32,
+            offset: (self.bytes.len() - data.len()) as u32,
             size: data.len() as u32,
         }
     }
     /// Return a reference to a stored data object.
     pub fn get(&self, ptr: DataPointer) -> &[u8] {
-        &self.0[ptr.offset as usize..(ptr.offset + ptr.size) as usize]
+        &self.bytes[ptr.offset as usize..(ptr.offset + ptr.size) as usize]
+    }
+    /// Store a vertex array, which is too large to be a part of the command.
+    pub fn add_vertex_array(&mut self, vertex_array: VertexArray, offsets: VertexOffsets)
+                            -> VertexArrayPointer {
+        self.vertex_arrays.push((vertex_array, offsets));
+        VertexArrayPointer(self.vertex_arrays.len() as u32 - 1)
+    }
+    /// Return a reference to a stored vertex array, with the buffer offsets.
+    pub fn get_vertex_array(&self, ptr: VertexArrayPointer) -> &(VertexArray, VertexOffsets) {
+        &self.vertex_arrays[ptr.0 as usize]
+    }
+    fn clear(&mut self) {
+        self.bytes.clear();
+        self.vertex_arrays.clear();
     }
 }
 
@@ -100,18 +144,19 @@ pub enum Command {
     BindSampler(c::pso::SamplerParam<Resources>, Option<gl::types::GLenum>),
     BindPixelTargets(c::pso::PixelTargetSet<Resources>),
     BindVao,
-    BindAttribute(c::AttributeSlot, Buffer, BufferElement),
-    UnbindAttribute(c::AttributeSlot),
+    BindVertexArray(VertexArrayPointer),
     BindIndex(Buffer),
     BindFrameBuffer(Access, FrameBuffer),
     BindUniform(c::shade::Location, c::shade::UniformValue),
     SetDrawColorBuffers(c::ColorSlot),
     SetRasterizer(s::Rasterizer),
-    SetViewport(Rect),
+    SetMultisample(c::pso::MultisampleInfo),
+    SetViewport(u8, command::Viewport),
     SetScissor(Option<Rect>),
     SetDepthState(Option<s::Depth>),
     SetStencilState(Option<s::Stencil>, (Stencil, Stencil), s::CullFace),
-    SetBlendState(c::ColorSlot, s::Color),
+    SetBlendState(c::ColorSlot, s::Color, c::pso::DualSource),
+    SetLogicOp(Option<c::pso::LogicOp>),
     SetBlendColor(ColorValue),
     SetPatches(c::PatchSize),
     CopyBuffer(Buffer, Buffer,
@@ -135,6 +180,16 @@ pub enum Command {
                   DataPointer,
                   c::texture::RawImageInfo),
     GenerateMipmap(ResourceView),
+    // queries
+    BeginQuery(Query),
+    EndQuery,
+    BeginConditional(Query, gl::types::GLenum),
+    EndConditional,
+    // transform feedback
+    BeginStreamOutput(Buffer, StreamOutput),
+    EndStreamOutput,
+    // synchronization
+    MemoryBarrier(gl::types::GLbitfield),
     // drawing
     Clear(Option<command::ClearColor>, Option<Depth>, Option<Stencil>),
     Draw(gl::types::GLenum, c::VertexCount, c::VertexCount, Option<command::InstanceParams>),
@@ -152,10 +207,16 @@ pub const COLOR_DEFAULT: s::Color = s::Color {
     blend: None,
 };
 
-pub const RESET: [Command; 14] = [
+pub const DUAL_SOURCE_DEFAULT: c::pso::DualSource = c::pso::DualSource {
+    color_source: false,
+    color_destination: false,
+    alpha_source: false,
+    alpha_destination: false,
+};
+
+pub const RESET: [Command; 16] = [
     Command::BindProgram(0),
     Command::BindVao,
-    // Command::UnbindAttribute, //not needed, handled by the cache
     Command::BindIndex(0),
     Command::BindFrameBuffer(gl::FRAMEBUFFER, 0),
     Command::SetRasterizer(s::Rasterizer {
@@ -165,19 +226,29 @@ pub const RESET: [Command; 14] = [
         offset: None,
         samples: None,
     }),
-    Command::SetViewport(Rect {
-        x: 0,
-        y: 0,
-        w: 0,
-        h: 0,
+    Command::SetMultisample(c::pso::MultisampleInfo {
+        alpha_to_coverage: false,
+        sample_mask: !0,
+        sample_shading: None,
+    }),
+    Command::SetViewport(0, command::Viewport {
+        rect: Rect {
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+        },
+        min_depth: 0.0,
+        max_depth: 1.0,
     }),
     Command::SetScissor(None),
     Command::SetDepthState(None),
     Command::SetStencilState(None, (0, 0), s::CullFace::Nothing),
-    Command::SetBlendState(0, COLOR_DEFAULT),
-    Command::SetBlendState(1, COLOR_DEFAULT),
-    Command::SetBlendState(2, COLOR_DEFAULT),
-    Command::SetBlendState(3, COLOR_DEFAULT),
+    Command::SetBlendState(0, COLOR_DEFAULT, DUAL_SOURCE_DEFAULT),
+    Command::SetBlendState(1, COLOR_DEFAULT, DUAL_SOURCE_DEFAULT),
+    Command::SetBlendState(2, COLOR_DEFAULT, DUAL_SOURCE_DEFAULT),
+    Command::SetBlendState(3, COLOR_DEFAULT, DUAL_SOURCE_DEFAULT),
+    Command::SetLogicOp(None),
     Command::SetBlendColor([0f32; 4]),
 ];
 
@@ -185,6 +256,7 @@ struct Cache {
     primitive: gl::types::GLenum,
     index_type: c::IndexType,
     current_vbs: Option<c::pso::VertexBufferSet<Resources>>,
+    vertex_array: Option<(VertexArray, VertexOffsets)>,
     attributes: [Option<BufferElement>; c::MAX_VERTEX_ATTRIBUTES],
     resource_binds: [Option<gl::types::GLenum>; c::MAX_RESOURCE_VIEWS],
     scissor: bool,
@@ -199,12 +271,22 @@ struct Cache {
     resource_view: Option<c::pso::ResourceViewParam<Resources>>,
     scissor_test: Option<Rect>,
     depth_state: Option<s::Depth>,
-    blend_state: Option<(c::ColorSlot, s::Color)>,
+    blend_state: Option<(c::ColorSlot, s::Color, c::pso::DualSource)>,
+    logic_op: Option<Option<c::pso::LogicOp>>,
     blend_color: Option<ColorValue>,
-    viewport: Option<Rect>,
+    viewports: [Option<command::Viewport>; c::MAX_VIEWPORTS],
     rasterizer: Option<s::Rasterizer>,
+    multisample: Option<c::pso::MultisampleInfo>,
     framebuffer: Option<(Access, FrameBuffer)>,
     index: Buffer,
+    stream_output: Option<StreamOutput>,
+    stream_buffer: Option<Buffer>,
+    /// Whether the capture of the vertices has started and is continued by the draw calls.
+    stream_active: bool,
+    /// Whether the conditional rendering has started.
+    conditional: bool,
+    /// Barriers covering the ways the writes of the bound unordered views can be read.
+    unordered_barriers: gl::types::GLbitfield,
 }
 
 impl Cache {
@@ -213,6 +295,7 @@ impl Cache {
             primitive: 0,
             index_type: c::IndexType::U16,
             current_vbs: None,
+            vertex_array: None,
             attributes: [None; c::MAX_VERTEX_ATTRIBUTES],
             resource_binds: [None; c::MAX_RESOURCE_VIEWS],
             scissor: false,
@@ -228,11 +311,18 @@ impl Cache {
             scissor_test: None,
             depth_state: None,
             blend_state: None,
+            logic_op: None,
             blend_color: None,
-            viewport: None,
+            viewports: [None; c::MAX_VIEWPORTS],
             rasterizer: None,
+            multisample: None,
             framebuffer: None,
             index: 0,
+            stream_output: None,
+            stream_buffer: None,
+            stream_active: false,
+            conditional: false,
+            unordered_barriers: 0,
         }
     }
 
@@ -285,12 +375,26 @@ impl Cache {
         Some(Command::SetRasterizer(rasterizer))
     }
 
-    fn set_viewport(&mut self, rect: Rect) -> Option<Command> {
-        if self.viewport == Some(rect) {
+    fn set_multisample(&mut self, multisample: c::pso::MultisampleInfo) -> Option<Command> {
+        if self.multisample == Some(multisample) {
             return None;
         }
-        self.viewport = Some(rect);
-        Some(Command::SetViewport(rect))
+        self.multisample = Some(multisample);
+        Some(Command::SetMultisample(multisample))
+    }
+
+    fn set_viewport(&mut self, index: usize, viewport: command::Viewport) -> Option<Command> {
+        if self.viewports[index] == Some(viewport) {
+            return None;
+        }
+        if index == 0 {
+            // `glViewport` resets all the viewports when the arrays are not supported
+            for vp in self.viewports[1..].iter_mut() {
+                *vp = None;
+            }
+        }
+        self.viewports[index] = Some(viewport);
+        Some(Command::SetViewport(index as u8, viewport))
     }
 
     fn set_scissor(&mut self, rect: Option<Rect>) -> Option<Command> {
@@ -318,12 +422,20 @@ impl Cache {
         // For now though, we just don't handle it.
         Some(Command::SetStencilState(option_stencil, stencils, cullface))
     }
-    fn set_blend_state(&mut self, color_slot: c::ColorSlot, color: s::Color) -> Option<Command> {
-        if self.blend_state == Some((color_slot, color)) {
+    fn set_blend_state(&mut self, color_slot: c::ColorSlot, color: s::Color,
+                       dual: c::pso::DualSource) -> Option<Command> {
+        if self.blend_state == Some((color_slot, color, dual)) {
+            return None;
+        }
+        self.blend_state = Some((color_slot, color, dual));
+        Some(Command::SetBlendState(color_slot, color, dual))
+    }
+    fn set_logic_op(&mut self, op: Option<c::pso::LogicOp>) -> Option<Command> {
+        if self.logic_op == Some(op) {
             return None;
         }
-        self.blend_state = Some((color_slot, color));
-        Some(Command::SetBlendState(color_slot, color))
+        self.logic_op = Some(op);
+        Some(Command::SetLogicOp(op))
     }
     fn set_blend_color(&mut self, color_value: ColorValue) -> Option<Command> {
         if self.blend_color == Some(color_value) {
@@ -354,7 +466,6 @@ pub struct RawCommandBuffer {
     /// etc.) so that rendering to it can occur immediately.
     pub display_fb: FrameBuffer,
     cache: Cache,
-    active_attribs: usize,
 }
 
 impl command::CommandBuffer<Backend> for RawCommandBuffer {
@@ -374,7 +485,6 @@ impl RawCommandBuffer {
             fbo: fbo,
             display_fb: 0 as FrameBuffer,
             cache: Cache::new(),
-            active_attribs: 0,
         }
     }
     fn is_main_target(&self, tv: Option<TargetView>) -> bool {
@@ -384,34 +494,77 @@ impl RawCommandBuffer {
             Some(_) => false,
         }
     }
+    /// Start capturing the vertices of a draw call, if the pipeline has a stream output.
+    /// The capture is continued by the following draw calls, which write after each other,
+    /// until the pipeline or the buffer changes.
+    fn begin_stream_output(&mut self) {
+        if self.cache.stream_active {
+            return;
+        }
+        match (self.cache.stream_output, self.cache.stream_buffer) {
+            (Some(so), Some(buffer)) => {
+                self.buf.push(Command::BeginStreamOutput(buffer, so));
+                self.cache.stream_active = true;
+            },
+            (Some(_), None) => {
+                error!("The pipeline captures the vertices, but no stream output buffer is bound");
+            },
+            (None, _) => (),
+        }
+    }
+
+    /// Stop the capture of the vertices, if any.
+    fn end_stream_output(&mut self) {
+        if self.cache.stream_active {
+            self.buf.push(Command::EndStreamOutput);
+            self.cache.stream_active = false;
+        }
+    }
+
+    /// Make the shader writes to the unordered views visible to the following commands.
+    fn end_unordered_access(&mut self) {
+        if self.cache.unordered_barriers != 0 {
+            self.buf.push(Command::MemoryBarrier(self.cache.unordered_barriers));
+        }
+    }
 }
 
 impl command::Buffer<Resources> for RawCommandBuffer {
     fn reset(&mut self) {
         self.buf.clear();
-        self.data.0.clear();
+        self.data.clear();
         self.cache = Cache::new();
-        self.active_attribs = (1 << c::MAX_VERTEX_ATTRIBUTES) - 1;
     }
 
     fn bind_pipeline_state(&mut self, pso: PipelineState) {
         let cull = pso.rasterizer.cull_face;
         self.cache.primitive = primitive_to_gl(pso.primitive);
+        if self.cache.attributes != pso.input {
+            // the vertex array depends on the attribute layout
+            self.cache.current_vbs = None;
+        }
         self.cache.attributes = pso.input;
         self.cache.stencil = pso.output.stencil;
         self.cache.cull_face = cull;
         self.cache.draw_mask = pso.output.draw_mask;
+        if pso.program != self.cache.program || pso.stream_output != self.cache.stream_output {
+            // the program can't be changed during the capture
+            self.end_stream_output();
+        }
+        self.cache.stream_output = pso.stream_output;
         self.buf.extend(self.cache.bind_program(pso.program));
         self.cache.scissor = pso.scissor;
         self.buf.extend(self.cache.set_rasterizer(pso.rasterizer));
+        self.buf.extend(self.cache.set_multisample(pso.multisample));
         self.buf.extend(self.cache.set_depth_state(pso.output.depth));
         self.buf.extend(self.cache.set_stencil_state(pso.output.stencil, (0, 0), cull));
         for i in 0..c::MAX_COLOR_TARGETS {
             if pso.output.draw_mask & (1 << i) != 0 {
                 self.buf.extend(self.cache.set_blend_state(i as c::ColorSlot,
-                                           pso.output.colors[i]));
+                                           pso.output.colors[i], pso.output.dual_sources[i]));
             }
         }
+        self.buf.extend(self.cache.set_logic_op(pso.output.logic_op));
         if let c::Primitive::PatchList(num) = pso.primitive {
             self.buf.push(Command::SetPatches(num));
         }
@@ -422,26 +575,26 @@ impl command::Buffer<Resources> for RawCommandBuffer {
             return;
         }
         self.cache.current_vbs = Some(vbs);
+        let mut vertex_array = [None; c::MAX_VERTEX_ATTRIBUTES];
+        let mut offsets = [0; c::MAX_VERTEX_ATTRIBUTES];
         for i in 0..c::MAX_VERTEX_ATTRIBUTES {
             match (vbs.0[i], self.cache.attributes[i]) {
                 (None, Some(fm)) => {
                     error!("No vertex input provided for slot {} of format {:?}", i, fm)
                 }
-                (Some((buffer, offset)), Some(mut bel)) => {
-                    bel.elem.offset += offset as gl::types::GLuint;
-                    self.buf.push(Command::BindAttribute(
-                        i as c::AttributeSlot,
-                        buffer,
-                        bel));
-                    self.active_attribs |= 1 << i;
-                }
-                (_, None) if self.active_attribs & (1 << i) != 0 => {
-                    self.buf.push(Command::UnbindAttribute(i as c::AttributeSlot));
-                    self.active_attribs ^= 1 << i;
+                (Some((buffer, offset)), Some(bel)) => {
+                    vertex_array[i] = Some((buffer, bel));
+                    offsets[i] = offset;
                 }
                 (_, None) => (),
             }
         }
+        if self.cache.vertex_array == Some((vertex_array, offsets)) {
+            return;
+        }
+        self.cache.vertex_array = Some((vertex_array, offsets));
+        let pointer = self.data.add_vertex_array(vertex_array, offsets);
+        self.buf.push(Command::BindVertexArray(pointer));
     }
 
     fn bind_constant_buffers(&mut self, cbs: &[c::pso::ConstantBufferParam<Resources>]) {
@@ -458,13 +611,27 @@ impl command::Buffer<Resources> for RawCommandBuffer {
         for i in 0..c::MAX_RESOURCE_VIEWS {
             self.cache.resource_binds[i] = None;
         }
-        for param in srvs.iter() {
+        for (i, param) in srvs.iter().enumerate() {
+            let view = param.0;
+            // the depth and the stencil of a texture without views can't be read together
+            let conflict = srvs[.. i].iter().any(|other| {
+                other.0.object == view.object && other.0.depth_stencil_mode != view.depth_stencil_mode
+            });
+            if conflict {
+                error!("Texture {} is read as both depth and stencil, which requires texture views",
+                       view.object);
+                continue;
+            }
             self.cache.resource_binds[param.2 as usize] = Some(param.0.bind);
             self.buf.extend(self.cache.bind_resource_view(param.clone()));
         }
     }
 
     fn bind_unordered_views(&mut self, uavs: &[c::pso::UnorderedViewParam<Resources>]) {
+        self.cache.unordered_barriers = uavs.iter().fold(0, |bits, param| bits | match param.0 {
+            UnorderedView::Buffer(_) => BUFFER_BARRIER_BITS,
+            UnorderedView::Image(..) => IMAGE_BARRIER_BITS,
+        });
         for param in uavs.iter() {
             self.buf.push(Command::BindUnorderedView(param.clone()));
         }
@@ -496,18 +663,45 @@ impl command::Buffer<Resources> for RawCommandBuffer {
         let view = pts.get_view();
         self.cache.target_dim = view;
         self.buf.extend(
-            self.cache.set_viewport(Rect {
+            self.cache.set_viewport(0, Rect {
                                     x: 0,
                                     y: 0,
                                     w: view.0,
                                     h: view.1,
-                                }));
+                                }.into()));
     }
 
     fn bind_index(&mut self, buf: Buffer, itype: c::IndexType) {
         self.buf.extend(self.cache.bind_index(buf, itype));
     }
 
+    fn bind_stream_output(&mut self, buf: Option<Buffer>) {
+        if buf != self.cache.stream_buffer {
+            self.end_stream_output();
+        }
+        self.cache.stream_buffer = buf;
+    }
+
+    fn set_viewports(&mut self, viewports: &[command::Viewport]) {
+        use std::cmp;
+        if viewports.len() > c::MAX_VIEWPORTS {
+            error!("Too many viewports: {}, only {} are supported", viewports.len(), c::MAX_VIEWPORTS);
+        }
+        let target_dim = self.cache.target_dim;
+        for (i, vp) in viewports.iter().take(c::MAX_VIEWPORTS).enumerate() {
+            let rect = vp.rect;
+            let viewport = command::Viewport {
+                rect: Rect {
+                    // inverting the Y axis in order to match D3D11
+                    y: cmp::max(target_dim.1, rect.y + rect.h) - rect.y - rect.h,
+                    ..rect
+                },
+                ..*vp
+            };
+            self.buf.extend(self.cache.set_viewport(i, viewport));
+        }
+    }
+
     fn set_scissor(&mut self, rect: Rect) {
         use std::cmp;
         let scissor = self.cache.scissor;
@@ -541,6 +735,8 @@ impl command::Buffer<Resources> for RawCommandBuffer {
                    src_offset_bytes: usize,
                    dst_offset_bytes: usize,
                    size_bytes: usize) {
+        // the captured buffer can't be accessed until the capture ends
+        self.end_stream_output();
         self.buf.push(Command::CopyBuffer(src, dst,
                                           src_offset_bytes as gl::types::GLintptr,
                                           dst_offset_bytes as gl::types::GLintptr,
@@ -570,6 +766,7 @@ impl command::Buffer<Resources> for RawCommandBuffer {
                               face: Option<c::texture::CubeFace>,
                               img: c::texture::RawImageInfo,
                               dst: Buffer, dst_offset_bytes: usize) {
+        self.end_stream_output();
         self.buf.push(Command::CopyTextureToBuffer(
             src, kind, face, img,
             dst, dst_offset_bytes as gl::types::GLintptr
@@ -577,6 +774,7 @@ impl command::Buffer<Resources> for RawCommandBuffer {
     }
 
     fn update_buffer(&mut self, buf: Buffer, data: &[u8], offset_bytes: usize) {
+        self.end_stream_output();
         let ptr = self.data.add(data);
         self.buf.push(Command::UpdateBuffer(buf, ptr, offset_bytes));
     }
@@ -604,6 +802,7 @@ impl command::Buffer<Resources> for RawCommandBuffer {
 
     fn clear_color(&mut self, target: TargetView, value: command::ClearColor) {
         // this could be optimized by deferring the actual clear call
+        self.end_stream_output();
         let mut pts = c::pso::PixelTargetSet::new();
         pts.colors[0] = Some(target);
         self.bind_pixel_targets(pts);
@@ -614,6 +813,7 @@ impl command::Buffer<Resources> for RawCommandBuffer {
                            target: TargetView,
                            depth: Option<Depth>,
                            stencil: Option<Stencil>) {
+        self.end_stream_output();
         let mut pts = c::pso::PixelTargetSet::new();
         if depth.is_some() {
             pts.depth = Some(target);
@@ -625,11 +825,49 @@ impl command::Buffer<Resources> for RawCommandBuffer {
         self.buf.push(Command::Clear(None, depth, stencil));
     }
 
+    fn begin_query(&mut self, query: Query) {
+        // the queries are not supported, which was reported on their creation
+        if query != 0 {
+            self.buf.push(Command::BeginQuery(query));
+        }
+    }
+
+    fn end_query(&mut self, query: Query) {
+        if query != 0 {
+            self.buf.push(Command::EndQuery);
+        }
+    }
+
+    fn begin_conditional(&mut self, query: Query, mode: command::ConditionalMode) {
+        use core::command::ConditionalMode::*;
+        // without a query the draw calls are executed unconditionally
+        self.cache.conditional = query != 0;
+        if !self.cache.conditional {
+            return;
+        }
+        let mode = match mode {
+            Wait => gl::QUERY_WAIT,
+            NoWait => gl::QUERY_NO_WAIT,
+            ByRegionWait => gl::QUERY_BY_REGION_WAIT,
+            ByRegionNoWait => gl::QUERY_BY_REGION_NO_WAIT,
+        };
+        self.buf.push(Command::BeginConditional(query, mode));
+    }
+
+    fn end_conditional(&mut self) {
+        if self.cache.conditional {
+            self.buf.push(Command::EndConditional);
+            self.cache.conditional = false;
+        }
+    }
+
     fn call_draw(&mut self,
                  start: c::VertexCount,
                  count: c::VertexCount,
                  instances: Option<command::InstanceParams>) {
+        self.begin_stream_output();
         self.buf.push(Command::Draw(self.cache.primitive, start, count, instances));
+        self.end_unordered_access();
     }
 
     fn call_draw_indexed(&mut self,
@@ -641,6 +879,7 @@ impl command::Buffer<Resources> for RawCommandBuffer {
             c::IndexType::U16 => (start * 2u32, gl::UNSIGNED_SHORT),
             c::IndexType::U32 => (start * 4u32, gl::UNSIGNED_INT),
         };
+        self.begin_stream_output();
         self.buf.push(
                   Command::DrawIndexed(
                       self.cache.primitive,
@@ -649,6 +888,7 @@ impl command::Buffer<Resources> for RawCommandBuffer {
                       count,
                       base,
                       instances));
+        self.end_unordered_access();
     }
 }
 
@@ -675,3 +915,155 @@ impl command::CommandBuffer<Backend> for SubpassCommandBuffer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::IndexType;
+    use core::command::{Buffer, ConditionalMode, Viewport};
+    use core::target::Rect;
+    use core::pso::{ResourceViewParam, UnorderedViewParam};
+    use core::shade::Usage;
+    use core::texture::{AaMode, Kind};
+    use gl;
+    use {ResourceView, StreamOutput, UnorderedView};
+    use super::{Command, RawCommandBuffer, BUFFER_BARRIER_BITS, IMAGE_BARRIER_BITS};
+
+    #[test]
+    fn test_cache_redundancy() {
+        let mut cb = RawCommandBuffer::new(0);
+        cb.reset();
+        cb.bind_index(1, IndexType::U16);
+        cb.bind_index(1, IndexType::U16);
+        cb.bind_index(2, IndexType::U16);
+        let viewport = Viewport {
+            rect: Rect { x: 0, y: 0, w: 64, h: 64 },
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        cb.set_viewports(&[viewport]);
+        cb.set_viewports(&[viewport]);
+
+        let indices = cb.buf.iter().filter_map(|cmd| match *cmd {
+            Command::BindIndex(buffer) => Some(buffer),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(indices, vec![1, 2]);
+        assert_eq!(cb.buf.iter().filter(|cmd| match **cmd {
+            Command::SetViewport(..) => true,
+            _ => false,
+        }).count(), 1);
+    }
+
+    #[test]
+    fn test_cache_viewport_reset() {
+        let mut cb = RawCommandBuffer::new(0);
+        cb.reset();
+        let first = Viewport {
+            rect: Rect { x: 0, y: 0, w: 64, h: 64 },
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let second = Viewport {
+            rect: Rect { x: 64, y: 0, w: 64, h: 64 },
+            .. first
+        };
+        let third = Viewport {
+            rect: Rect { x: 0, y: 0, w: 32, h: 32 },
+            .. first
+        };
+        cb.set_viewports(&[first, second]);
+        cb.set_viewports(&[third, second]);
+
+        let indices = cb.buf.iter().filter_map(|cmd| match *cmd {
+            Command::SetViewport(index, _) => Some(index),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(indices, vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_stream_output_continued() {
+        let mut cb = RawCommandBuffer::new(0);
+        cb.reset();
+        cb.cache.stream_output = Some(StreamOutput {
+            mode: gl::POINTS,
+            discard: true,
+        });
+        cb.bind_stream_output(Some(1));
+        cb.call_draw(0, 4, None);
+        cb.bind_stream_output(Some(1));
+        cb.call_draw(4, 4, None);
+        cb.bind_stream_output(Some(2));
+        cb.call_draw(0, 4, None);
+
+        let captures = cb.buf.iter().filter_map(|cmd| match *cmd {
+            Command::BeginStreamOutput(buffer, _) => Some(Some(buffer)),
+            Command::EndStreamOutput => Some(None),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(captures, vec![Some(1), None, Some(2)]);
+    }
+
+    #[test]
+    fn test_unsupported_query() {
+        let mut cb = RawCommandBuffer::new(0);
+        cb.reset();
+        cb.begin_query(0);
+        cb.end_query(0);
+        cb.begin_conditional(0, ConditionalMode::Wait);
+        cb.end_conditional();
+        assert!(cb.buf.is_empty());
+
+        cb.begin_query(1);
+        cb.end_query(1);
+        cb.begin_conditional(1, ConditionalMode::Wait);
+        cb.end_conditional();
+        assert_eq!(cb.buf.len(), 4);
+    }
+
+    #[test]
+    fn test_depth_stencil_conflict() {
+        let mut cb = RawCommandBuffer::new(0);
+        cb.reset();
+        let kind = Kind::D2(64, 64, AaMode::Single);
+        let mut depth = ResourceView::new_texture(1, kind);
+        depth.depth_stencil_mode = Some(gl::DEPTH_COMPONENT);
+        let mut stencil = ResourceView::new_texture(1, kind);
+        stencil.depth_stencil_mode = Some(gl::STENCIL_INDEX);
+        let other = ResourceView::new_texture(2, kind);
+        cb.bind_resource_views(&[
+            ResourceViewParam(depth, Usage::empty(), 0),
+            ResourceViewParam(stencil, Usage::empty(), 1),
+            ResourceViewParam(other, Usage::empty(), 2),
+        ]);
+
+        let slots = cb.buf.iter().filter_map(|cmd| match *cmd {
+            Command::BindResourceView(ResourceViewParam(_, _, slot)) => Some(slot),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(slots, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_unordered_barriers() {
+        let mut cb = RawCommandBuffer::new(0);
+        cb.reset();
+        cb.bind_unordered_views(&[]);
+        cb.call_draw(0, 3, None);
+        cb.bind_unordered_views(&[
+            UnorderedViewParam(UnorderedView::Image(1, gl::RGBA8), Usage::empty(), 0),
+        ]);
+        cb.call_draw(0, 3, None);
+        cb.bind_unordered_views(&[
+            UnorderedViewParam(UnorderedView::Image(1, gl::RGBA8), Usage::empty(), 0),
+            UnorderedViewParam(UnorderedView::Buffer(2), Usage::empty(), 1),
+        ]);
+        cb.call_draw(0, 3, None);
+
+        let barriers = cb.buf.iter().filter_map(|cmd| match *cmd {
+            Command::MemoryBarrier(bits) => Some(bits),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(barriers, vec![IMAGE_BARRIER_BITS, IMAGE_BARRIER_BITS | BUFFER_BARRIER_BITS]);
+    }
+}
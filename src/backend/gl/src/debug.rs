@@ -0,0 +1,197 @@
+// Copyright 2017 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Forwarding of the driver debug output (`KHR_debug`) into `log`.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::os::raw::c_void;
+use std::{ptr, slice, str};
+use gl;
+use gl::types::{GLchar, GLenum, GLsizei, GLuint};
+
+
+/// Severity of a driver debug message.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    fn from_gl(severity: GLenum) -> Severity {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH   => Severity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => Severity::Medium,
+            gl::DEBUG_SEVERITY_LOW    => Severity::Low,
+            _                         => Severity::Notification,
+        }
+    }
+}
+
+/// Configuration of the debug output, see `Adapter::enable_debug_output`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct DebugConfig {
+    /// Messages with a lower severity are ignored.
+    pub min_severity: Severity,
+    /// Panic on a `High` severity message or a GL error.
+    /// The panic happens after the offending command, so it can be named.
+    pub panic_on_high: bool,
+    /// Check `glGetError` after every command executed by the queues, even in
+    /// release builds. This is forced on when `KHR_debug` is not supported.
+    /// The device calls creating resources are only checked in debug builds.
+    pub check_commands: bool,
+}
+
+impl Default for DebugConfig {
+    fn default() -> DebugConfig {
+        DebugConfig {
+            min_severity: Severity::Low,
+            panic_on_high: false,
+            check_commands: false,
+        }
+    }
+}
+
+fn source_to_str(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API             => "API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM   => "Window System",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "Shader Compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY     => "Third Party",
+        gl::DEBUG_SOURCE_APPLICATION     => "Application",
+        _                                => "Other",
+    }
+}
+
+fn type_to_str(ty: GLenum) -> &'static str {
+    match ty {
+        gl::DEBUG_TYPE_ERROR               => "Error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "Deprecated Behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR  => "Undefined Behavior",
+        gl::DEBUG_TYPE_PORTABILITY         => "Portability",
+        gl::DEBUG_TYPE_PERFORMANCE         => "Performance",
+        gl::DEBUG_TYPE_MARKER              => "Marker",
+        gl::DEBUG_TYPE_PUSH_GROUP          => "Push Group",
+        gl::DEBUG_TYPE_POP_GROUP           => "Pop Group",
+        _                                  => "Other",
+    }
+}
+
+/// Receiver of the debug messages. Has to stay at the same address
+/// for as long as the callback is installed.
+pub struct Messenger {
+    config: DebugConfig,
+    /// Messages that were already reported, by (source, type, id).
+    seen: RefCell<HashSet<(GLenum, GLenum, GLuint)>>,
+    /// A high severity message waiting to be turned into a panic.
+    fatal: RefCell<Option<String>>,
+}
+
+impl Messenger {
+    /// Create a new messenger and install it as the debug callback of the current context.
+    pub fn install(gl: &gl::Gl, config: DebugConfig) -> Box<Messenger> {
+        let messenger = Box::new(Messenger {
+            config: config,
+            seen: RefCell::new(HashSet::new()),
+            fatal: RefCell::new(None),
+        });
+        unsafe {
+            gl.Enable(gl::DEBUG_OUTPUT);
+            // deliver the messages on the thread and in the call that caused them
+            gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+            gl.DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE,
+                                   0, ptr::null(), gl::TRUE);
+            gl.DebugMessageCallback(Some(callback), &*messenger as *const Messenger as *const c_void);
+        }
+        messenger
+    }
+
+    /// Return the pending high severity message, if any.
+    pub fn take_fatal(&self) -> Option<String> {
+        self.fatal.borrow_mut().take()
+    }
+
+    fn receive(&self, source: GLenum, ty: GLenum, id: GLuint, severity: Severity, message: &str) {
+        if severity < self.config.min_severity {
+            return
+        }
+        if !self.seen.borrow_mut().insert((source, ty, id)) {
+            return
+        }
+        let source = source_to_str(source);
+        let ty = type_to_str(ty);
+        match severity {
+            Severity::High => error!("GL [{}] {} #{}: {}", source, ty, id, message),
+            Severity::Medium => warn!("GL [{}] {} #{}: {}", source, ty, id, message),
+            Severity::Low => info!("GL [{}] {} #{}: {}", source, ty, id, message),
+            Severity::Notification => debug!("GL [{}] {} #{}: {}", source, ty, id, message),
+        }
+        if severity == Severity::High && self.config.panic_on_high {
+            // unwinding through the driver is not allowed, so defer the panic
+            *self.fatal.borrow_mut() = Some(format!("GL [{}] {} #{}: {}", source, ty, id, message));
+        }
+    }
+}
+
+extern "system" fn callback(source: GLenum, ty: GLenum, id: GLuint, severity: GLenum,
+                            length: GLsizei, message: *const GLchar, user: *mut c_void) {
+    if user.is_null() || message.is_null() {
+        return
+    }
+    let messenger = unsafe { &*(user as *const Messenger) };
+    let bytes = unsafe { slice::from_raw_parts(message as *const u8, length as usize) };
+    let text = str::from_utf8(bytes).unwrap_or("<invalid UTF-8>");
+    messenger.receive(source, ty, id, Severity::from_gl(severity), text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DebugConfig, Messenger, Severity};
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use gl;
+
+    fn messenger(config: DebugConfig) -> Messenger {
+        Messenger {
+            config: config,
+            seen: RefCell::new(HashSet::new()),
+            fatal: RefCell::new(None),
+        }
+    }
+
+    #[test]
+    fn test_severity_order() {
+        assert_eq!(Severity::from_gl(gl::DEBUG_SEVERITY_HIGH), Severity::High);
+        assert_eq!(Severity::from_gl(gl::DEBUG_SEVERITY_NOTIFICATION), Severity::Notification);
+        assert!(Severity::Notification < Severity::Low);
+        assert!(Severity::Medium < Severity::High);
+    }
+
+    #[test]
+    fn test_fatal_deduplicated() {
+        let m = messenger(DebugConfig {
+            panic_on_high: true,
+            .. DebugConfig::default()
+        });
+        m.receive(gl::DEBUG_SOURCE_API, gl::DEBUG_TYPE_ERROR, 7, Severity::High, "boom");
+        assert!(m.take_fatal().is_some());
+        m.receive(gl::DEBUG_SOURCE_API, gl::DEBUG_TYPE_ERROR, 7, Severity::High, "boom");
+        assert!(m.take_fatal().is_none());
+        m.receive(gl::DEBUG_SOURCE_API, gl::DEBUG_TYPE_ERROR, 8, Severity::Low, "meh");
+        assert!(m.take_fatal().is_none());
+    }
+}
@@ -13,7 +13,10 @@
 // limitations under the License.
 
 use std::rc::Rc;
-use std::{slice, ptr};
+use std::{mem, slice, ptr};
+use std::ops::Range;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use {gl, tex};
 use core::{self as c, device as d, texture as t, buffer, mapping};
@@ -22,10 +25,10 @@ use core::format::{ChannelType, Format};
 use core::handle::{self, Producer};
 use core::target::{Layer, Level};
 
-use command::{COLOR_DEFAULT};
-use {Info, Resources as R, Share, OutputMerger};
+use command::{COLOR_DEFAULT, DUAL_SOURCE_DEFAULT};
+use {Info, PrivateCaps, Resources as R, Share, OutputMerger};
 use {Buffer, BufferElement, FatSampler, NewTexture,
-     PipelineState, ResourceView, TargetView, Fence};
+     PipelineState, ResourceView, StreamOutput, TargetView, UnorderedView, Fence, Semaphore};
 
 
 pub fn role_to_target(role: buffer::Role) -> gl::types::GLenum {
@@ -93,6 +96,18 @@ impl Device {
         &self.share.info
     }
 
+    /// Get the capabilities that are only used internally by the backend,
+    /// mostly useful for diagnostics.
+    pub fn get_private_caps(&self) -> &PrivateCaps {
+        &self.share.private_caps
+    }
+
+    /// Get the statistics of the resources created by this device.
+    /// Creation and destruction counts are relative to the previous call.
+    pub fn get_resource_report(&self) -> handle::Report {
+        self.share.handles.borrow_mut().report()
+    }
+
     fn create_buffer_internal(&mut self) -> Buffer {
         let gl = &self.share.context;
         let mut name = 0 as Buffer;
@@ -116,13 +131,14 @@ impl Device {
             0 as *const gl::types::GLvoid
         };
 
+        let persistent = gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
         if self.share.private_caps.buffer_storage_supported {
             let usage = match info.usage {
                 Data => 0,
-                // TODO: we could use mapping instead of glBufferSubData
-                Dynamic => gl::DYNAMIC_STORAGE_BIT,
-                Upload => access_to_map_bits(memory::WRITE) | gl::MAP_PERSISTENT_BIT,
-                Download => access_to_map_bits(memory::READ) | gl::MAP_PERSISTENT_BIT,
+                // still updatable with `glBufferSubData`
+                Dynamic => gl::DYNAMIC_STORAGE_BIT | access_to_map_bits(memory::WRITE) | persistent,
+                Upload => access_to_map_bits(memory::WRITE) | persistent,
+                Download => access_to_map_bits(memory::READ) | persistent,
             };
             let size = if info.size == 0 {
                 // we are not allowed to pass size=0 into `glBufferStorage`
@@ -161,18 +177,18 @@ impl Device {
         }
 
         let mapping_access = match info.usage {
-            Data | Dynamic => None,
-            Upload => Some(memory::WRITE),
+            Data => None,
+            // the temporary mappings would get in the way of `glBufferSubData`
+            Dynamic if !self.share.private_caps.buffer_storage_supported => None,
+            Dynamic | Upload => Some(memory::WRITE),
             Download => Some(memory::READ),
         };
 
         mapping_access.map(|access| {
             let (kind, ptr) = if self.share.private_caps.buffer_storage_supported {
-                let mut gl_access = access_to_map_bits(access) |
-                                    gl::MAP_PERSISTENT_BIT;
-                if access.contains(memory::WRITE) {
-                    gl_access |= gl::MAP_FLUSH_EXPLICIT_BIT;
-                }
+                // coherent, so it stays mapped for the lifetime of the buffer
+                // without any flush, the accesses being guarded by fences
+                let gl_access = access_to_map_bits(access) | persistent;
                 let size = info.size as isize;
                 let ptr = unsafe {
                     gl.BindBuffer(target, buffer);
@@ -221,7 +237,7 @@ impl Device {
             },
         };
         let result = create_program(&self.share.context, &self.share.capabilities,
-                                    &self.share.private_caps, shader_slice, usage);
+                                    &self.share.private_caps, shader_slice, usage, &[]);
         if let Err(err) = self.share.check() {
             panic!("Error {:?} creating program: {:?}", err, shader_set)
         }
@@ -234,6 +250,23 @@ impl Device {
             (&NewTexture::Surface(_), Some(_)) => Err(d::TargetViewError::Unsupported),
             (&NewTexture::Surface(_), None) if level != 0 => Err(d::TargetViewError::Unsupported),
             (&NewTexture::Surface(s), None) => Ok(TargetView::Surface(s)),
+            (&NewTexture::Texture(name), layer) if !self.share.private_caps.framebuffer_texture_supported => {
+                // only a single image can be attached with `glFramebufferTexture2D`
+                match (htex.get_info().kind, layer) {
+                    (t::Kind::D2(_, _, t::AaMode::Single), None) =>
+                        Ok(TargetView::Texture2D(name, level, gl::TEXTURE_2D)),
+                    (t::Kind::Cube(_), Some(l)) if (l as usize) < t::CUBE_FACES.len() => {
+                        let face = tex::cube_face_to_gl(t::CUBE_FACES[l as usize]);
+                        Ok(TargetView::Texture2D(name, level, face))
+                    },
+                    (_, Some(l)) if !self.share.info.is_gles2_tier() =>
+                        Ok(TargetView::TextureLayer(name, level, l)),
+                    (kind, _) => {
+                        error!("Target view of {:?} at layer {:?} needs `glFramebufferTexture`", kind, layer);
+                        Err(d::TargetViewError::Unsupported)
+                    },
+                }
+            },
             (&NewTexture::Texture(t), Some(l)) => Ok(TargetView::TextureLayer(t, level, l)),
             (&NewTexture::Texture(t), None) => Ok(TargetView::Texture(t, level)),
         }
@@ -303,6 +336,11 @@ impl d::Device<R> for Device {
         &self.share.capabilities
     }
 
+    fn get_format_properties(&self, format: Format) -> c::format::FormatProperties {
+        ::info::get_format_properties(&self.share.info, &self.share.capabilities,
+                                      &self.share.private_caps, format)
+    }
+
     fn create_buffer_raw(&mut self, info: buffer::Info) -> Result<handle::RawBuffer<R>, buffer::CreationError> {
         if !self.share.capabilities.constant_buffer_supported && info.role == buffer::Role::Constant {
             error!("Constant buffers are not supported by this GL version");
@@ -342,6 +380,7 @@ impl d::Device<R> for Device {
     fn create_pipeline_state_raw(&mut self, program: &handle::Program<R>, desc: &c::pso::Descriptor)
                                  -> Result<handle::RawPipelineState<R>, c::pso::CreationError> {
         use core::state as s;
+        use shade::create_stream_output_program;
         let caps = &self.share.capabilities;
         match desc.primitive {
             c::Primitive::PatchList(num) if num == 0 || num > caps.max_patch_size =>
@@ -359,9 +398,15 @@ impl d::Device<R> for Device {
             },
             depth: desc.depth_stencil.and_then(|(_, t)| t.depth),
             colors: [COLOR_DEFAULT; c::MAX_COLOR_TARGETS],
+            dual_sources: [DUAL_SOURCE_DEFAULT; c::MAX_COLOR_TARGETS],
+            logic_op: None,
         };
         for i in 0 .. c::MAX_COLOR_TARGETS {
             if let Some((_, ref bi)) = desc.color_targets[i] {
+                if i >= caps.max_color_targets {
+                    error!("Color slot {} exceeds the supported {} targets", i, caps.max_color_targets);
+                    return Err(c::pso::CreationError);
+                }
                 output.draw_mask |= 1<<i;
                 output.colors[i].mask = bi.mask;
                 if bi.color.is_some() || bi.alpha.is_some() {
@@ -370,22 +415,107 @@ impl d::Device<R> for Device {
                         alpha: bi.alpha.unwrap_or_default(),
                     });
                 }
+                if bi.dual_source.is_used() {
+                    if !caps.dual_source_blending_supported {
+                        error!("Dual-source blending is not supported");
+                        return Err(c::pso::CreationError);
+                    }
+                    if i != 0 {
+                        error!("Dual-source blending is only supported on color slot 0, got {}", i);
+                        return Err(c::pso::CreationError);
+                    }
+                    let max_dual = self.share.private_caps.max_dual_source_draw_buffers;
+                    if i >= max_dual {
+                        error!("Color slot {} exceeds the dual-source draw buffers limit {}", i, max_dual);
+                        return Err(c::pso::CreationError);
+                    }
+                    output.dual_sources[i] = bi.dual_source;
+                }
+                if let Some(op) = bi.logic_op {
+                    if !caps.logic_op_supported {
+                        error!("Logic operations are not supported");
+                        return Err(c::pso::CreationError);
+                    }
+                    // GL has a single logic operation for all the targets
+                    match output.logic_op {
+                        Some(other) if other != op => {
+                            error!("Color targets have different logic operations: {:?} and {:?}",
+                                   other, op);
+                            return Err(c::pso::CreationError);
+                        },
+                        _ => output.logic_op = Some(op),
+                    }
+                }
             }
         }
+        if desc.multisample.sample_shading.is_some() && !self.share.private_caps.sample_shading_supported {
+            warn!("Sample shading is not supported, falling back to per-pixel shading");
+        }
         let mut inputs = [None; c::MAX_VERTEX_ATTRIBUTES];
         for i in 0 .. c::MAX_VERTEX_ATTRIBUTES {
+            if desc.attributes[i].is_some() && i >= caps.max_vertex_attributes {
+                error!("Attribute slot {} exceeds the supported {} attributes", i, caps.max_vertex_attributes);
+                return Err(c::pso::CreationError);
+            }
             inputs[i] = desc.attributes[i].map(|at| BufferElement {
                 desc: desc.vertex_buffers[at.0 as usize].unwrap(),
                 elem: at.1,
             });
         }
+        let mut gl_program = *self.frame_handles.ref_program(program);
+        let stream_output = match desc.stream_output {
+            Some(ref so) => {
+                if !caps.stream_output_supported {
+                    error!("Stream output is not supported");
+                    return Err(c::pso::CreationError);
+                }
+                // the derived programs are shared by the PSOs and owned by the original one
+                let key = (gl_program, *so);
+                let cached = self.share.stream_programs.borrow().get(&key).cloned();
+                let derived = match cached {
+                    Some(derived) => derived,
+                    None => match create_stream_output_program(
+                            &self.share.context, caps, &self.share.private_caps,
+                            gl_program, program.get_info(), so) {
+                        Ok(derived) => {
+                            self.share.stream_programs.borrow_mut().insert(key, derived);
+                            derived
+                        },
+                        Err(e) => {
+                            error!("Unable to create the stream output program: {}", e);
+                            return Err(c::pso::CreationError);
+                        },
+                    },
+                };
+                let mode = match (derived.geometry_mode, desc.primitive) {
+                    (Some(mode), _) => mode,
+                    (None, c::Primitive::PointList) => gl::POINTS,
+                    (None, c::Primitive::LineList) |
+                    (None, c::Primitive::LineStrip) => gl::LINES,
+                    (None, c::Primitive::TriangleList) |
+                    (None, c::Primitive::TriangleStrip) => gl::TRIANGLES,
+                    (None, primitive) => {
+                        error!("Capturing {:?} requires a geometry shader", primitive);
+                        return Err(c::pso::CreationError);
+                    },
+                };
+                gl_program = derived.program;
+                Some(StreamOutput {
+                    mode: mode,
+                    discard: output.draw_mask == 0 && desc.depth_stencil.is_none(),
+                })
+            },
+            None => None,
+        };
         let pso = PipelineState {
-            program: *self.frame_handles.ref_program(program),
+            program: gl_program,
             primitive: desc.primitive,
             input: inputs,
             scissor: desc.scissor,
             rasterizer: desc.rasterizer,
+            multisample: desc.multisample,
             output: output,
+            stream_output: stream_output,
         };
         Ok(self.share.handles.borrow_mut().make_pso(pso, program))
     }
@@ -405,16 +535,39 @@ impl d::Device<R> for Device {
         if dim.1 as usize > max_size {
             return Err(CreationError::Size(dim.1));
         }
+        let kind_supported = match desc.kind {
+            t::Kind::D1(..) | t::Kind::D1Array(..) => !self.share.info.version.is_embedded,
+            t::Kind::D3(..) => self.share.capabilities.max_texture_3d_size != 0,
+            t::Kind::D2Array(..) => self.share.capabilities.max_texture_array_layers > 1,
+            _ => true,
+        };
+        if !kind_supported {
+            return Err(CreationError::Kind);
+        }
         let cty = hint.unwrap_or(ChannelType::Uint); //careful here
+        let is_texture = desc.bind.intersects(SHADER_RESOURCE | UNORDERED_ACCESS) || data_opt.is_some();
+        // the GLES 2.0 formats are mostly provided by extensions
+        if self.share.info.is_gles2_tier() {
+            let props = ::info::get_format_properties(&self.share.info, &self.share.capabilities,
+                                                      caps, Format(desc.format, cty));
+            let required = if is_texture {
+                SHADER_RESOURCE
+            } else {
+                memory::RENDER_TARGET | memory::DEPTH_STENCIL
+            };
+            if !props.bind.intersects(required) {
+                return Err(CreationError::Format(desc.format, Some(cty)));
+            }
+        }
         let gl = &self.share.context;
-        let object = if desc.bind.intersects(SHADER_RESOURCE | UNORDERED_ACCESS) || data_opt.is_some() {
+        let object = if is_texture {
             let name = if caps.immutable_storage_supported {
                 try!(tex::make_with_storage(gl, &desc, cty))
             } else {
-                try!(tex::make_without_storage(gl, &desc, cty))
+                try!(tex::make_without_storage(gl, &desc, cty, caps))
             };
             if let Some(data) = data_opt {
-                try!(tex::init_texture_data(gl, name, desc, cty, data));
+                try!(tex::init_texture_data(gl, name, desc, cty, data, caps));
             }
             NewTexture::Texture(name)
         }else {
@@ -446,26 +599,90 @@ impl d::Device<R> for Device {
         Ok(self.share.handles.borrow_mut().make_buffer_srv(view, hbuf))
     }
 
-    fn view_buffer_as_unordered_access_raw(&mut self, _hbuf: &handle::RawBuffer<R>)
+    fn view_buffer_as_unordered_access_raw(&mut self, hbuf: &handle::RawBuffer<R>)
                                        -> Result<handle::RawUnorderedAccessView<R>, d::ResourceViewError> {
-        Err(d::ResourceViewError::Unsupported) //TODO
+        if !self.share.private_caps.storage_buffer_supported {
+            error!("Shader storage buffers are not supported");
+            return Err(d::ResourceViewError::Unsupported)
+        }
+        let view = UnorderedView::Buffer(*self.frame_handles.ref_buffer(hbuf));
+        Ok(self.share.handles.borrow_mut().make_buffer_uav(view, hbuf))
     }
 
-    fn view_texture_as_shader_resource_raw(&mut self, htex: &handle::RawTexture<R>, _desc: t::ResourceDesc)
+    fn view_texture_as_shader_resource_raw(&mut self, htex: &handle::RawTexture<R>, desc: t::ResourceDesc)
                                        -> Result<handle::RawShaderResourceView<R>, d::ResourceViewError> {
         match self.frame_handles.ref_texture(htex) {
             &NewTexture::Surface(_) => Err(d::ResourceViewError::NoBindFlag),
             &NewTexture::Texture(t) => {
-                //TODO: use the view descriptor
-                let view = ResourceView::new_texture(t, htex.get_info().kind);
+                //TODO: use the rest of the view descriptor
+                let info = htex.get_info();
+                let (mut view, surface) = match desc.surface {
+                    Some(surface) => {
+                        if !info.format.is_view_compatible(surface) {
+                            return Err(d::ResourceViewError::Surface(surface))
+                        }
+                        // the texture views require the immutable storage
+                        let caps = &self.share.private_caps;
+                        if !caps.texture_view_supported || !caps.immutable_storage_supported {
+                            error!("Reinterpreting {:?} as {:?} requires texture views", info.format, surface);
+                            return Err(d::ResourceViewError::Unsupported)
+                        }
+                        let name = try!(tex::make_view(&self.share.context, t, info, Format(surface, desc.channel)));
+                        (ResourceView::new_texture_view(name, info.kind), surface)
+                    },
+                    None => (ResourceView::new_texture(t, info.kind), info.format),
+                };
+                if surface.has_depth() && surface.has_stencil() {
+                    let is_stencil = Format(surface, desc.channel).is_stencil_view();
+                    let caps = &self.share.private_caps;
+                    let mode = if is_stencil {
+                        gl::STENCIL_INDEX
+                    } else {
+                        gl::DEPTH_COMPONENT
+                    };
+                    if !caps.stencil_texturing_supported {
+                        if is_stencil {
+                            return Err(d::ResourceViewError::Channel(desc.channel));
+                        }
+                    } else if caps.texture_view_supported && caps.immutable_storage_supported {
+                        // the mode is a texture state, so each view gets its own texture object
+                        if !view.owned {
+                            let name = try!(tex::make_view(&self.share.context, t, info, Format(surface, desc.channel)));
+                            view = ResourceView::new_texture_view(name, info.kind);
+                        }
+                        let gl = &self.share.context;
+                        unsafe {
+                            gl.BindTexture(view.bind, view.object);
+                            gl.TexParameteri(view.bind, gl::DEPTH_STENCIL_TEXTURE_MODE, mode as gl::types::GLint);
+                        }
+                    } else {
+                        // set when binding, so the views of a texture can't be mixed in a draw call
+                        view.depth_stencil_mode = Some(mode);
+                    }
+                }
                 Ok(self.share.handles.borrow_mut().make_texture_srv(view, htex))
             },
         }
     }
 
-    fn view_texture_as_unordered_access_raw(&mut self, _htex: &handle::RawTexture<R>)
+    fn view_texture_as_unordered_access_raw(&mut self, htex: &handle::RawTexture<R>)
                                         -> Result<handle::RawUnorderedAccessView<R>, d::ResourceViewError> {
-        Err(d::ResourceViewError::Unsupported) //TODO
+        match self.frame_handles.ref_texture(htex) {
+            &NewTexture::Surface(_) => Err(d::ResourceViewError::NoBindFlag),
+            &NewTexture::Texture(t) => {
+                if !self.share.capabilities.unordered_access_view_supported {
+                    error!("Image load/store is not supported");
+                    return Err(d::ResourceViewError::Unsupported)
+                }
+                let format = tex::get_internal_format(&self.share.context, t, htex.get_info().kind);
+                if let Err(err) = self.share.check() {
+                    error!("Error {:?} creating texture UAV: {:?}", err, htex.get_info());
+                    return Err(d::ResourceViewError::Unsupported)
+                }
+                let view = UnorderedView::Image(t, format);
+                Ok(self.share.handles.borrow_mut().make_texture_uav(view, htex))
+            },
+        }
     }
 
     fn view_texture_as_render_target_raw(&mut self, htex: &handle::RawTexture<R>, desc: t::RenderDesc)
@@ -503,7 +720,7 @@ impl d::Device<R> for Device {
     }
 
     fn create_semaphore(&mut self) -> handle::Semaphore<R> {
-        self.share.handles.borrow_mut().make_semaphore(())
+        self.share.handles.borrow_mut().make_semaphore(Semaphore::new(ptr::null()))
     }
 
     fn create_fence(&mut self, signalled: bool) -> handle::Fence<R> {
@@ -513,7 +730,41 @@ impl d::Device<R> for Device {
         } else {
             ptr::null()
         };
-        self.share.handles.borrow_mut().make_fence(Fence(sync))
+        self.share.handles.borrow_mut().make_fence(Fence::new(sync))
+    }
+
+    fn create_query(&mut self) -> handle::Query<R> {
+        let mut name = 0;
+        if self.share.capabilities.occlusion_query_supported {
+            unsafe { self.share.context.GenQueries(1, &mut name) };
+        } else {
+            error!("Occlusion queries are not supported");
+        }
+        self.share.handles.borrow_mut().make_query(name)
+    }
+
+    fn get_query_result(&mut self, query: &handle::Query<R>, wait: bool) -> Option<u64> {
+        let name = *self.frame_handles.ref_query(query);
+        if name == 0 {
+            return Some(1)
+        }
+        let gl = &self.share.context;
+        if !wait {
+            let mut available = 0;
+            unsafe { gl.GetQueryObjectuiv(name, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+            if available == 0 {
+                return None
+            }
+        }
+        if self.share.private_caps.query_u64_supported {
+            let mut result = 0;
+            unsafe { gl.GetQueryObjectui64v(name, gl::QUERY_RESULT, &mut result) };
+            Some(result)
+        } else {
+            let mut result = 0;
+            unsafe { gl.GetQueryObjectuiv(name, gl::QUERY_RESULT, &mut result) };
+            Some(result as u64)
+        }
     }
 
     fn reset_fences(&mut self, fences: &[&handle::Fence<R>]) {
@@ -524,7 +775,7 @@ impl d::Device<R> for Device {
         let gl = &self.share.context;
         for fence in fences {
             let fence = &mut *self.frame_handles.ref_fence(&fence).lock().unwrap();
-            let sync = &mut fence.0;
+            let sync = &mut fence.sync;
             unsafe {
                 if gl.IsSync(*sync) == gl::TRUE {
                     gl.DeleteSync(*sync);
@@ -542,7 +793,7 @@ impl d::Device<R> for Device {
         match wait {
             d::WaitFor::All => {
                 for fence in fences {
-                    let fence = &*self.frame_handles.ref_fence(&fence).lock().unwrap();
+                    let fence = self.frame_handles.ref_fence(&fence);
                     match wait_fence(fence, &self.share.context, timeout_ms) {
                         gl::TIMEOUT_EXPIRED => return false,
                         gl::WAIT_FAILED => {
@@ -560,7 +811,7 @@ impl d::Device<R> for Device {
             d::WaitFor::Any => {
                 let mut waiting = |timeout_ms: u32| {
                     for fence in fences {
-                        let fence = &*self.frame_handles.ref_fence(&fence).lock().unwrap();
+                        let fence = self.frame_handles.ref_fence(&fence);
                         match wait_fence(fence, &self.share.context, 0) {
                             gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => return true,
                             gl::WAIT_FAILED => {
@@ -588,13 +839,32 @@ impl d::Device<R> for Device {
                                -> Result<mapping::Reader<'b, R, T>,
                                          mapping::Error>
         where T: Copy
+    {
+        let len = buf.len();
+        self.read_mapping_range(buf, 0 .. len)
+    }
+
+    fn write_mapping<'a, 'b, T>(&'a mut self, buf: &'b handle::Buffer<R, T>)
+                                -> Result<mapping::Writer<'b, R, T>,
+                                          mapping::Error>
+        where T: Copy
+    {
+        let len = buf.len();
+        self.write_mapping_range(buf, 0 .. len)
+    }
+
+    fn read_mapping_range<'a, 'b, T>(&'a mut self, buf: &'b handle::Buffer<R, T>, range: Range<usize>)
+                                     -> Result<mapping::Reader<'b, R, T>,
+                                               mapping::Error>
+        where T: Copy
     {
         let gl = &self.share.context;
         let handles = &mut self.frame_handles;
+        let bytes = byte_range::<T>(&range);
         unsafe {
-            mapping::read(buf.raw(), |mapping| match mapping.kind {
+            mapping::read_range(buf.raw(), range, |mapping| match mapping.kind {
                 MappingKind::Persistent(ref mut status) =>
-                    status.cpu_access(|fence| { wait_fence(&*handles.ref_fence(&fence).lock().unwrap(), gl, 1_000_000); }),
+                    status.cpu_access(bytes, |fence| { wait_fence(handles.ref_fence(&fence), gl, 1_000_000); }),
                 MappingKind::Temporary =>
                     temporary_ensure_mapped(&mut mapping.pointer,
                                             role_to_target(buf.get_info().role),
@@ -605,17 +875,18 @@ impl d::Device<R> for Device {
         }
     }
 
-    fn write_mapping<'a, 'b, T>(&'a mut self, buf: &'b handle::Buffer<R, T>)
-                                -> Result<mapping::Writer<'b, R, T>,
-                                          mapping::Error>
+    fn write_mapping_range<'a, 'b, T>(&'a mut self, buf: &'b handle::Buffer<R, T>, range: Range<usize>)
+                                      -> Result<mapping::Writer<'b, R, T>,
+                                                mapping::Error>
         where T: Copy
     {
         let gl = &self.share.context;
         let handles = &mut self.frame_handles;
+        let bytes = byte_range::<T>(&range);
         unsafe {
-            mapping::write(buf.raw(), |mapping| match mapping.kind {
+            mapping::write_range(buf.raw(), range, |mapping| match mapping.kind {
                 MappingKind::Persistent(ref mut status) =>
-                    status.cpu_write_access(|fence| { wait_fence(&*handles.ref_fence(&fence).lock().unwrap(), gl, 1_000_000); }),
+                    status.cpu_access(bytes, |fence| { wait_fence(handles.ref_fence(&fence), gl, 1_000_000); }),
                 MappingKind::Temporary =>
                     temporary_ensure_mapped(&mut mapping.pointer,
                                             role_to_target(buf.get_info().role),
@@ -627,11 +898,28 @@ impl d::Device<R> for Device {
     }
 }
 
-pub fn wait_fence(fence: &Fence, gl: &gl::Gl, timeout_ms: u32) -> gl::types::GLenum {
-    let timeout = timeout_ms as u64 * 1_000_000;
+fn byte_range<T>(range: &Range<usize>) -> Range<usize> {
+    let stride = mem::size_of::<T>();
+    range.start * stride .. range.end * stride
+}
+
+pub fn wait_fence(fence: &Mutex<Fence>, gl: &gl::Gl, timeout_ms: u32) -> gl::types::GLenum {
+    let timeout = Duration::from_millis(timeout_ms as u64);
+    let start = Instant::now();
     // TODO:
     // This can be called by multiple objects wanting to ensure they have exclusive
     // access to a resource. How much does this call costs ? The status of the fence
     // could be cached to avoid calling this more than once (in core or in the backend ?).
-    unsafe { gl.ClientWaitSync(fence.0, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) }
+    let fence = match ::lock_placed(fence, Some(start + timeout)) {
+        Some(fence) => fence,
+        // the transfer worker has not reached it
+        None => return gl::TIMEOUT_EXPIRED,
+    };
+    if fence.sync.is_null() {
+        // never signalled
+        return gl::TIMEOUT_EXPIRED
+    }
+    let remaining = timeout.checked_sub(start.elapsed()).unwrap_or(Duration::from_millis(0));
+    let nanos = remaining.as_secs() * 1_000_000_000 + remaining.subsec_nanos() as u64;
+    unsafe { gl.ClientWaitSync(fence.sync, gl::SYNC_FLUSH_COMMANDS_BIT, nanos) }
 }
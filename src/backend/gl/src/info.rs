@@ -13,12 +13,16 @@
 // limitations under the License.
 
 use std::collections::HashSet;
+use std::os::raw::{c_int, c_uint};
 use std::{ffi, fmt, mem, str};
 use gl;
-use core::{Capabilities, IndexCount, VertexCount};
+use core::{memory, Capabilities, HeapType, IndexCount, VertexCount,
+           MAX_COLOR_TARGETS, MAX_VERTEX_ATTRIBUTES, MAX_VIEWPORTS};
+use core::format::{Format, FormatProperties};
 
 /// A version number for a specific component of an OpenGL implementation
 #[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
 pub struct Version {
     pub is_embedded: bool,
     pub major: u32,
@@ -137,12 +141,19 @@ fn get_usize(gl: &gl::Gl, name: gl::types::GLenum) -> usize {
     value as usize
 }
 
+fn get_float(gl: &gl::Gl, name: gl::types::GLenum) -> f32 {
+    let mut value = 0 as gl::types::GLfloat;
+    unsafe { gl.GetFloatv(name, &mut value) };
+    value
+}
+
 unsafe fn c_str_as_static_str(c_str: *const i8) -> &'static str {
     mem::transmute(str::from_utf8(ffi::CStr::from_ptr(c_str as *const _).to_bytes()).unwrap())
 }
 
 /// A unique platform identifier that does not change between releases
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
 pub struct PlatformName {
     /// The company responsible for the OpenGL implementation
     pub vendor: &'static str,
@@ -160,8 +171,10 @@ impl PlatformName {
 }
 
 /// Private capabilities that don't need to be exposed.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
 pub struct PrivateCaps {
+    /// Without it, the vertex array objects are emulated.
     pub array_buffer_supported: bool,
     pub frame_buffer_supported: bool,
     pub immutable_storage_supported: bool,
@@ -172,10 +185,40 @@ pub struct PrivateCaps {
     pub frag_data_location_supported: bool,
     pub sampler_lod_bias_supported: bool,
     pub sync_supported: bool,
+    pub debug_output_supported: bool,
+    pub sample_mask_supported: bool,
+    pub sample_shading_supported: bool,
+    pub stream_output_skip_supported: bool,
+    pub stencil_texturing_supported: bool,
+    pub texture_view_supported: bool,
+    pub storage_buffer_supported: bool,
+    pub explicit_binding_supported: bool,
+    /// Image uniforms accept `glUniform1i`, GLES only takes the shader binding.
+    pub image_unit_assignment_supported: bool,
+    /// `glGetQueryObjectui64v`, otherwise the query results are 32 bits wide.
+    pub query_u64_supported: bool,
+    /// Without it, the textures are allocated with the unsized formats.
+    pub sized_internal_format_supported: bool,
+    pub integer_attribute_supported: bool,
+    /// Base/max mipmap levels and min/max LOD of the textures.
+    pub texture_lod_supported: bool,
+    /// `TEXTURE_WRAP_R` of the textures other than the 3D ones.
+    pub texture_wrap_r_supported: bool,
+    pub shadow_sampler_supported: bool,
+    pub border_color_supported: bool,
+    /// `glFramebufferTexture`, otherwise `glFramebufferTexture2D` is used.
+    pub framebuffer_texture_supported: bool,
+    /// Separate read and draw framebuffer binding points.
+    pub framebuffer_blit_supported: bool,
+    pub draw_buffers_supported: bool,
+    pub max_integer_samples: usize,
+    /// Number of color targets that can be used with dual-source blending.
+    pub max_dual_source_draw_buffers: usize,
 }
 
 /// OpenGL implementation information
 #[derive(Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
 pub struct Info {
     /// The platform identifier
     pub platform_name: PlatformName,
@@ -199,7 +242,8 @@ impl Info {
         let platform_name = PlatformName::get(gl);
         let version = Version::parse(get_string(gl, gl::VERSION)).unwrap();
         let shading_language = Version::parse(get_string(gl, gl::SHADING_LANGUAGE_VERSION)).unwrap();
-        let extensions = if version >= Version::new(3, 0, None, "") {
+        // `glGetStringi` is available from both GL 3.0 and GLES 3.0
+        let extensions = if version.major >= 3 {
             let num_exts = get_usize(gl, gl::NUM_EXTENSIONS) as gl::types::GLuint;
             (0..num_exts)
                 .map(|i| unsafe { c_str_as_static_str(gl.GetStringi(gl::EXTENSIONS, i) as *const i8) })
@@ -217,11 +261,19 @@ impl Info {
     }
 
     pub fn is_version_supported(&self, major: u32, minor: u32) -> bool {
-        !self.version.is_embedded && self.version >= Version::new(major, minor, None, "")
+        !self.version.is_embedded && (self.version.major, self.version.minor) >= (major, minor)
     }
 
     pub fn is_embedded_version_supported(&self, major: u32, minor: u32) -> bool {
-        self.version.is_embedded && self.version >= Version::new(major, minor, None, "")
+        // not comparing the versions, which would order any embedded one
+        // after all the desktop ones
+        self.version.is_embedded && (self.version.major, self.version.minor) >= (major, minor)
+    }
+
+    /// Returns `true` for OpenGL ES 2.0 (and WebGL 1) implementations, which
+    /// get by with the extensions and the fallbacks of the backend.
+    pub fn is_gles2_tier(&self) -> bool {
+        self.version.is_embedded && self.version.major < 3
     }
 
     /// Returns `true` if the implementation supports the extension
@@ -256,39 +308,112 @@ pub fn get(gl: &gl::Gl) -> (Info, Capabilities, PrivateCaps) {
     let info = Info::get(gl);
     let tessellation_supported =           info.is_supported(&[Core(4,0),
                                                                Ext("GL_ARB_tessellation_shader")]);
+    let viewport_array_supported =         info.is_supported(&[Core(4,1),
+                                                               Ext("GL_ARB_viewport_array")]);
+    let constant_buffer_supported =        info.is_supported(&[Core(3,1),
+                                                               Es  (3,0),
+                                                               Ext ("GL_ARB_uniform_buffer_object")]);
+    let texture_3d_supported =             info.is_supported(&[Core(1,2),
+                                                               Es  (3,0),
+                                                               Ext ("GL_OES_texture_3D")]);
+    let texture_array_supported =          info.is_supported(&[Core(3,0),
+                                                               Es  (3,0),
+                                                               Ext ("GL_EXT_texture_array")]);
+    let anisotropy_supported =             info.is_supported(&[Core(4,6),
+                                                               Ext ("GL_ARB_texture_filter_anisotropic"),
+                                                               Ext ("GL_EXT_texture_filter_anisotropic")]);
+    let multisample_supported =            info.is_supported(&[Core(3,0),
+                                                               Es  (3,0),
+                                                               Ext ("GL_ARB_framebuffer_object")]);
+    let draw_buffers_supported =           info.is_supported(&[Core(2,0),
+                                                               Es  (3,0),
+                                                               Ext ("GL_EXT_draw_buffers")]);
+    let dual_source_blending_supported =   info.is_supported(&[Core(3,3),
+                                                               Ext ("GL_ARB_blend_func_extended"),
+                                                               Ext ("GL_EXT_blend_func_extended")]);
     let caps = Capabilities {
         max_texture_size: get_usize(gl, gl::MAX_TEXTURE_SIZE),
         max_patch_size: if tessellation_supported { get_usize(gl, gl::MAX_PATCH_VERTICES) as u8 } else {0},
+        max_viewports: if viewport_array_supported {
+            get_usize(gl, gl::MAX_VIEWPORTS).min(MAX_VIEWPORTS)
+        } else {1},
+        max_color_targets: if draw_buffers_supported {
+            get_usize(gl, gl::MAX_DRAW_BUFFERS).min(MAX_COLOR_TARGETS)
+        } else {1},
+        max_vertex_attributes: get_usize(gl, gl::MAX_VERTEX_ATTRIBS).min(MAX_VERTEX_ATTRIBUTES),
+        max_constant_buffer_size: if constant_buffer_supported {
+            get_usize(gl, gl::MAX_UNIFORM_BLOCK_SIZE)
+        } else {0},
+        max_texture_3d_size: if texture_3d_supported { get_usize(gl, gl::MAX_3D_TEXTURE_SIZE) } else {0},
+        max_texture_array_layers: if texture_array_supported {
+            get_usize(gl, gl::MAX_ARRAY_TEXTURE_LAYERS)
+        } else {1},
+        max_anisotropy: if anisotropy_supported {
+            get_float(gl, gl::MAX_TEXTURE_MAX_ANISOTROPY_EXT).min(u8::max_value() as f32) as u8
+        } else {1},
+        max_samples: if multisample_supported { get_usize(gl, gl::MAX_SAMPLES) } else {1},
 
         instance_base_supported:           info.is_supported(&[Core(4,2),
                                                                Ext ("GL_ARB_base_instance")]),
         instance_call_supported:           info.is_supported(&[Core(3,1),
                                                                Es  (3,0),
-                                                               Ext ("GL_ARB_draw_instanced")]),
+                                                               Ext ("GL_ARB_draw_instanced"),
+                                                               Ext ("GL_ANGLE_instanced_arrays")]),
         instance_rate_supported:           info.is_supported(&[Core(3,3),
                                                                Es  (3,0),
-                                                               Ext ("GL_ARB_instanced_arrays")]),
+                                                               Ext ("GL_ARB_instanced_arrays"),
+                                                               Ext ("GL_ANGLE_instanced_arrays")]),
         vertex_base_supported:             info.is_supported(&[Core(3,2),
                                                                Es  (3,2),
                                                                Ext ("GL_ARB_draw_elements_base_vertex")]),
         srgb_color_supported:              info.is_supported(&[Core(3,2),
                                                                Ext ("GL_ARB_framebuffer_sRGB")]),
-        constant_buffer_supported:         info.is_supported(&[Core(3,1),
-                                                               Es  (3,0),
-                                                               Ext ("GL_ARB_uniform_buffer_object")]),
-        unordered_access_view_supported:   info.is_supported(&[Core(4,0)]), //TODO: extension
+        constant_buffer_supported:         constant_buffer_supported,
+        unordered_access_view_supported:   info.is_supported(&[Core(4,2),
+                                                               Es  (3,1),
+                                                               Ext ("GL_ARB_shader_image_load_store")]),
         separate_blending_slots_supported: info.is_supported(&[Core(4,0),
                                                                Es  (3,0),
                                                                Ext ("GL_ARB_draw_buffers_blend")]),
+        dual_source_blending_supported:    dual_source_blending_supported,
+        logic_op_supported:                !info.version.is_embedded,
+        stream_output_supported:           info.is_supported(&[Core(3,0),
+                                                               Es  (3,0),
+                                                               Ext ("GL_EXT_transform_feedback")]),
+        occlusion_query_supported:         info.is_supported(&[Core(1,5),
+                                                               Es  (3,0),
+                                                               Ext ("GL_ARB_occlusion_query"),
+                                                               Ext ("GL_EXT_occlusion_query_boolean")]),
+        conditional_render_supported:      info.is_supported(&[Core(3,0),
+                                                               Ext ("GL_NV_conditional_render")]),
         copy_buffer_supported:             info.is_supported(&[Core(3,1),
                                                                Es  (3,0),
                                                                Ext ("GL_ARB_copy_buffer"),
                                                                Ext ("GL_NV_copy_buffer")]),
+        geometry_shader_supported:         info.is_supported(&[Core(3,2),
+                                                               Es  (3,2),
+                                                               Ext ("GL_ARB_geometry_shader4"),
+                                                               Ext ("GL_EXT_geometry_shader")]),
+        tessellation_supported:            tessellation_supported,
+        compute_supported:                 info.is_supported(&[Core(4,3),
+                                                               Es  (3,1),
+                                                               Ext ("GL_ARB_compute_shader")]),
+        indirect_draw_supported:           info.is_supported(&[Core(4,0),
+                                                               Es  (3,1),
+                                                               Ext ("GL_ARB_draw_indirect")]),
+        srgb_decode_supported:             info.is_supported(&[Ext ("GL_EXT_texture_sRGB_decode")]),
+        depth_clamp_supported:             info.is_supported(&[Core(3,2),
+                                                               Ext ("GL_ARB_depth_clamp"),
+                                                               Ext ("GL_EXT_depth_clamp")]),
+        seamless_cube_supported:           info.is_supported(&[Core(3,2),
+                                                               Es  (3,0),
+                                                               Ext ("GL_ARB_seamless_cube_map")]),
     };
     let private = PrivateCaps {
         array_buffer_supported:            info.is_supported(&[Core(3,0),
                                                                Es  (3,0),
-                                                               Ext ("GL_ARB_vertex_array_object")]),
+                                                               Ext ("GL_ARB_vertex_array_object"),
+                                                               Ext ("GL_OES_vertex_array_object")]),
         frame_buffer_supported:            info.is_supported(&[Core(3,0),
                                                                Es  (2,0),
                                                                Ext ("GL_ARB_framebuffer_object")]),
@@ -308,13 +433,410 @@ pub fn get(gl: &gl::Gl) -> (Info, Capabilities, PrivateCaps) {
         sync_supported:                    info.is_supported(&[Core(3,2),
                                                                Es  (3,0),
                                                                Ext ("GL_ARB_sync")]),
+        debug_output_supported:            info.is_supported(&[Core(4,3),
+                                                               Es  (3,2),
+                                                               Ext ("GL_KHR_debug")]),
+        sample_mask_supported:             info.is_supported(&[Core(3,2),
+                                                               Es  (3,1),
+                                                               Ext ("GL_ARB_texture_multisample")]),
+        sample_shading_supported:          info.is_supported(&[Core(4,0),
+                                                               Es  (3,2),
+                                                               Ext ("GL_ARB_sample_shading")]),
+        stream_output_skip_supported:      info.is_supported(&[Core(4,0),
+                                                               Ext ("GL_ARB_transform_feedback3")]),
+        stencil_texturing_supported:       info.is_supported(&[Core(4,3),
+                                                               Es  (3,1),
+                                                               Ext ("GL_ARB_stencil_texturing")]),
+        texture_view_supported:            info.is_supported(&[Core(4,3),
+                                                               Ext ("GL_ARB_texture_view"),
+                                                               Ext ("GL_OES_texture_view"),
+                                                               Ext ("GL_EXT_texture_view")]),
+        storage_buffer_supported:          info.is_supported(&[Core(4,3),
+                                                               Es  (3,1),
+                                                               Ext ("GL_ARB_shader_storage_buffer_object")]),
+        explicit_binding_supported:        info.is_supported(&[Core(4,2),
+                                                               Es  (3,1),
+                                                               Ext ("GL_ARB_shading_language_420pack")]),
+        image_unit_assignment_supported:   !info.version.is_embedded,
+        query_u64_supported:               info.is_supported(&[Core(3,3),
+                                                               Ext ("GL_ARB_timer_query")]),
+        sized_internal_format_supported:   !info.is_gles2_tier(),
+        integer_attribute_supported:       info.is_supported(&[Core(3,0),
+                                                               Es  (3,0),
+                                                               Ext ("GL_EXT_gpu_shader4")]),
+        texture_lod_supported:             info.is_supported(&[Core(1,2),
+                                                               Es  (3,0)]),
+        texture_wrap_r_supported:          !info.is_gles2_tier(),
+        shadow_sampler_supported:          info.is_supported(&[Core(1,4),
+                                                               Es  (3,0),
+                                                               Ext ("GL_EXT_shadow_samplers")]),
+        border_color_supported:            info.is_supported(&[Core(1,3),
+                                                               Es  (3,2),
+                                                               Ext ("GL_OES_texture_border_clamp"),
+                                                               Ext ("GL_EXT_texture_border_clamp")]),
+        framebuffer_texture_supported:     info.is_supported(&[Core(3,2),
+                                                               Es  (3,2)]),
+        framebuffer_blit_supported:        info.is_supported(&[Core(3,0),
+                                                               Es  (3,0),
+                                                               Ext ("GL_ARB_framebuffer_object"),
+                                                               Ext ("GL_EXT_framebuffer_blit")]),
+        draw_buffers_supported:            draw_buffers_supported,
+        max_integer_samples: if info.is_supported(&[Core(3,2), Es(3,1), Ext("GL_ARB_texture_multisample")]) {
+            get_usize(gl, gl::MAX_INTEGER_SAMPLES)
+        } else {1},
+        max_dual_source_draw_buffers: if dual_source_blending_supported {
+            get_usize(gl, gl::MAX_DUAL_SOURCE_DRAW_BUFFERS)
+        } else {0},
     };
     (info, caps, private)
 }
 
+// PCI vendor ids
+const VENDOR_AMD: usize = 0x1002;
+const VENDOR_APPLE: usize = 0x106B;
+const VENDOR_ARM: usize = 0x13B5;
+const VENDOR_BROADCOM: usize = 0x14E4;
+const VENDOR_IMAGINATION: usize = 0x1010;
+const VENDOR_INTEL: usize = 0x8086;
+const VENDOR_NVIDIA: usize = 0x10DE;
+const VENDOR_QUALCOMM: usize = 0x5143;
+const VENDOR_VMWARE: usize = 0x15AD;
+
+// GL_NVX_gpu_memory_info, in kilobytes
+const GPU_MEMORY_INFO_DEDICATED_VIDMEM_NVX: gl::types::GLenum = 0x9047;
+// GL_ATI_meminfo, in kilobytes
+const TEXTURE_FREE_MEMORY_ATI: gl::types::GLenum = 0x87FC;
+
+// GLX_MESA_query_renderer
+const GLX_RENDERER_VENDOR_ID_MESA: c_int = 0x8183;
+const GLX_RENDERER_DEVICE_ID_MESA: c_int = 0x8184;
+const GLX_RENDERER_ACCELERATED_MESA: c_int = 0x8186;
+const GLX_RENDERER_VIDEO_MEMORY_MESA: c_int = 0x8187;
+const GLX_RENDERER_UNIFIED_MEMORY_ARCHITECTURE_MESA: c_int = 0x8188;
+
+/// `glXQueryCurrentRendererIntegerMESA`, returning false for the unknown attributes.
+pub type QueryRendererInteger = extern "C" fn(attribute: c_int, value: *mut c_uint) -> c_int;
+
+/// Identification and memory of the adapter, as far as the driver tells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdapterDetails {
+    /// PCI vendor id, or 0 for the software rasterizers.
+    pub vendor: usize,
+    /// PCI device id, or 0 if unknown.
+    pub device: usize,
+    pub software_rendering: bool,
+    /// Size of the video memory in bytes, if reported.
+    pub video_memory: Option<u64>,
+    /// Whether the video memory is the system memory.
+    pub unified_memory: bool,
+}
+
+fn is_software_renderer(name: &PlatformName) -> bool {
+    let renderer = name.renderer.to_lowercase();
+    ["llvmpipe", "softpipe", "swrast", "swr", "software rasterizer", "gdi generic"]
+        .iter().any(|s| renderer.contains(s))
+}
+
+/// Guess the PCI vendor id from the names of the vendor and the renderer,
+/// since the Mesa drivers don't put the hardware vendor in the former.
+fn guess_vendor_id(name: &PlatformName) -> usize {
+    if is_software_renderer(name) {
+        return 0
+    }
+    let known = [
+        ("nvidia", VENDOR_NVIDIA), ("nouveau", VENDOR_NVIDIA), ("geforce", VENDOR_NVIDIA),
+        ("ati technologies", VENDOR_AMD), ("amd", VENDOR_AMD), ("radeon", VENDOR_AMD),
+        ("intel", VENDOR_INTEL),
+        ("apple", VENDOR_APPLE),
+        ("mali", VENDOR_ARM),
+        ("qualcomm", VENDOR_QUALCOMM), ("adreno", VENDOR_QUALCOMM),
+        ("imagination", VENDOR_IMAGINATION), ("powervr", VENDOR_IMAGINATION),
+        ("broadcom", VENDOR_BROADCOM), ("vc4", VENDOR_BROADCOM),
+        ("vmware", VENDOR_VMWARE), ("svga3d", VENDOR_VMWARE),
+    ];
+    for full_name in &[name.vendor, name.renderer] {
+        let full_name = full_name.to_lowercase();
+        if let Some(&(_, id)) = known.iter().find(|&&(key, _)| full_name.contains(key)) {
+            return id
+        }
+    }
+    if name.vendor == "ARM" { VENDOR_ARM } else { 0 }
+}
+
+/// Identify the adapter, asking the Mesa drivers through GLX if possible.
+pub fn get_details(gl: &gl::Gl, info: &Info, query_renderer: Option<QueryRendererInteger>)
+                   -> AdapterDetails {
+    let mut details = AdapterDetails {
+        vendor: guess_vendor_id(&info.platform_name),
+        device: 0,
+        software_rendering: is_software_renderer(&info.platform_name),
+        video_memory: None,
+        unified_memory: false,
+    };
+
+    if let Some(query) = query_renderer {
+        let get = |attribute| {
+            let mut value = 0 as c_uint;
+            if query(attribute, &mut value) != 0 { Some(value) } else { None }
+        };
+        if let Some(accelerated) = get(GLX_RENDERER_ACCELERATED_MESA) {
+            details.software_rendering = accelerated == 0;
+        }
+        if !details.software_rendering {
+            if let Some(vendor) = get(GLX_RENDERER_VENDOR_ID_MESA) {
+                details.vendor = vendor as usize;
+            }
+            if let Some(device) = get(GLX_RENDERER_DEVICE_ID_MESA) {
+                details.device = device as usize;
+            }
+        }
+        details.video_memory = get(GLX_RENDERER_VIDEO_MEMORY_MESA).map(|mb| (mb as u64) << 20);
+        details.unified_memory = get(GLX_RENDERER_UNIFIED_MEMORY_ARCHITECTURE_MESA)
+                                     .map_or(false, |unified| unified != 0);
+    }
+
+    if details.video_memory.is_none() {
+        if info.is_extension_supported("GL_NVX_gpu_memory_info") {
+            let kb = get_usize(gl, GPU_MEMORY_INFO_DEDICATED_VIDMEM_NVX);
+            details.video_memory = Some((kb as u64) << 10);
+        } else if info.is_extension_supported("GL_ATI_meminfo") {
+            // only the free memory is known, which is the closest estimate
+            let mut values = [0 as gl::types::GLint; 4];
+            unsafe { gl.GetIntegerv(TEXTURE_FREE_MEMORY_ATI, values.as_mut_ptr()) };
+            details.video_memory = Some((values[0] as u64) << 10);
+        }
+    }
+    if details.software_rendering {
+        details.unified_memory = true;
+    }
+    details
+}
+
+/// Describe the memory heaps of the adapter. The drivers manage the memory
+/// of the objects, so this only tells where they would be placed.
+/// The size of an unknown heap is 0.
+pub fn get_heaps(details: &AdapterDetails) -> (Vec<HeapType>, Vec<u64>) {
+    let video_memory = details.video_memory.unwrap_or(0);
+    if details.unified_memory {
+        let heap_types = vec![
+            HeapType {
+                id: 0,
+                properties: memory::DEVICE_LOCAL | memory::CPU_VISIBLE,
+                heap_index: 0,
+            },
+            HeapType {
+                id: 1,
+                properties: memory::DEVICE_LOCAL | memory::CPU_VISIBLE | memory::CPU_CACHED,
+                heap_index: 0,
+            },
+        ];
+        (heap_types, vec![video_memory])
+    } else {
+        let heap_types = vec![
+            HeapType {
+                id: 0,
+                properties: memory::DEVICE_LOCAL,
+                heap_index: 0,
+            },
+            HeapType {
+                id: 1,
+                properties: memory::CPU_VISIBLE | memory::WRITE_COMBINED,
+                heap_index: 1,
+            },
+            HeapType {
+                id: 2,
+                properties: memory::CPU_VISIBLE | memory::CPU_CACHED,
+                heap_index: 1,
+            },
+        ];
+        (heap_types, vec![video_memory, 0])
+    }
+}
+
+/// Get the capabilities of a format, as used by `Device::get_format_properties`.
+pub fn get_format_properties(info: &Info, caps: &Capabilities, private: &PrivateCaps, format: Format)
+                             -> FormatProperties {
+    format_properties(info, format, caps.max_samples, private.max_integer_samples)
+}
+
+fn format_properties(info: &Info, format: Format, max_samples: usize, max_integer_samples: usize)
+                     -> FormatProperties {
+    use self::Requirement::*;
+    use core::format::SurfaceType as S;
+    use core::format::ChannelType as C;
+    use core::memory::{Bind, RENDER_TARGET, DEPTH_STENCIL, SHADER_RESOURCE, TRANSFER_SRC, TRANSFER_DST};
+
+    if ::tex::format_to_glfull(format).is_err() {
+        return FormatProperties::unsupported()
+    }
+    if info.is_gles2_tier() {
+        return gles2_format_properties(info, format)
+    }
+    let es = info.version.is_embedded;
+    let is_depth = format.0.has_depth() || format.0.has_stencil();
+    let is_integer = match format.1 {
+        C::Int | C::Uint => true,
+        _ => false,
+    };
+    let is_float32 = match (format.0, format.1) {
+        (S::R32, C::Float) | (S::R32_G32, C::Float) |
+        (S::R32_G32_B32, C::Float) | (S::R32_G32_B32_A32, C::Float) => true,
+        _ => false,
+    };
+
+    let stencil_texturing = info.is_supported(&[Core(4,3), Es(3,1), Ext("GL_ARB_stencil_texturing")]);
+    let sampled = match (format.0, format.1) {
+        (S::S8, _) =>
+            info.is_supported(&[Core(4,4), Es(3,2), Ext("GL_ARB_texture_stencil8"), Ext("GL_OES_texture_stencil8")]),
+        (S::D24_S8, C::Uint) => stencil_texturing,
+        (S::D32_S8, C::Uint) => stencil_texturing &&
+            info.is_supported(&[Core(3,0), Es(3,0), Ext("GL_ARB_depth_buffer_float")]),
+        (S::D32, _) | (S::D32_S8, _) =>
+            info.is_supported(&[Core(3,0), Es(3,0), Ext("GL_ARB_depth_buffer_float")]),
+        (_, C::Float) =>
+            info.is_supported(&[Core(3,0), Es(3,0), Ext("GL_ARB_texture_float")]),
+        (_, C::Int) | (_, C::Uint) =>
+            info.is_supported(&[Core(3,0), Es(3,0), Ext("GL_EXT_texture_integer")]),
+        (_, C::Inorm) =>
+            info.is_supported(&[Core(3,1), Es(3,0), Ext("GL_EXT_texture_snorm")]),
+        (_, C::Srgb) =>
+            info.is_supported(&[Core(2,1), Es(3,0), Ext("GL_EXT_texture_sRGB")]),
+        (S::R16, C::Unorm) | (S::R16_G16, C::Unorm) |
+        (S::R16_G16_B16, C::Unorm) | (S::R16_G16_B16_A16, C::Unorm) =>
+            !es || info.is_extension_supported("GL_EXT_texture_norm16"),
+        _ => true,
+    };
+    if !sampled {
+        return FormatProperties::unsupported()
+    }
+
+    // 3-component formats are not required to be color-renderable
+    let renderable = !is_depth && match (format.0, format.1) {
+        (S::R16_G16_B16, _) | (S::R32_G32_B32, _) => false,
+        (_, C::Float) if is_float32 => !es || info.is_extension_supported("GL_EXT_color_buffer_float"),
+        (_, C::Float) => !es || info.is_any_extension_supported(&["GL_EXT_color_buffer_float",
+                                                                 "GL_EXT_color_buffer_half_float"]),
+        (_, C::Inorm) => !es || info.is_extension_supported("GL_EXT_render_snorm"),
+        _ => true,
+    };
+    let filterable = !is_integer && if is_float32 {
+        !es || info.is_extension_supported("GL_OES_texture_float_linear")
+    } else {
+        // depth textures are not filterable without comparison on GLES
+        !(es && is_depth)
+    };
+    let blendable = renderable && !is_integer &&
+        (!is_float32 || !es || info.is_extension_supported("GL_EXT_float_blend"));
+
+    let mut bind = SHADER_RESOURCE | TRANSFER_SRC | TRANSFER_DST;
+    if renderable {
+        bind.insert(RENDER_TARGET);
+    }
+    if is_depth {
+        bind.insert(DEPTH_STENCIL);
+    }
+    let max = if !bind.intersects(RENDER_TARGET | DEPTH_STENCIL) {
+        1
+    } else if is_integer {
+        max_integer_samples
+    } else {
+        max_samples
+    };
+    let mut sample_counts = 0;
+    let mut count = 1;
+    while count <= max && count <= 128 {
+        sample_counts |= count as u8;
+        count <<= 1;
+    }
+
+    FormatProperties {
+        bind: bind,
+        sample_counts: sample_counts,
+        filterable: filterable,
+        blendable: blendable,
+    }
+}
+
+/// The formats of OpenGL ES 2.0 beyond the 16-bit and RGBA8 ones come from the
+/// extensions, and there is no multisampling.
+fn gles2_format_properties(info: &Info, format: Format) -> FormatProperties {
+    use core::format::SurfaceType as S;
+    use core::format::ChannelType as C;
+    use core::memory::{Bind, RENDER_TARGET, DEPTH_STENCIL, SHADER_RESOURCE, TRANSFER_DST};
+
+    let ext = |name| info.is_extension_supported(name);
+    let rg = ext("GL_EXT_texture_rg");
+    let half_float = ext("GL_OES_texture_half_float");
+    let float = ext("GL_OES_texture_float");
+    let depth_texture = ext("GL_OES_depth_texture");
+    let packed_depth_stencil = ext("GL_OES_packed_depth_stencil");
+    let is_depth = format.0.has_depth() || format.0.has_stencil();
+
+    let (sampled, renderable) = match (format.0, format.1) {
+        (S::R8_G8_B8_A8, C::Unorm) |
+        (S::R5_G6_B5, C::Unorm) | (S::R4_G4_B4_A4, C::Unorm) | (S::R5_G5_B5_A1, C::Unorm) =>
+            (true, true),
+        (S::R8_G8_B8_A8, C::Srgb) => (ext("GL_EXT_sRGB"), ext("GL_EXT_sRGB")),
+        (S::B8_G8_R8_A8, C::Unorm) => (ext("GL_EXT_texture_format_BGRA8888"), false),
+        (S::R8, C::Unorm) | (S::R8_G8, C::Unorm) => (rg, rg),
+        (S::R16, C::Float) | (S::R16_G16, C::Float) =>
+            (half_float && rg, rg && ext("GL_EXT_color_buffer_half_float")),
+        (S::R16_G16_B16_A16, C::Float) => (half_float, ext("GL_EXT_color_buffer_half_float")),
+        (S::R16_G16_B16, C::Float) => (half_float, false),
+        (S::R32, C::Float) | (S::R32_G32, C::Float) => (float && rg, false),
+        (S::R32_G32_B32, C::Float) | (S::R32_G32_B32_A32, C::Float) => (float, false),
+        (S::D16, _) => (depth_texture, true),
+        (S::X8_D24, _) => (depth_texture, depth_texture || ext("GL_OES_depth24")),
+        (S::D24_S8, _) => (depth_texture && packed_depth_stencil, packed_depth_stencil),
+        // a renderbuffer only, there are no stencil textures
+        (S::S8, _) => (false, true),
+        _ => (false, false),
+    };
+    // the textures are allocated with the unsized formats
+    let sampled = sampled && ::tex::format_to_gles2(format).is_ok();
+
+    let mut bind = Bind::empty();
+    if sampled {
+        bind.insert(SHADER_RESOURCE | TRANSFER_DST);
+    }
+    if renderable {
+        bind.insert(if is_depth { DEPTH_STENCIL } else { RENDER_TARGET });
+    }
+    let filterable = sampled && !is_depth && match (format.0, format.1) {
+        (S::R32, C::Float) | (S::R32_G32, C::Float) |
+        (S::R32_G32_B32, C::Float) | (S::R32_G32_B32_A32, C::Float) =>
+            ext("GL_OES_texture_float_linear"),
+        (_, C::Float) => ext("GL_OES_texture_half_float_linear"),
+        _ => true,
+    };
+
+    FormatProperties {
+        bind: bind,
+        sample_counts: if bind.is_empty() { 0 } else { 1 },
+        filterable: filterable,
+        blendable: renderable && !is_depth,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Version;
+    use super::{format_properties, get, get_heaps, guess_vendor_id, AdapterDetails, Info, PlatformName, Version};
+    use {gl, mock};
+    use core::MAX_VERTEX_ATTRIBUTES;
+    use core::format::{ChannelType, Format, SurfaceType};
+    use core::memory::{RENDER_TARGET, DEPTH_STENCIL, SHADER_RESOURCE, DEVICE_LOCAL, CPU_VISIBLE};
+
+    fn make_info(version: Version, extensions: &[&'static str]) -> Info {
+        Info {
+            platform_name: PlatformName {
+                vendor: "",
+                renderer: "",
+            },
+            version: version,
+            shading_language: version,
+            extensions: extensions.iter().cloned().collect(),
+        }
+    }
 
     #[test]
     fn test_version_parse() {
@@ -333,4 +855,171 @@ mod tests {
         assert_eq!(Version::parse("OpenGL ES 2.0 Google Nexus"), Ok(Version::new_embedded(2, 0, "Google Nexus")));
         assert_eq!(Version::parse("GLSL ES 1.1"), Ok(Version::new_embedded(1, 1, "")));
     }
+
+    #[test]
+    fn test_get() {
+        mock::install(mock::Driver {
+            version: "OpenGL ES 3.0 Mock",
+            shading_language: "OpenGL ES GLSL ES 3.00",
+            extensions: vec!["GL_EXT_texture_filter_anisotropic"],
+            integers: vec![
+                (gl::MAX_TEXTURE_SIZE, 4096),
+                (gl::MAX_VIEWPORTS, 16),
+                (gl::MAX_DRAW_BUFFERS, 4),
+                (gl::MAX_VERTEX_ATTRIBS, 32),
+                (gl::MAX_TEXTURE_MAX_ANISOTROPY_EXT, 16),
+            ],
+            .. mock::Driver::default()
+        });
+        let gl = gl::Gl::load_with(mock::get_proc_address);
+        let (info, caps, private) = get(&gl);
+        assert_eq!(info.version, Version::new_embedded(3, 0, "Mock"));
+        assert!(info.is_extension_supported("GL_EXT_texture_filter_anisotropic"));
+        assert_eq!(caps.max_texture_size, 4096);
+        assert_eq!(caps.max_viewports, 1);
+        assert_eq!(caps.max_color_targets, 4);
+        assert_eq!(caps.max_vertex_attributes, MAX_VERTEX_ATTRIBUTES);
+        assert_eq!(caps.max_anisotropy, 16);
+        assert!(caps.constant_buffer_supported && !caps.tessellation_supported);
+        assert!(private.clear_buffer_supported && !private.frag_data_location_supported);
+    }
+
+    #[test]
+    fn test_get_gles2() {
+        mock::install(mock::Driver {
+            version: "OpenGL ES 2.0 Mock",
+            shading_language: "OpenGL ES GLSL ES 1.00",
+            extensions: vec!["GL_OES_vertex_array_object", "GL_ANGLE_instanced_arrays"],
+            integers: vec![(gl::MAX_TEXTURE_SIZE, 2048)],
+            .. mock::Driver::default()
+        });
+        let gl = gl::Gl::load_with(mock::get_proc_address);
+        let (info, caps, private) = get(&gl);
+        assert!(info.is_gles2_tier());
+        assert!(!info.is_version_supported(3, 0) && !info.is_embedded_version_supported(3, 0));
+        assert!(info.is_embedded_version_supported(2, 0));
+        assert!(caps.instance_call_supported && caps.instance_rate_supported);
+        assert!(!caps.instance_base_supported && !caps.constant_buffer_supported);
+        assert_eq!(caps.max_color_targets, 1);
+        assert!(private.array_buffer_supported && !private.sampler_objects_supported);
+        assert!(!private.sized_internal_format_supported && !private.integer_attribute_supported);
+        assert!(!private.texture_lod_supported && !private.border_color_supported);
+        assert!(!private.texture_wrap_r_supported);
+        assert!(!private.framebuffer_texture_supported && !private.framebuffer_blit_supported);
+    }
+
+    #[test]
+    fn test_gles2_format_properties() {
+        let rgba8 = Format(SurfaceType::R8_G8_B8_A8, ChannelType::Unorm);
+        let rgba16f = Format(SurfaceType::R16_G16_B16_A16, ChannelType::Float);
+        let depth = Format(SurfaceType::D16, ChannelType::Unorm);
+
+        let es2 = make_info(Version::new_embedded(2, 0, ""), &[]);
+        let props = format_properties(&es2, rgba8, 4, 1);
+        assert!(props.bind.contains(SHADER_RESOURCE | RENDER_TARGET));
+        assert!(props.filterable && props.blendable);
+        assert_eq!(props.sample_counts, 1);
+        assert!(!format_properties(&es2, rgba16f, 4, 1).is_supported());
+        let props = format_properties(&es2, depth, 4, 1);
+        assert!(props.bind.contains(DEPTH_STENCIL) && !props.bind.contains(SHADER_RESOURCE));
+        let stencil = Format(SurfaceType::S8, ChannelType::Uint);
+        let props = format_properties(&es2, stencil, 4, 1);
+        assert!(props.bind.contains(DEPTH_STENCIL) && !props.bind.contains(SHADER_RESOURCE));
+
+        let ext = make_info(Version::new_embedded(2, 0, ""),
+                            &["GL_OES_texture_half_float", "GL_OES_depth_texture"]);
+        let props = format_properties(&ext, rgba16f, 4, 1);
+        assert!(props.bind.contains(SHADER_RESOURCE) && !props.bind.contains(RENDER_TARGET));
+        assert!(!props.filterable);
+        assert!(format_properties(&ext, depth, 4, 1).bind.contains(SHADER_RESOURCE | DEPTH_STENCIL));
+    }
+
+    #[test]
+    fn test_format_properties() {
+        let rgba16f = Format(SurfaceType::R16_G16_B16_A16, ChannelType::Float);
+        let rgba32f = Format(SurfaceType::R32_G32_B32_A32, ChannelType::Float);
+        let rgba8i = Format(SurfaceType::R8_G8_B8_A8, ChannelType::Int);
+
+        let desktop = make_info(Version::new(3, 3, None, ""), &[]);
+        let props = format_properties(&desktop, rgba16f, 8, 4);
+        assert!(props.bind.contains(RENDER_TARGET));
+        assert!(props.filterable && props.blendable);
+        assert_eq!(props.sample_counts, 1 | 2 | 4 | 8);
+        assert!(props.supports_samples(4) && !props.supports_samples(3));
+        let props = format_properties(&desktop, rgba8i, 8, 4);
+        assert!(!props.filterable && !props.blendable);
+        assert_eq!(props.sample_counts, 1 | 2 | 4);
+        let props = format_properties(&desktop, Format(SurfaceType::D24_S8, ChannelType::Unorm), 8, 4);
+        assert!(props.bind.contains(DEPTH_STENCIL) && !props.bind.contains(RENDER_TARGET));
+        assert!(!format_properties(&desktop, Format(SurfaceType::R4_G4, ChannelType::Unorm), 8, 4)
+            .is_supported());
+
+        let es = make_info(Version::new_embedded(3, 0, ""), &[]);
+        let props = format_properties(&es, rgba32f, 4, 1);
+        assert!(props.is_supported());
+        assert!(!props.bind.contains(RENDER_TARGET));
+        assert!(!props.filterable);
+        assert_eq!(props.sample_counts, 1);
+        let es = make_info(Version::new_embedded(3, 0, ""), &["GL_EXT_color_buffer_float"]);
+        let props = format_properties(&es, rgba32f, 4, 1);
+        assert!(props.bind.contains(RENDER_TARGET) && !props.blendable);
+    }
+
+    #[test]
+    fn test_stencil_formats() {
+        let stencil = Format(SurfaceType::S8, ChannelType::Uint);
+        let depth_view = Format(SurfaceType::D24_S8, ChannelType::Unorm);
+        let stencil_view = Format(SurfaceType::D24_S8, ChannelType::Uint);
+
+        let old = make_info(Version::new(3, 3, None, ""), &[]);
+        assert!(!format_properties(&old, stencil, 8, 4).is_supported());
+        assert!(!format_properties(&old, stencil_view, 8, 4).is_supported());
+        assert!(format_properties(&old, depth_view, 8, 4).filterable);
+
+        let ext = make_info(Version::new(3, 3, None, ""), &["GL_ARB_stencil_texturing"]);
+        let props = format_properties(&ext, stencil_view, 8, 4);
+        assert!(props.bind.contains(SHADER_RESOURCE) && !props.filterable);
+
+        let new = make_info(Version::new(4, 4, None, ""), &[]);
+        let props = format_properties(&new, stencil, 8, 4);
+        assert!(props.bind.contains(DEPTH_STENCIL) && !props.bind.contains(RENDER_TARGET));
+        assert!(!props.filterable && !props.blendable);
+    }
+
+    #[test]
+    fn test_vendor_id() {
+        let vendor = |vendor, renderer| guess_vendor_id(&PlatformName {
+            vendor: vendor,
+            renderer: renderer,
+        });
+        assert_eq!(vendor("NVIDIA Corporation", "GeForce GTX 1080/PCIe/SSE2"), 0x10DE);
+        assert_eq!(vendor("ATI Technologies Inc.", "AMD Radeon R9 200 Series"), 0x1002);
+        assert_eq!(vendor("X.Org", "AMD Radeon (TM) RX 480 Graphics (POLARIS10, DRM 3.18.0)"), 0x1002);
+        assert_eq!(vendor("Intel Open Source Technology Center", "Mesa DRI Intel(R) HD Graphics 530 (Skylake GT2)"), 0x8086);
+        assert_eq!(vendor("nouveau", "NVE7"), 0x10DE);
+        assert_eq!(vendor("ARM", "Mali-T880"), 0x13B5);
+        assert_eq!(vendor("VMware, Inc.", "llvmpipe (LLVM 4.0, 256 bits)"), 0);
+        assert_eq!(vendor("Mesa/X.org", "softpipe"), 0);
+        assert_eq!(vendor("Unknown", "Unknown"), 0);
+    }
+
+    #[test]
+    fn test_heaps() {
+        let mut details = AdapterDetails {
+            vendor: 0x10DE,
+            device: 0,
+            software_rendering: false,
+            video_memory: Some(1 << 30),
+            unified_memory: false,
+        };
+        let (types, heaps) = get_heaps(&details);
+        assert_eq!(heaps, vec![1 << 30, 0]);
+        assert!(types.iter().any(|t| t.properties == DEVICE_LOCAL && t.heap_index == 0));
+        assert!(types.iter().all(|t| t.heap_index < heaps.len()));
+
+        details.unified_memory = true;
+        let (types, heaps) = get_heaps(&details);
+        assert_eq!(heaps.len(), 1);
+        assert!(types.iter().all(|t| t.properties.contains(DEVICE_LOCAL | CPU_VISIBLE)));
+    }
 }
@@ -22,26 +22,39 @@
 extern crate log;
 extern crate gfx_gl as gl;
 extern crate gfx_core as core;
+#[cfg(feature = "serialize")]
+#[macro_use]
+extern crate serde_derive;
 
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::{mem, ptr};
 use std::rc::Rc;
-use core::{self as c, handle, state as s, format, pso, texture, memory, command as com, buffer};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use core::{self as c, handle, state as s, format, pso, texture, memory, command as com};
 use core::QueueType;
 use core::target::{Layer, Level};
-use command::{Command, DataBuffer};
+use command::{Command, DataBuffer, VertexArray, VertexOffsets};
 use device::MappingKind;
 
+pub use self::debug::{DebugConfig, Severity};
 pub use self::device::Device;
-pub use self::info::{Info, PlatformName, Version};
+pub use self::info::{Info, PlatformName, PrivateCaps, Version};
+pub use self::transfer::SharedContext;
 
 mod command;
+mod debug;
 mod device;
 mod info;
+#[cfg(test)]
+mod mock;
 mod native;
 mod pool;
 mod shade;
 mod state;
 mod tex;
+mod transfer;
 
 pub type Buffer         = gl::types::GLuint;
 pub type ArrayBuffer    = gl::types::GLuint;
@@ -51,12 +64,96 @@ pub type FrameBuffer    = gl::types::GLuint;
 pub type Surface        = gl::types::GLuint;
 pub type Texture        = gl::types::GLuint;
 pub type Sampler        = gl::types::GLuint;
-
-#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
-pub struct Fence(gl::types::GLsync);
+pub type Query          = gl::types::GLuint;
+
+/// Sync object signalled once the previous commands of a queue are complete.
+#[derive(Debug)]
+pub struct Fence {
+    sync: gl::types::GLsync,
+    /// Whether the transfer worker is yet to place the sync.
+    pending: bool,
+    /// Notified by the transfer worker once it has placed the sync.
+    placed: Arc<Condvar>,
+}
 unsafe impl Send for Fence {}
 unsafe impl Sync for Fence {}
 
+impl Fence {
+    fn new(sync: gl::types::GLsync) -> Fence {
+        Fence {
+            sync: sync,
+            pending: false,
+            placed: Arc::new(Condvar::new()),
+        }
+    }
+
+    /// A fence to be placed by the transfer worker.
+    fn pending() -> Fence {
+        Fence {
+            pending: true,
+            .. Fence::new(ptr::null())
+        }
+    }
+
+    /// Replace the sync, deleting the previous one.
+    fn set_sync(&mut self, gl: &gl::Gl, sync: gl::types::GLsync) {
+        if !self.sync.is_null() {
+            unsafe { gl.DeleteSync(self.sync) };
+        }
+        self.sync = sync;
+    }
+}
+
+/// Fence placed by a queue, for another one to wait on.
+pub type Semaphore = Fence;
+
+/// Lock the fence once its sync is placed, waiting for the transfer worker
+/// until the deadline, if any.
+fn lock_placed(fence: &Mutex<Fence>, deadline: Option<Instant>) -> Option<MutexGuard<Fence>> {
+    let mut fence = fence.lock().unwrap();
+    let placed = fence.placed.clone();
+    while fence.pending {
+        fence = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None
+                }
+                placed.wait_timeout(fence, deadline - now).unwrap().0
+            },
+            None => placed.wait(fence).unwrap(),
+        };
+    }
+    Some(fence)
+}
+
+/// How long a queue waits for the transfer worker to place a semaphore.
+const PLACE_TIMEOUT_MS: u64 = 5_000;
+
+/// Make the following commands of the current context wait for the semaphore.
+/// The wait happens on the GPU, the CPU only waits for the transfer worker
+/// to place the sync.
+fn wait_semaphore(gl: &gl::Gl, semaphore: &Mutex<Semaphore>) {
+    let deadline = Instant::now() + Duration::from_millis(PLACE_TIMEOUT_MS);
+    let mut semaphore = match lock_placed(semaphore, Some(deadline)) {
+        Some(semaphore) => semaphore,
+        None => {
+            error!("The semaphore was not placed within {} ms, skipping the wait", PLACE_TIMEOUT_MS);
+            return
+        },
+    };
+    if !semaphore.sync.is_null() {
+        unsafe { gl.WaitSync(semaphore.sync, 0, gl::TIMEOUT_IGNORED) };
+        semaphore.set_sync(gl, ptr::null());
+    }
+}
+
+/// Signal the semaphore once the previous commands of the current context are complete.
+fn signal_semaphore(gl: &gl::Gl, semaphore: &Mutex<Semaphore>) {
+    let sync = unsafe { gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+    semaphore.lock().unwrap().set_sync(gl, sync);
+}
+
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Backend {}
 impl c::Backend for Backend {
@@ -84,10 +181,11 @@ impl c::Resources for Resources {
     type RenderTargetView    = TargetView;
     type DepthStencilView    = TargetView;
     type ShaderResourceView  = ResourceView;
-    type UnorderedAccessView = ();
+    type UnorderedAccessView = UnorderedView;
     type Sampler             = FatSampler;
+    type Query               = Query;
     type Fence               = Fence;
-    type Semaphore           = (); // TODO
+    type Semaphore           = Semaphore;
     type Mapping             = device::MappingGate;
 }
 
@@ -103,6 +201,17 @@ pub struct OutputMerger {
     pub stencil: Option<s::Stencil>,
     pub depth: Option<s::Depth>,
     pub colors: [s::Color; c::MAX_COLOR_TARGETS],
+    pub dual_sources: [c::pso::DualSource; c::MAX_COLOR_TARGETS],
+    pub logic_op: Option<c::pso::LogicOp>,
+}
+
+/// Transform feedback state of a pipeline.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct StreamOutput {
+    /// Primitive mode of the capture.
+    pub mode: gl::types::GLenum,
+    /// Whether the rasterization is skipped, for pipelines without targets.
+    pub discard: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
@@ -112,7 +221,9 @@ pub struct PipelineState {
     input: [Option<BufferElement>; c::MAX_VERTEX_ATTRIBUTES],
     scissor: bool,
     rasterizer: s::Rasterizer,
+    multisample: c::pso::MultisampleInfo,
     output: OutputMerger,
+    stream_output: Option<StreamOutput>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
@@ -126,6 +237,8 @@ pub struct ResourceView {
     object: Texture,
     bind: gl::types::GLenum,
     owned: bool,
+    /// Component read from a combined depth-stencil texture.
+    depth_stencil_mode: Option<gl::types::GLenum>,
 }
 
 impl ResourceView {
@@ -134,6 +247,15 @@ impl ResourceView {
             object: t,
             bind: tex::kind_to_gl(kind),
             owned: false,
+            depth_stencil_mode: None,
+        }
+    }
+    pub fn new_texture_view(t: Texture, kind: texture::Kind) -> ResourceView {
+        ResourceView {
+            object: t,
+            bind: tex::kind_to_gl(kind),
+            owned: true,
+            depth_stencil_mode: None,
         }
     }
     pub fn new_buffer(b: Texture) -> ResourceView {
@@ -141,10 +263,20 @@ impl ResourceView {
             object: b,
             bind: gl::TEXTURE_BUFFER,
             owned: true,
+            depth_stencil_mode: None,
         }
     }
 }
 
+/// Storage bound for the shader reads and writes.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum UnorderedView {
+    /// Shader storage buffer.
+    Buffer(Buffer),
+    /// Image of all the layers of the top level, with its internal format.
+    Image(Texture, gl::types::GLenum),
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FatSampler {
     object: Sampler,
@@ -156,6 +288,9 @@ pub enum TargetView {
     Surface(Surface),
     Texture(Texture, Level),
     TextureLayer(Texture, Level, Layer),
+    /// Single image attached with `glFramebufferTexture2D` to the given
+    /// target, which is a cube face for the cube maps.
+    Texture2D(Texture, Level, gl::types::GLenum),
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -223,6 +358,12 @@ pub struct Share {
     capabilities: c::Capabilities,
     private_caps: info::PrivateCaps,
     handles: RefCell<handle::Manager<Resources>>,
+    messenger: RefCell<Option<Box<debug::Messenger>>>,
+    /// Programs capturing the vertex data, derived from the original programs.
+    stream_programs: RefCell<HashMap<(Program, pso::StreamOutputDesc), shade::StreamOutputProgram>>,
+    /// Vertex array objects created for the attribute layouts and the vertex buffers
+    /// by all the queues, along with the offsets their attributes point at.
+    vertex_arrays: RefCell<HashMap<VertexArray, (ArrayBuffer, VertexOffsets)>>,
 }
 
 impl Share {
@@ -237,22 +378,63 @@ impl Share {
         }
         Ok(())
     }
+
+    /// Target of the occlusion queries. GLES only knows whether any samples passed.
+    fn occlusion_target(&self) -> gl::types::GLenum {
+        if self.info.version.is_embedded {
+            gl::ANY_SAMPLES_PASSED
+        } else {
+            gl::SAMPLES_PASSED
+        }
+    }
 }
 
+/// Entry points of the OpenGL ES 2.0 extensions, by the name of the
+/// function they provide in the later versions.
+const GLES2_ALIASES: &'static [(&'static str, &'static str)] = &[
+    ("glGenVertexArrays", "glGenVertexArraysOES"),
+    ("glBindVertexArray", "glBindVertexArrayOES"),
+    ("glDeleteVertexArrays", "glDeleteVertexArraysOES"),
+    ("glVertexAttribDivisor", "glVertexAttribDivisorANGLE"),
+    ("glDrawArraysInstanced", "glDrawArraysInstancedANGLE"),
+    ("glDrawElementsInstanced", "glDrawElementsInstancedANGLE"),
+    ("glDrawBuffers", "glDrawBuffersEXT"),
+    ("glMapBuffer", "glMapBufferOES"),
+    ("glUnmapBuffer", "glUnmapBufferOES"),
+];
+
 pub struct Adapter {
     share: Rc<Share>,
     adapter_info: c::AdapterInfo,
-    queue_family: [(QueueFamily, QueueType); 1],
+    heap_types: Vec<c::HeapType>,
+    memory_heaps: Vec<u64>,
+    queue_families: Vec<(QueueFamily, QueueType)>,
+    debug: Option<DebugConfig>,
+    transfer_context: RefCell<Option<Box<SharedContext>>>,
 }
 
 impl Adapter {
     #[doc(hidden)]
-    pub fn new<F>(fn_proc: F) -> Self where
+    pub fn new<F>(mut fn_proc: F) -> Self where
         F: FnMut(&str) -> *const std::os::raw::c_void
     {
-        let gl = gl::Gl::load_with(fn_proc);
+        let mut gl = gl::Gl::load_with(&mut fn_proc);
         // query information
         let (info, caps, private) = info::get(&gl);
+        if info.is_gles2_tier() {
+            info!("Using the OpenGL ES 2.0 tier, with the functions of its extensions");
+            gl = gl::Gl::load_with(|symbol| {
+                let address = match GLES2_ALIASES.iter().find(|&&(name, _)| name == symbol) {
+                    Some(&(_, ext_name)) => fn_proc(ext_name),
+                    None => ptr::null(),
+                };
+                if address.is_null() {
+                    fn_proc(symbol)
+                } else {
+                    address
+                }
+            });
+        }
         info!("Vendor: {:?}", info.platform_name.vendor);
         info!("Renderer: {:?}", info.platform_name.renderer);
         info!("Version: {:?}", info.version);
@@ -262,11 +444,27 @@ impl Adapter {
             debug!("- {}", *extension);
         }
 
+        // GLX_MESA_query_renderer is not listed in the GL extensions,
+        // but all the Mesa drivers under GLX expose it
+        let query_renderer = if info.version.vendor_info.contains("Mesa") {
+            let address = fn_proc("glXQueryCurrentRendererIntegerMESA");
+            if address.is_null() {
+                None
+            } else {
+                Some(unsafe { std::mem::transmute::<_, info::QueryRendererInteger>(address) })
+            }
+        } else {
+            None
+        };
+        let details = info::get_details(&gl, &info, query_renderer);
+        info!("Adapter details: {:?}", details);
+        let (heap_types, memory_heaps) = info::get_heaps(&details);
+
         let adapter_info = c::AdapterInfo {
             name: info.platform_name.renderer.into(),
-            vendor: 0, // TODO
-            device: 0, // TODO
-            software_rendering: true, // not always true ..
+            vendor: details.vendor,
+            device: details.device,
+            software_rendering: details.software_rendering,
         };
 
         // create the shared context
@@ -277,21 +475,62 @@ impl Adapter {
             capabilities: caps,
             private_caps: private,
             handles: RefCell::new(handles),
+            messenger: RefCell::new(None),
+            stream_programs: RefCell::new(HashMap::new()),
+            vertex_arrays: RefCell::new(HashMap::new()),
         };
 
         Adapter {
             share: Rc::new(share),
             adapter_info: adapter_info,
-            queue_family: [(QueueFamily, QueueType::Graphics)],
+            heap_types: heap_types,
+            memory_heaps: memory_heaps,
+            queue_families: vec![(QueueFamily { transfer_worker: false }, QueueType::Graphics)],
+            debug: None,
+            transfer_context: RefCell::new(None),
         }
     }
+
+    /// Forward the driver debug output into `log` once the adapter is opened.
+    /// Falls back to checking `glGetError` after every command if `KHR_debug`
+    /// is not supported by the implementation.
+    pub fn enable_debug_output(&mut self, config: DebugConfig) {
+        self.debug = Some(config);
+    }
+
+    /// Expose a second queue family with a transfer queue, executing its
+    /// command buffers on a worker thread where the shared context is current.
+    /// The submissions are synchronized with the other queue by the semaphores.
+    pub fn enable_transfer_queue(&mut self, context: Box<SharedContext>) {
+        if !self.share.private_caps.sync_supported {
+            warn!("Sync objects are not supported, the transfer queue is disabled");
+            return
+        }
+        if self.transfer_context.get_mut().is_none() {
+            self.queue_families.push((QueueFamily { transfer_worker: true }, QueueType::Transfer));
+        }
+        *self.transfer_context.get_mut() = Some(context);
+    }
 }
 
 impl c::Adapter<Backend> for Adapter {
     fn open(&self, queue_descs: &[(&QueueFamily, QueueType, u32)]) -> c::Gpu<Backend> {
-        // Only support a single queue
-        assert_eq!(queue_descs.len(), 1);
-        assert!(queue_descs[0].2 <= 1);
+        // Only support a single queue per family
+        assert!(queue_descs.len() <= self.queue_families.len());
+        assert!(queue_descs.iter().all(|&(_, _, num)| num <= 1));
+
+        // the PSO descriptors are sized by the compile-time limits,
+        // which the implementation may not be able to satisfy,
+        // so the PSOs going beyond the capabilities fail to be created
+        let caps = &self.share.capabilities;
+        if caps.max_color_targets < c::MAX_COLOR_TARGETS {
+            warn!("Only {} color targets are supported, gfx allows up to {}",
+                caps.max_color_targets, c::MAX_COLOR_TARGETS);
+        }
+        if caps.max_vertex_attributes < c::MAX_VERTEX_ATTRIBUTES {
+            warn!("Only {} vertex attributes are supported, gfx allows up to {}",
+                caps.max_vertex_attributes, c::MAX_VERTEX_ATTRIBUTES);
+        }
 
         // initialize permanent states
         let gl = &self.share.context;
@@ -307,6 +546,15 @@ impl c::Adapter<Backend> for Adapter {
                 gl.Enable(gl::PROGRAM_POINT_SIZE);
             }
         }
+        let debug = self.debug.map(|mut config| {
+            if self.share.private_caps.debug_output_supported {
+                *self.share.messenger.borrow_mut() = Some(debug::Messenger::install(gl, config));
+            } else {
+                info!("Debug output is not supported, checking errors after each command");
+                config.check_commands = true;
+            }
+            config
+        });
         // create main VAO and bind it
         let mut vao = 0;
         if self.share.private_caps.array_buffer_supported {
@@ -322,34 +570,56 @@ impl c::Adapter<Backend> for Adapter {
             graphics_queues: Vec::new(),
             compute_queues: Vec::new(),
             transfer_queues: Vec::new(),
-            heap_types: Vec::new(), // TODO
-            memory_heaps: Vec::new(), // TODO
+            heap_types: self.heap_types.clone(),
+            memory_heaps: self.memory_heaps.clone(),
         };
 
-        let raw_queue = || {
+        let raw_queue = |family: &QueueFamily| {
+            let transfer = if family.transfer_worker {
+                match self.transfer_context.borrow_mut().take() {
+                    Some(context) => Some(transfer::Worker::spawn(
+                        context, self.share.capabilities, self.share.private_caps)),
+                    None => {
+                        error!("The shared context of the transfer queue is already in use");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
             CommandQueue {
                 share: self.share.clone(),
                 vao: vao,
+                emulated_vertex_array: None,
+                index_buffer: 0,
+                stream_output: None,
                 frame_handles: handle::Manager::new(),
-                max_resource_count: Some(999999),
+                max_resource_count: Some(handle::MAX_FRAME_RESOURCES),
+                debug: debug,
+                transfer: transfer,
+                transfers: Vec::new(),
             }
         };
 
-        let (_, queue_type, num_queues) = queue_descs[0];
-        for _ in 0..num_queues {
+        for &(family, queue_type, num_queues) in queue_descs {
+            if num_queues == 0 {
+                continue
+            }
+            // the worker can't render, it lacks the state of the main context
+            assert!(!family.transfer_worker || queue_type == QueueType::Transfer);
             unsafe {
                 match queue_type {
                     QueueType::General => {
-                        gpu.general_queues.push(c::GeneralQueue::new(raw_queue()));
+                        gpu.general_queues.push(c::GeneralQueue::new(raw_queue(family)));
                     }
                     QueueType::Graphics => {
-                        gpu.graphics_queues.push(c::GraphicsQueue::new(raw_queue()));
+                        gpu.graphics_queues.push(c::GraphicsQueue::new(raw_queue(family)));
                     }
                     QueueType::Compute => {
-                        gpu.compute_queues.push(c::ComputeQueue::new(raw_queue()));
+                        gpu.compute_queues.push(c::ComputeQueue::new(raw_queue(family)));
                     }
                     QueueType::Transfer => {
-                        gpu.transfer_queues.push(c::TransferQueue::new(raw_queue()));
+                        gpu.transfer_queues.push(c::TransferQueue::new(raw_queue(family)));
                     }
                 }
             }
@@ -363,15 +633,26 @@ impl c::Adapter<Backend> for Adapter {
     }
 
     fn get_queue_families(&self) -> &[(QueueFamily, QueueType)] {
-        &self.queue_family
+        &self.queue_families
     }
 }
 
 pub struct CommandQueue {
     share: Rc<Share>,
     vao: ArrayBuffer,
+    /// Attributes bound when the vertex array objects are emulated, if known.
+    emulated_vertex_array: Option<(VertexArray, VertexOffsets)>,
+    /// Index buffer bound by the last command, which is a part of the vertex array state.
+    index_buffer: Buffer,
+    /// Capture of the vertices started by the current command buffer.
+    stream_output: Option<StreamOutput>,
     frame_handles: handle::Manager<Resources>,
     max_resource_count: Option<usize>,
+    debug: Option<DebugConfig>,
+    /// Worker executing the submissions, for the transfer queue.
+    transfer: Option<transfer::Worker>,
+    /// Resources of the transfer submissions, until their fence is signalled.
+    transfers: Vec<(handle::Fence<Resources>, handle::Manager<Resources>)>,
 }
 
 impl CommandQueue {
@@ -380,17 +661,28 @@ impl CommandQueue {
     pub unsafe fn with_gl<F: FnMut(&gl::Gl)>(&mut self, mut fun: F) {
         self.reset_state();
         fun(&self.share.context);
+        self.emulated_vertex_array = None;
     }
 
-    fn bind_attribute(&mut self, slot: c::AttributeSlot, buffer: Buffer, bel: BufferElement) {
+    fn bind_attribute(&mut self, slot: c::AttributeSlot, buffer: Buffer, bel: BufferElement,
+                      buffer_offset: c::pso::BufferOffset) {
         use core::format::SurfaceType as S;
         use core::format::ChannelType as C;
+        let half_float = if self.share.info.is_gles2_tier() {
+            tex::HALF_FLOAT_OES
+        } else {
+            gl::HALF_FLOAT
+        };
         let (fm8, fm16, fm32) = match bel.elem.format.1 {
+            C::Int | C::Uint if !self.share.private_caps.integer_attribute_supported => {
+                error!("Integer attributes are not supported");
+                return
+            }
             C::Int | C::Inorm =>
                 (gl::BYTE, gl::SHORT, gl::INT),
             C::Uint | C::Unorm =>
                 (gl::UNSIGNED_BYTE, gl::UNSIGNED_SHORT, gl::UNSIGNED_INT),
-            C::Float => (gl::ZERO, gl::HALF_FLOAT, gl::FLOAT),
+            C::Float => (gl::ZERO, half_float, gl::FLOAT),
             C::Srgb => {
                 error!("Unsupported Srgb channel type");
                 return
@@ -415,7 +707,7 @@ impl CommandQueue {
         };
         let gl = &self.share.context;
         unsafe { gl.BindBuffer(gl::ARRAY_BUFFER, buffer) };
-        let offset = bel.elem.offset as *const gl::types::GLvoid;
+        let offset = (bel.elem.offset as usize + buffer_offset) as *const gl::types::GLvoid;
         let stride = bel.desc.stride as gl::types::GLint;
         match bel.elem.format.1 {
             C::Int | C::Uint => unsafe {
@@ -445,12 +737,68 @@ impl CommandQueue {
         }
     }
 
+    /// Bind the vertex array object of the attributes, creating it on the first use.
+    /// The attributes of a cached vertex array are pointed at the new buffer offsets.
+    fn bind_vertex_array(&mut self, vertex_array: &VertexArray, offsets: &VertexOffsets) {
+        if !self.share.private_caps.array_buffer_supported {
+            // the attributes of the only vertex array are changed in place,
+            // skipping the ones that are already bound
+            let current = self.emulated_vertex_array.take();
+            for (slot, attribute) in vertex_array.iter().enumerate() {
+                if let Some((ref current, ref current_offsets)) = current {
+                    if current[slot] == *attribute && current_offsets[slot] == offsets[slot] {
+                        continue
+                    }
+                }
+                match *attribute {
+                    Some((buffer, bel)) => self.bind_attribute(slot as c::AttributeSlot, buffer, bel, offsets[slot]),
+                    None => unsafe {
+                        self.share.context.DisableVertexAttribArray(slot as gl::types::GLuint);
+                    },
+                }
+            }
+            self.emulated_vertex_array = Some((*vertex_array, *offsets));
+            return
+        }
+
+        let cached = self.share.vertex_arrays.borrow().get(vertex_array).cloned();
+        let (vao, current) = match cached {
+            Some((vao, current)) => {
+                unsafe { self.share.context.BindVertexArray(vao) };
+                (vao, Some(current))
+            },
+            None => {
+                let mut vao = 0;
+                unsafe {
+                    self.share.context.GenVertexArrays(1, &mut vao);
+                    self.share.context.BindVertexArray(vao);
+                }
+                (vao, None)
+            },
+        };
+        // the attributes of a new vertex array are disabled
+        for (slot, attribute) in vertex_array.iter().enumerate() {
+            if let Some((buffer, bel)) = *attribute {
+                if current.map_or(true, |current| current[slot] != offsets[slot]) {
+                    self.bind_attribute(slot as c::AttributeSlot, buffer, bel, offsets[slot]);
+                }
+            }
+        }
+        self.share.vertex_arrays.borrow_mut().insert(*vertex_array, (vao, *offsets));
+        let gl = &self.share.context;
+        unsafe { gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.index_buffer) };
+    }
+
     fn bind_target(&mut self, point: gl::types::GLenum, attachment: gl::types::GLenum, view: &TargetView) {
         let gl = &self.share.context;
         match view {
             &TargetView::Surface(surface) => unsafe {
                 gl.FramebufferRenderbuffer(point, attachment, gl::RENDERBUFFER, surface);
             },
+            &TargetView::Texture2D(texture, level, target) => unsafe {
+                gl.FramebufferTexture2D(point, attachment, target, texture,
+                                        level as gl::types::GLint);
+            },
             &TargetView::Texture(texture, level) => unsafe {
                 gl.FramebufferTexture(point, attachment, texture,
                                       level as gl::types::GLint);
@@ -465,7 +813,35 @@ impl CommandQueue {
 
     fn unbind_target(&mut self, point: gl::types::GLenum, attachment: gl::types::GLenum) {
         let gl = &self.share.context;
-        unsafe { gl.FramebufferTexture(point, attachment, 0, 0) };
+        if self.share.private_caps.framebuffer_texture_supported {
+            unsafe { gl.FramebufferTexture(point, attachment, 0, 0) };
+        } else {
+            unsafe { gl.FramebufferTexture2D(point, attachment, gl::TEXTURE_2D, 0, 0) };
+        }
+    }
+
+    /// Get the framebuffer binding point, as the separate draw and read ones
+    /// may not be supported.
+    fn framebuffer_point(&self, point: gl::types::GLenum) -> gl::types::GLenum {
+        if self.share.private_caps.framebuffer_blit_supported {
+            point
+        } else {
+            gl::FRAMEBUFFER
+        }
+    }
+
+    /// End the capture of the vertices, which can't outlive the command buffer.
+    fn end_stream_output(&mut self) {
+        if let Some(so) = self.stream_output.take() {
+            let gl = &self.share.context;
+            unsafe {
+                gl.EndTransformFeedback();
+                gl.BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, 0);
+                if so.discard {
+                    gl.Disable(gl::RASTERIZER_DISCARD);
+                }
+            }
+        }
     }
 
     fn reset_state(&mut self) {
@@ -527,7 +903,11 @@ impl CommandQueue {
                         flags |= gl::DEPTH_BUFFER_BIT;
                         unsafe  {
                             gl.DepthMask(gl::TRUE);
-                            gl.ClearDepth(d as gl::types::GLdouble);
+                            if self.share.info.version.is_embedded {
+                                gl.ClearDepthf(d);
+                            } else {
+                                gl.ClearDepth(d as gl::types::GLdouble);
+                            }
                         }
                     }
                     if let Some(s) = stencil {
@@ -551,8 +931,24 @@ impl CommandQueue {
             Command::BindResourceView(pso::ResourceViewParam(view, _, slot)) => unsafe {
                 self.share.context.ActiveTexture(gl::TEXTURE0 + slot as gl::types::GLenum);
                 self.share.context.BindTexture(view.bind, view.object);
+                // the mode is a texture state, so it's shared by all the views without their own object
+                if let Some(mode) = view.depth_stencil_mode {
+                    self.share.context.TexParameteri(view.bind, gl::DEPTH_STENCIL_TEXTURE_MODE,
+                                                     mode as gl::types::GLint);
+                }
+            },
+            Command::BindUnorderedView(pso::UnorderedViewParam(view, _, slot)) => unsafe {
+                let gl = &self.share.context;
+                match view {
+                    UnorderedView::Buffer(buffer) => {
+                        gl.BindBufferBase(gl::SHADER_STORAGE_BUFFER, slot as gl::types::GLuint, buffer);
+                    },
+                    UnorderedView::Image(texture, format) => {
+                        gl.BindImageTexture(slot as gl::types::GLuint, texture, 0, gl::TRUE, 0,
+                                            gl::READ_WRITE, format);
+                    },
+                }
             },
-            Command::BindUnorderedView(_uav) => unimplemented!(),
             Command::BindSampler(pso::SamplerParam(sampler, _, slot), bind_opt) => {
                 let gl = &self.share.context;
                 if self.share.private_caps.sampler_objects_supported {
@@ -568,7 +964,7 @@ impl CommandQueue {
                 }
             },
             Command::BindPixelTargets(pts) => {
-                let point = gl::DRAW_FRAMEBUFFER;
+                let point = self.framebuffer_point(gl::DRAW_FRAMEBUFFER);
                 for i in 0 .. c::MAX_COLOR_TARGETS {
                     let att = gl::COLOR_ATTACHMENT0 + i as gl::types::GLuint;
                     if let Some(ref target) = pts.colors[i] {
@@ -592,18 +988,18 @@ impl CommandQueue {
                     }
                 }
             },
-            Command::BindAttribute(slot, buffer,  bel) => {
-                self.bind_attribute(slot, buffer, bel);
-            },
-            Command::UnbindAttribute(slot) => unsafe {
-                self.share.context.DisableVertexAttribArray(slot as gl::types::GLuint);
+            Command::BindVertexArray(pointer) => {
+                let (vertex_array, offsets) = *data_buf.get_vertex_array(pointer);
+                self.bind_vertex_array(&vertex_array, &offsets);
             },
             Command::BindIndex(buffer) => {
                 let gl = &self.share.context;
                 unsafe { gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, buffer) };
+                self.index_buffer = buffer;
             },
             Command::BindFrameBuffer(point, frame_buffer) => {
                 if self.share.private_caps.frame_buffer_supported {
+                    let point = self.framebuffer_point(point);
                     let gl = &self.share.context;
                     unsafe { gl.BindFramebuffer(point, frame_buffer) };
                 } else if frame_buffer != 0 {
@@ -615,14 +1011,28 @@ impl CommandQueue {
                 shade::bind_uniform(gl, loc as gl::types::GLint, uniform);
             },
             Command::SetDrawColorBuffers(num) => {
-                let mask = (1 << (num as usize)) - 1;
-                state::bind_draw_color_buffers(&self.share.context, mask);
+                if self.share.private_caps.draw_buffers_supported {
+                    let mask = (1 << (num as usize)) - 1;
+                    state::bind_draw_color_buffers(&self.share.context, mask);
+                } else if num > 1 {
+                    error!("Multiple color targets are not supported");
+                }
             },
             Command::SetRasterizer(rast) => {
                 state::bind_rasterizer(&self.share.context, &rast, self.share.info.version.is_embedded);
             },
-            Command::SetViewport(rect) => {
-                state::bind_viewport(&self.share.context, rect);
+            Command::SetMultisample(ms) => {
+                state::bind_multisample(&self.share.context, &ms, &self.share.private_caps);
+            },
+            Command::SetViewport(index, viewport) => {
+                let max_viewports = self.share.capabilities.max_viewports;
+                if (index as usize) < max_viewports {
+                    state::bind_viewport(&self.share.context, index, viewport, max_viewports > 1,
+                                         self.share.info.version.is_embedded);
+                } else {
+                    error!("Viewport {} is out of the supported {}", index,
+                           self.share.capabilities.max_viewports);
+                }
             },
             Command::SetScissor(rect) => {
                 state::bind_scissor(&self.share.context, rect);
@@ -633,16 +1043,22 @@ impl CommandQueue {
             Command::SetStencilState(stencil, refs, cull) => {
                 state::bind_stencil(&self.share.context, &stencil, refs, cull);
             },
-            Command::SetBlendState(slot, color) => {
+            Command::SetBlendState(slot, color, dual) => {
                 if self.share.capabilities.separate_blending_slots_supported {
-                    state::bind_blend_slot(&self.share.context, slot, color);
+                    state::bind_blend_slot(&self.share.context, slot, color, dual);
                 }else if slot == 0 {
                     //self.temp.color = color; //TODO
-                    state::bind_blend(&self.share.context, color);
+                    state::bind_blend(&self.share.context, color, dual);
                 }else if false {
                     error!("Separate blending slots are not supported");
                 }
             },
+            Command::SetLogicOp(op) => {
+                // unsupported logic ops are rejected at the PSO creation
+                if self.share.capabilities.logic_op_supported {
+                    state::bind_logic_op(&self.share.context, op);
+                }
+            },
             Command::SetBlendColor(color) => {
                 state::set_blend_color(&self.share.context, color);
             },
@@ -652,76 +1068,77 @@ impl CommandQueue {
                     gl.PatchParameteri(gl::PATCH_VERTICES, num as gl::types::GLint);
                 }
             },
-            Command::CopyBuffer(src, dst, src_offset, dst_offset, size) => {
+            Command::CopyBuffer(..) |
+            Command::CopyBufferToTexture(..) |
+            Command::CopyTextureToBuffer(..) |
+            Command::UpdateBuffer(..) |
+            Command::UpdateTexture(..) => {
+                transfer::execute(&self.share.context, &self.share.capabilities,
+                                  &self.share.private_caps, cmd, data_buf);
+            },
+            Command::GenerateMipmap(view) => {
+                tex::generate_mipmap(&self.share.context, view.object, view.bind);
+            },
+            Command::BeginStreamOutput(buffer, so) => unsafe {
                 let gl = &self.share.context;
-
-                if self.share.capabilities.copy_buffer_supported {
-                    unsafe {
-                        gl.BindBuffer(gl::COPY_READ_BUFFER, src);
-                        gl.BindBuffer(gl::COPY_WRITE_BUFFER, dst);
-                        gl.CopyBufferSubData(gl::COPY_READ_BUFFER,
-                                            gl::COPY_WRITE_BUFFER,
-                                            src_offset,
-                                            dst_offset,
-                                            size);
-                    }
-                } else {
-                    debug_assert!(self.share.private_caps.buffer_storage_supported == false);
-
-                    unsafe {
-                        let mut src_ptr = 0 as *mut ::std::os::raw::c_void;
-                        device::temporary_ensure_mapped(&mut src_ptr, gl::COPY_READ_BUFFER, src, memory::READ, gl);
-                        src_ptr.offset(src_offset);
-
-                        let mut dst_ptr = 0 as *mut ::std::os::raw::c_void;
-                        device::temporary_ensure_mapped(&mut dst_ptr, gl::COPY_WRITE_BUFFER, dst, memory::WRITE, gl);
-                        dst_ptr.offset(dst_offset);
-
-                        ::std::ptr::copy(src_ptr, dst_ptr, size as usize);
-
-                        device::temporary_ensure_unmapped(&mut src_ptr, gl::COPY_READ_BUFFER, src, gl);
-                        device::temporary_ensure_unmapped(&mut dst_ptr, gl::COPY_WRITE_BUFFER, dst, gl);
-                    }
+                if so.discard {
+                    gl.Enable(gl::RASTERIZER_DISCARD);
                 }
+                gl.BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, buffer);
+                gl.BeginTransformFeedback(so.mode);
+                self.stream_output = Some(so);
             },
-            Command::CopyBufferToTexture(src, src_offset, dst, kind, face, img) => {
-                match tex::copy_from_buffer(&self.share.context, dst, kind, face, &img, src, src_offset) {
-                    Ok(_) => (),
-                    Err(e) => error!("GL: {:?} failed: {:?}", cmd, e)
-                }
+            Command::EndStreamOutput => {
+                self.end_stream_output();
             },
-            Command::CopyTextureToBuffer(src, kind, face, img, dst, dst_offset) => {
-                match tex::copy_to_buffer(&self.share.context, src, kind, face, &img, dst, dst_offset) {
-                    Ok(_) => (),
-                    Err(e) => error!("GL: {:?} failed: {:?}", cmd, e)
-                }
+            Command::MemoryBarrier(barriers) => unsafe {
+                self.share.context.MemoryBarrier(barriers);
+            },
+            Command::BeginQuery(query) => unsafe {
+                let gl = &self.share.context;
+                gl.BeginQuery(self.share.occlusion_target(), query);
             },
-            Command::UpdateBuffer(buffer, pointer, offset) => {
-                let data = data_buf.get(pointer);
-                device::update_sub_buffer(&self.share.context, buffer,
-                    data.as_ptr(), data.len(), offset, buffer::Role::Vertex);
+            Command::EndQuery => unsafe {
+                let gl = &self.share.context;
+                gl.EndQuery(self.share.occlusion_target());
             },
-            Command::UpdateTexture(texture, kind, face, pointer, ref image) => {
-                let data = data_buf.get(pointer);
-                match tex::update_texture(&self.share.context, texture, kind, face, image, data) {
-                    Ok(_) => (),
-                    Err(e) => error!("GL: Texture({}) update failed: {:?}", texture, e),
+            Command::BeginConditional(query, mode) => {
+                // without the support the draw calls are executed unconditionally
+                if self.share.capabilities.conditional_render_supported {
+                    let gl = &self.share.context;
+                    unsafe { gl.BeginConditionalRender(query, mode) };
                 }
             },
-            Command::GenerateMipmap(view) => {
-                tex::generate_mipmap(&self.share.context, view.object, view.bind);
+            Command::EndConditional => {
+                if self.share.capabilities.conditional_render_supported {
+                    let gl = &self.share.context;
+                    unsafe { gl.EndConditionalRender() };
+                }
             },
             Command::Draw(primitive, start, count, instances) => {
                 let gl = &self.share.context;
+                let caps = &self.share.capabilities;
                 match instances {
-                    Some((num, base)) if self.share.capabilities.instance_call_supported => unsafe {
-                        gl.DrawArraysInstancedBaseInstance(
-                            primitive,
-                            start as gl::types::GLsizei,
-                            count as gl::types::GLsizei,
-                            num as gl::types::GLsizei,
-                            base as gl::types::GLuint,
-                        );
+                    Some((num, base)) if caps.instance_call_supported => unsafe {
+                        if base == 0 || !caps.instance_base_supported {
+                            if base != 0 {
+                                error!("Instance bases are not supported");
+                            }
+                            gl.DrawArraysInstanced(
+                                primitive,
+                                start as gl::types::GLsizei,
+                                count as gl::types::GLsizei,
+                                num as gl::types::GLsizei,
+                            );
+                        } else {
+                            gl.DrawArraysInstancedBaseInstance(
+                                primitive,
+                                start as gl::types::GLsizei,
+                                count as gl::types::GLsizei,
+                                num as gl::types::GLsizei,
+                                base as gl::types::GLuint,
+                            );
+                        }
                     },
                     Some(_) => {
                         error!("Instanced draw calls are not supported");
@@ -855,45 +1272,47 @@ impl CommandQueue {
                 ) };
             },
         }
-        if let Err(err) = self.share.check() {
-            panic!("Error {:?} executing command: {:?}", err, cmd)
+        self.check_command(cmd);
+    }
+
+    fn check_command(&self, cmd: &Command) {
+        if let Some(ref messenger) = *self.share.messenger.borrow() {
+            if let Some(message) = messenger.take_fatal() {
+                panic!("{} executing command: {:?}", message, cmd)
+            }
+        }
+        match self.debug {
+            Some(config) if config.check_commands => {
+                let gl = &self.share.context;
+                let err = Error::from_error_code(unsafe { gl.GetError() });
+                if err == Error::NoError {
+                    return
+                }
+                if config.panic_on_high {
+                    panic!("Error {:?} executing command: {:?}", err, cmd)
+                }
+                error!("Error {:?} executing command: {:?}", err, cmd);
+            },
+            _ => if let Err(err) = self.share.check() {
+                panic!("Error {:?} executing command: {:?}", err, cmd)
+            },
         }
     }
 
     fn before_submit<'a>(&mut self, gpu_access: &'a com::AccessInfo<Resources>)
                          -> c::SubmissionResult<com::AccessGuard<'a, Resources>> {
         let mut gpu_access = try!(gpu_access.take_accesses());
-        if self.share.private_caps.buffer_storage_supported {
-            // MappingKind::Persistent
-            self.ensure_mappings_flushed(&mut gpu_access);
-        } else {
+        // MappingKind::Persistent mappings are coherent, nothing to do
+        if !self.share.private_caps.buffer_storage_supported {
             // MappingKind::Temporary
             self.ensure_mappings_unmapped(&mut gpu_access);
         }
         Ok(gpu_access)
     }
 
-    // MappingKind::Persistent
-    fn ensure_mappings_flushed(&mut self, gpu_access: &mut com::AccessGuard<Resources>) {
-        let gl = &self.share.context;
-        for (buffer, mapping) in gpu_access.access_mapped_reads() {
-            let target = device::role_to_target(buffer.get_info().role);
-            let status = match &mut mapping.kind {
-                &mut MappingKind::Persistent(ref mut status) => status,
-                _ => unreachable!(),
-            };
-
-            status.ensure_flushed(|| unsafe {
-                gl.BindBuffer(target, *buffer.resource());
-                let size = buffer.get_info().size as isize;
-                gl.FlushMappedBufferRange(target, 0, size);
-            });
-        }
-    }
-
     // MappingKind::Temporary
     fn ensure_mappings_unmapped(&mut self, gpu_access: &mut com::AccessGuard<Resources>) {
-        for (buffer, mapping) in gpu_access.access_mapped() {
+        for (buffer, _, mapping) in gpu_access.access_mapped() {
             let target = device::role_to_target(buffer.get_info().role);
             device::temporary_ensure_unmapped(&mut mapping.pointer,
                                                target,
@@ -906,22 +1325,13 @@ impl CommandQueue {
         if self.share.private_caps.buffer_storage_supported {
             // MappingKind::Persistent
             if gpu_access.has_mapped_reads() || gpu_access.has_mapped_writes() {
-                if gpu_access.has_mapped_writes() {
-                    self.place_memory_barrier();
-                }
-
-                let fence = self.place_fence(); // TODO: do we need a fence here?
+                // the GPU writes are visible to the CPU once the fence is signalled
+                let fence = self.place_fence();
                 self.track_mapped_gpu_access(gpu_access, &fence);
             }
         }
     }
 
-    fn place_memory_barrier(&mut self) {
-        let gl = &self.share.context;
-        // TODO: other flags ?
-        unsafe { gl.MemoryBarrier(gl::CLIENT_MAPPED_BUFFER_BARRIER_BIT); }
-    }
-
     fn place_fence(&mut self) -> handle::Fence<Resources> {
         use core::handle::Producer;
 
@@ -929,7 +1339,7 @@ impl CommandQueue {
         let fence = unsafe {
             gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
         };
-        self.frame_handles.make_fence(Fence(fence))
+        self.frame_handles.make_fence(Fence::new(fence))
     }
 
     fn signal_fence(&mut self, fence: &handle::Fence<Resources>) {
@@ -939,7 +1349,7 @@ impl CommandQueue {
                 gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
             };
 
-            self.frame_handles.ref_fence(&fence).lock().unwrap().0 = sync;
+            self.frame_handles.ref_fence(&fence).lock().unwrap().set_sync(gl, sync);
         }
     }
 
@@ -947,20 +1357,62 @@ impl CommandQueue {
     fn track_mapped_gpu_access(&mut self,
                                gpu_access: &mut com::AccessGuard<Resources>,
                                fence: &handle::Fence<Resources>) {
-        for (_, mapping) in gpu_access.access_mapped() {
+        for (_, range, mapping) in gpu_access.access_mapped() {
             let status = match &mut mapping.kind {
                 &mut MappingKind::Persistent(ref mut status) => status,
                 _ => unreachable!(),
             };
-            status.gpu_access(fence.clone());
+            status.gpu_access(range.clone(), fence.clone());
+        }
+    }
+
+    /// Queue the submissions on the transfer worker. The fences and the
+    /// semaphores get signalled there, once the worker reaches them. The
+    /// resources referenced so far are kept alive until the worker is done.
+    unsafe fn submit_transfer<'a, I>(&mut self,
+                                     submit_infos: I,
+                                     fence: Option<&handle::Fence<Resources>>,
+                                     access: &com::AccessInfo<Resources>)
+    where I: Iterator<Item=c::RawSubmission<'a, Backend>> {
+        use core::handle::Producer;
+
+        let mut access = self.before_submit(access).unwrap();
+        let mut operations = Vec::new();
+        for submit in submit_infos {
+            for &(semaphore, _) in submit.wait_semaphores {
+                operations.push(transfer::Operation::Wait(semaphore.clone()));
+            }
+            for cb in submit.cmd_buffers {
+                operations.push(transfer::Operation::Execute((*cb.buf).clone(), (*cb.data).clone()));
+            }
+            for &semaphore in submit.signal_semaphores {
+                // the other queue has to wait for the worker to place the sync
+                self.frame_handles.ref_semaphore(semaphore).lock().unwrap().pending = true;
+                operations.push(transfer::Operation::Signal(semaphore.clone()));
+            }
+        }
+        if self.share.private_caps.buffer_storage_supported &&
+           (access.has_mapped_reads() || access.has_mapped_writes()) {
+            // MappingKind::Persistent
+            let mapping_fence = self.frame_handles.make_fence(Fence::pending());
+            self.track_mapped_gpu_access(&mut access, &mapping_fence);
+            operations.push(transfer::Operation::Fence(mapping_fence));
+        }
+        if let Some(fence) = fence {
+            self.frame_handles.ref_fence(fence).lock().unwrap().pending = true;
+            operations.push(transfer::Operation::Fence(fence.clone()));
         }
+        let done = self.frame_handles.make_fence(Fence::pending());
+        operations.push(transfer::Operation::Fence(done.clone()));
+        // the worker can only see the objects and data of the flushed commands
+        self.share.context.Flush();
+        self.transfer.as_mut().unwrap().submit(operations);
+        let handles = mem::replace(&mut self.frame_handles, handle::Manager::new());
+        self.transfers.push((done, handles));
     }
 
     fn wait_fence(&mut self, fence: &handle::Fence<Resources>) {
-        device::wait_fence(
-            &*self.frame_handles.ref_fence(&fence).lock().unwrap(),
-            &self.share.context,
-            1_000_000);
+        device::wait_fence(self.frame_handles.ref_fence(&fence), &self.share.context, 1_000_000);
     }
 }
 
@@ -971,24 +1423,46 @@ impl c::CommandQueue<Backend> for CommandQueue {
         fence: Option<&handle::Fence<Resources>>,
         access: &com::AccessInfo<Resources>,
     ) where I: Iterator<Item=c::RawSubmission<'a, Backend>> {
+        if self.transfer.is_some() {
+            return self.submit_transfer(submit_infos, fence, access)
+        }
+        let sync_supported = self.share.private_caps.sync_supported;
         let mut access = self.before_submit(access).unwrap();
+        let mut signalled = false;
         for submit in submit_infos {
+            if sync_supported {
+                for &(semaphore, _) in submit.wait_semaphores {
+                    wait_semaphore(&self.share.context, self.frame_handles.ref_semaphore(semaphore));
+                }
+            }
             for cb in submit.cmd_buffers {
                 self.reset_state();
                 for com in &*cb.buf {
                     self.process(com, &*cb.data);
                 }
+                self.end_stream_output();
+            }
+            if sync_supported {
+                for semaphore in submit.signal_semaphores {
+                    signal_semaphore(&self.share.context, self.frame_handles.ref_semaphore(semaphore));
+                    signalled = true;
+                }
             }
         }
         self.after_submit(&mut access);
         fence.map(|fence| self.signal_fence(fence));
+        if signalled {
+            // the transfer worker can only wait for the flushed syncs
+            unsafe { self.share.context.Flush() };
+        }
     }
 
     fn pin_submitted_resources(&mut self, man: &handle::Manager<Resources>) {
         self.frame_handles.extend(man);
         match self.max_resource_count {
             Some(c) if self.frame_handles.count() > c => {
-                error!("Way too many resources in the current frame. Did you call Device::cleanup()?");
+                error!("Way too many resources in the current frame ({}). Did you call Device::cleanup()?",
+                       self.frame_handles.counts());
                 self.max_resource_count = None;
             },
             _ => (),
@@ -997,7 +1471,22 @@ impl c::CommandQueue<Backend> for CommandQueue {
 
     fn cleanup(&mut self) {
         use core::handle::Producer;
+        {
+            let gl = &self.share.context;
+            let frame_handles = &mut self.frame_handles;
+            self.transfers.retain(|&(ref done, _)| {
+                let fence = frame_handles.ref_fence(done);
+                match device::wait_fence(fence, gl, 0) {
+                    gl::TIMEOUT_EXPIRED => true,
+                    _ => {
+                        fence.lock().unwrap().set_sync(gl, ptr::null());
+                        false
+                    },
+                }
+            });
+        }
         self.frame_handles.clear();
+        let deleted_buffers = RefCell::new(Vec::new());
         self.share.handles.borrow_mut().clean_with(&mut &self.share.context,
             |gl, buffer| {
                 buffer.mapping().map(|raw| {
@@ -1014,10 +1503,21 @@ impl c::CommandQueue<Backend> for CommandQueue {
                         }
                     }
                 });
+                deleted_buffers.borrow_mut().push(*buffer.resource());
                 unsafe { gl.DeleteBuffers(1, buffer.resource()) }
             },
             |gl, v| unsafe { gl.DeleteShader(*v) },
-            |gl, program| unsafe { gl.DeleteProgram(*program.resource()) },
+            |gl, program| {
+                let name = *program.resource();
+                let mut stream_programs = self.share.stream_programs.borrow_mut();
+                let derived = stream_programs.keys().filter(|key| key.0 == name)
+                                             .cloned().collect::<Vec<_>>();
+                for key in derived {
+                    let sop = stream_programs.remove(&key).unwrap();
+                    unsafe { gl.DeleteProgram(sop.program) };
+                }
+                unsafe { gl.DeleteProgram(name) }
+            },
             |_, _| {}, //PSO
             |gl, raw_texture| match raw_texture.resource() {
                 &NewTexture::Surface(ref suf) => unsafe { gl.DeleteRenderbuffers(1, suf) },
@@ -1030,15 +1530,237 @@ impl c::CommandQueue<Backend> for CommandQueue {
             |_, _| {}, //RTV
             |_, _| {}, //DSV
             |gl, v| unsafe { if v.object != 0 { gl.DeleteSamplers(1, &v.object) }},
-            |gl, fence| { fence.get_mut().map(|fence| unsafe { gl.DeleteSync(fence.0) }); },
-            |_, _| {}, // Semaphore
+            |gl, fence| { fence.get_mut().map(|fence| fence.set_sync(gl, ptr::null())); },
+            |gl, semaphore| { semaphore.get_mut().map(|semaphore| semaphore.set_sync(gl, ptr::null())); },
+            |gl, query| unsafe { gl.DeleteQueries(1, query) },
         );
+
+        // the vertex arrays fetching from the deleted buffers can't be used anymore,
+        // by any of the queues
+        let deleted_buffers = deleted_buffers.into_inner();
+        if !deleted_buffers.is_empty() {
+            let gl = &self.share.context;
+            self.share.vertex_arrays.borrow_mut().retain(|vertex_array, &mut (vao, _)| {
+                let stale = vertex_array.iter().any(|attribute| match *attribute {
+                    Some((buffer, _)) => deleted_buffers.contains(&buffer),
+                    None => false,
+                });
+                if stale {
+                    unsafe { gl.DeleteVertexArrays(1, &vao) };
+                }
+                !stale
+            });
+        }
     }
 }
 
 #[allow(missing_copy_implementations)]
-pub struct QueueFamily;
+pub struct QueueFamily {
+    /// Whether the queue executes the submissions on the transfer worker.
+    transfer_worker: bool,
+}
 
 impl c::QueueFamily for QueueFamily {
     fn num_queues(&self) -> u32 { 1 }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use core::{self as c, handle};
+    use core::command::ClearColor;
+    use core::format::{ChannelType, Format, SurfaceType};
+    use core::pso::{Element, VertexBufferDesc};
+    use core::target::Rect;
+    use command::{Command, DataBuffer};
+    use {gl, mock, Adapter, BufferElement, CommandQueue, Fence, Share, TargetView};
+
+    fn make_queue(driver: mock::Driver) -> CommandQueue {
+        mock::install(driver);
+        let adapter = Adapter::new(mock::get_proc_address);
+        mock::take_calls();
+        share_queue(adapter.share.clone())
+    }
+
+    fn share_queue(share: Rc<Share>) -> CommandQueue {
+        CommandQueue {
+            share: share,
+            vao: 0,
+            emulated_vertex_array: None,
+            index_buffer: 0,
+            stream_output: None,
+            frame_handles: handle::Manager::new(),
+            max_resource_count: None,
+            debug: None,
+            transfer: None,
+            transfers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_process_clear() {
+        let clear = Command::Clear(Some(ClearColor::Float([0.5, 0.0, 0.0, 1.0])), Some(1.0), None);
+
+        let mut queue = make_queue(mock::Driver::default());
+        queue.process(&clear, &DataBuffer::new());
+        assert_eq!(mock::take_calls(), vec![
+            "ColorMask(1, 1, 1, 1)".to_string(),
+            format!("ClearBufferfv({}, 0, 0.5)", gl::COLOR),
+            "DepthMask(1)".to_string(),
+            format!("ClearBufferfv({}, 0, 1)", gl::DEPTH),
+        ]);
+
+        let mut queue = make_queue(mock::Driver {
+            version: "2.1",
+            shading_language: "1.20",
+            .. mock::Driver::default()
+        });
+        queue.process(&clear, &DataBuffer::new());
+        assert_eq!(mock::take_calls(), vec![
+            "ColorMask(1, 1, 1, 1)".to_string(),
+            "ClearColor(0.5, 0, 0, 1)".to_string(),
+            "DepthMask(1)".to_string(),
+            "ClearDepth(1)".to_string(),
+            format!("Clear({})", gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT),
+        ]);
+
+        let mut queue = make_queue(mock::Driver {
+            version: "OpenGL ES 2.0",
+            shading_language: "OpenGL ES GLSL ES 1.00",
+            .. mock::Driver::default()
+        });
+        queue.process(&clear, &DataBuffer::new());
+        assert_eq!(mock::take_calls(), vec![
+            "ColorMask(1, 1, 1, 1)".to_string(),
+            "ClearColor(0.5, 0, 0, 1)".to_string(),
+            "DepthMask(1)".to_string(),
+            "ClearDepthf(1)".to_string(),
+            format!("Clear({})", gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT),
+        ]);
+    }
+
+    #[test]
+    fn test_bind_cube_face_es2() {
+        let mut queue = make_queue(mock::Driver {
+            version: "OpenGL ES 2.0",
+            shading_language: "OpenGL ES GLSL ES 1.00",
+            .. mock::Driver::default()
+        });
+        let view = TargetView::Texture2D(3, 1, gl::TEXTURE_CUBE_MAP_NEGATIVE_Y);
+        queue.bind_target(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, &view);
+        queue.unbind_target(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0);
+        assert_eq!(mock::take_calls(), vec![
+            format!("FramebufferTexture2D({}, {}, {}, 3, 1)",
+                    gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_CUBE_MAP_NEGATIVE_Y),
+            format!("FramebufferTexture2D({}, {}, {}, 0, 0)",
+                    gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D),
+        ]);
+    }
+
+    #[test]
+    fn test_write_dynamic_mapping() {
+        use core::Device as CoreDevice;
+        use core::buffer::Role;
+        use core::memory::{Bind, Usage};
+
+        mock::install(mock::Driver::default());
+        let adapter = Adapter::new(mock::get_proc_address);
+        let mut device = ::Device::new(adapter.share.clone());
+        mock::take_calls();
+        let buffer = device.create_buffer::<u8>(16, Role::Vertex, Usage::Dynamic, Bind::empty()).unwrap();
+        let flags = gl::DYNAMIC_STORAGE_BIT | gl::MAP_WRITE_BIT |
+                    gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+        assert!(mock::take_calls().contains(&format!("BufferStorage({}, 16, {})", gl::ARRAY_BUFFER, flags)));
+        {
+            let mut writer = device.write_mapping(&buffer).unwrap();
+            writer[5] = 7;
+        }
+        assert_eq!(mock::buffer_contents(*buffer.raw().resource())[5], 7);
+    }
+
+    #[test]
+    fn test_process_scissor() {
+        let mut queue = make_queue(mock::Driver::default());
+        let rect = Rect { x: 1, y: 2, w: 30, h: 40 };
+        queue.process(&Command::SetScissor(Some(rect)), &DataBuffer::new());
+        assert!(mock::is_enabled(gl::SCISSOR_TEST));
+        assert_eq!(mock::take_calls(), vec![
+            format!("Enable({})", gl::SCISSOR_TEST),
+            "Scissor(1, 2, 30, 40)".to_string(),
+        ]);
+        queue.process(&Command::SetScissor(None), &DataBuffer::new());
+        assert!(!mock::is_enabled(gl::SCISSOR_TEST));
+    }
+
+    #[test]
+    fn test_vertex_array_cache() {
+        let mut queue = make_queue(mock::Driver::default());
+        let bel = BufferElement {
+            desc: VertexBufferDesc { stride: 8, rate: 0 },
+            elem: Element { format: Format(SurfaceType::R32_G32, ChannelType::Float), offset: 0 },
+        };
+        let mut first = [None; c::MAX_VERTEX_ATTRIBUTES];
+        first[0] = Some((1, bel));
+        let mut second = first;
+        second[0] = Some((2, bel));
+
+        let mut offsets = [0; c::MAX_VERTEX_ATTRIBUTES];
+        offsets[0] = 64;
+
+        let mut data = DataBuffer::new();
+        let commands = [(first, [0; c::MAX_VERTEX_ATTRIBUTES]), (second, offsets), (first, offsets), (first, offsets)]
+            .iter()
+            .map(|&(va, offsets)| Command::BindVertexArray(data.add_vertex_array(va, offsets)))
+            .collect::<Vec<_>>();
+        for cmd in &commands {
+            queue.process(cmd, &data);
+        }
+        let calls = mock::take_calls();
+        let count = |name: &str| calls.iter().filter(|call| call.starts_with(name)).count();
+        assert_eq!(count("GenVertexArrays("), 2);
+        assert_eq!(count("BindVertexArray("), 4);
+        assert_eq!(calls.iter().filter_map(|call| {
+            if call.starts_with("VertexAttribPointer(") {
+                call.split(", ").last().map(|offset| offset.trim_right_matches(')').to_string())
+            } else {
+                None
+            }
+        }).collect::<Vec<_>>(), vec!["0", "64", "64"]);
+        assert_eq!(queue.share.vertex_arrays.borrow().len(), 2);
+
+        // the other queues of the context use the same vertex arrays
+        let mut other = share_queue(queue.share.clone());
+        other.process(&commands[3], &data);
+        let calls = mock::take_calls();
+        assert_eq!(calls.iter().filter(|call| call.starts_with("GenVertexArrays(")).count(), 0);
+        assert_eq!(calls.iter().filter(|call| call.starts_with("VertexAttribPointer(")).count(), 0);
+    }
+
+    #[test]
+    fn test_cleanup_pending_transfer() {
+        use core::handle::Producer;
+
+        let mut queue = make_queue(mock::Driver::default());
+        let query = queue.share.handles.borrow_mut().make_query(5);
+        let mut handles = handle::Manager::new();
+        handles.ref_query(&query);
+        let done = handles.make_fence(Fence::pending());
+        queue.transfers.push((done.clone(), handles));
+        drop(query);
+
+        queue.cleanup();
+        assert_eq!(mock::take_calls(), Vec::<String>::new());
+        assert_eq!(queue.transfers.len(), 1);
+
+        {
+            let mut fence = queue.frame_handles.ref_fence(&done).lock().unwrap();
+            fence.sync = 7 as gl::types::GLsync;
+            fence.pending = false;
+        }
+        queue.cleanup();
+        let calls = mock::take_calls();
+        assert!(calls.contains(&"DeleteSync(7)".to_string()));
+        assert!(calls.contains(&"DeleteQueries(1, 5)".to_string()));
+        assert!(queue.transfers.is_empty());
+    }
+}
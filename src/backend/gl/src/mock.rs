@@ -0,0 +1,404 @@
+// Copyright 2017 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A fake OpenGL implementation for the unit tests, loaded through the same
+//! `get_proc_address` hook as a real context.
+//!
+//! The driver is described by `install`, which answers the string and integer
+//! queries. The other functions record their calls, hand out object names and
+//! track the enabled capabilities. Missing functions are left unloaded, so
+//! calling them panics. The state is per thread, like a current context.
+
+#![allow(non_snake_case)]
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fmt::Display;
+use std::os::raw::c_void;
+use std::{ptr, slice};
+use gl;
+use gl::types::{GLbitfield, GLboolean, GLdouble, GLenum, GLfloat, GLint, GLintptr,
+                GLsizei, GLsizeiptr, GLsync, GLubyte, GLuint, GLuint64};
+
+/// Description of the fake driver.
+#[derive(Clone, Debug)]
+pub struct Driver {
+    pub vendor: &'static str,
+    pub renderer: &'static str,
+    pub version: &'static str,
+    pub shading_language: &'static str,
+    pub extensions: Vec<&'static str>,
+    /// Values returned by `glGetIntegerv`, zero for the others.
+    pub integers: Vec<(GLenum, GLint)>,
+}
+
+impl Default for Driver {
+    fn default() -> Driver {
+        Driver {
+            vendor: "gfx-rs",
+            renderer: "mock",
+            version: "4.5.0",
+            shading_language: "4.50",
+            extensions: Vec::new(),
+            integers: vec![
+                (gl::MAX_TEXTURE_SIZE, 16384),
+                (gl::MAX_3D_TEXTURE_SIZE, 2048),
+                (gl::MAX_ARRAY_TEXTURE_LAYERS, 2048),
+                (gl::MAX_DRAW_BUFFERS, 8),
+                (gl::MAX_VERTEX_ATTRIBS, 16),
+                (gl::MAX_UNIFORM_BLOCK_SIZE, 65536),
+                (gl::MAX_PATCH_VERTICES, 32),
+                (gl::MAX_VIEWPORTS, 16),
+                (gl::MAX_SAMPLES, 8),
+                (gl::MAX_INTEGER_SAMPLES, 8),
+            ],
+        }
+    }
+}
+
+struct State {
+    strings: HashMap<GLenum, *const GLubyte>,
+    extensions: Vec<*const GLubyte>,
+    integers: HashMap<GLenum, GLint>,
+    enabled: HashSet<GLenum>,
+    bound_buffers: HashMap<GLenum, GLuint>,
+    /// Contents of the buffers allocated with `glBufferStorage`.
+    buffers: HashMap<GLuint, Vec<u8>>,
+    next_name: GLuint,
+    calls: Vec<String>,
+}
+
+thread_local!(static STATE: RefCell<Option<State>> = RefCell::new(None));
+
+fn with<T, F: FnOnce(&mut State) -> T>(fun: F) -> T {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        fun(state.as_mut().expect("The mock driver is not installed"))
+    })
+}
+
+/// The strings stay alive until the end of the tests, like the ones of a driver.
+fn leak(s: &str) -> *const GLubyte {
+    CString::new(s).unwrap().into_raw() as *const GLubyte
+}
+
+/// Make the driver current on this thread, resetting the state.
+pub fn install(driver: Driver) {
+    let mut strings = HashMap::new();
+    strings.insert(gl::VENDOR, leak(driver.vendor));
+    strings.insert(gl::RENDERER, leak(driver.renderer));
+    strings.insert(gl::VERSION, leak(driver.version));
+    strings.insert(gl::SHADING_LANGUAGE_VERSION, leak(driver.shading_language));
+    strings.insert(gl::EXTENSIONS, leak(&driver.extensions.join(" ")));
+    let mut integers = driver.integers.iter().cloned().collect::<HashMap<_, _>>();
+    integers.insert(gl::NUM_EXTENSIONS, driver.extensions.len() as GLint);
+    let state = State {
+        strings: strings,
+        extensions: driver.extensions.iter().map(|e| leak(e)).collect(),
+        integers: integers,
+        enabled: HashSet::new(),
+        bound_buffers: HashMap::new(),
+        buffers: HashMap::new(),
+        next_name: 1,
+        calls: Vec::new(),
+    };
+    STATE.with(|s| *s.borrow_mut() = Some(state));
+}
+
+/// Take the calls recorded since the previous time, formatted as
+/// `Name(arg0, arg1, ...)` with the enumerations in decimal.
+pub fn take_calls() -> Vec<String> {
+    with(|state| state.calls.drain(..).collect())
+}
+
+/// Check if a capability is currently enabled.
+pub fn is_enabled(cap: GLenum) -> bool {
+    with(|state| state.enabled.contains(&cap))
+}
+
+/// Get the contents of a buffer allocated with `glBufferStorage`.
+pub fn buffer_contents(buffer: GLuint) -> Vec<u8> {
+    with(|state| state.buffers[&buffer].clone())
+}
+
+fn record(name: &str, args: &[&Display]) {
+    let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    with(|state| state.calls.push(format!("{}({})", name, args.join(", "))));
+}
+
+/// Get the address of a mock function, to be passed to `Adapter::new`.
+pub fn get_proc_address(symbol: &str) -> *const c_void {
+    if !symbol.starts_with("gl") {
+        return ptr::null()
+    }
+    let address = queries(&symbol[2..]);
+    if address.is_null() {
+        recorded(&symbol[2..])
+    } else {
+        address
+    }
+}
+
+fn queries(name: &str) -> *const c_void {
+    match name {
+        "GetString" => GetString as *const c_void,
+        "GetStringi" => GetStringi as *const c_void,
+        "GetIntegerv" => GetIntegerv as *const c_void,
+        "GetFloatv" => GetFloatv as *const c_void,
+        "GetError" => GetError as *const c_void,
+        "Enable" => Enable as *const c_void,
+        "Disable" => Disable as *const c_void,
+        "IsEnabled" => IsEnabled as *const c_void,
+        "BindBuffer" => BindBuffer as *const c_void,
+        "BufferStorage" => BufferStorage as *const c_void,
+        "MapBufferRange" => MapBufferRange as *const c_void,
+        "GenBuffers" => GenBuffers as *const c_void,
+        "GenTextures" => GenTextures as *const c_void,
+        "GenVertexArrays" => GenVertexArrays as *const c_void,
+        "GenFramebuffers" => GenFramebuffers as *const c_void,
+        "GenRenderbuffers" => GenRenderbuffers as *const c_void,
+        "ClearBufferfv" => ClearBufferfv as *const c_void,
+        "ClearBufferiv" => ClearBufferiv as *const c_void,
+        "ClearBufferuiv" => ClearBufferuiv as *const c_void,
+        "DeleteVertexArrays" => DeleteVertexArrays as *const c_void,
+        "DeleteQueries" => DeleteQueries as *const c_void,
+        "FenceSync" => FenceSync as *const c_void,
+        "ClientWaitSync" => ClientWaitSync as *const c_void,
+        "WaitSync" => WaitSync as *const c_void,
+        "DeleteSync" => DeleteSync as *const c_void,
+        "UniformMatrix2x3fv" => UniformMatrix2x3fv as *const c_void,
+        "VertexAttribPointer" => VertexAttribPointer as *const c_void,
+        "VertexAttribIPointer" => VertexAttribIPointer as *const c_void,
+        _ => ptr::null(),
+    }
+}
+
+extern "system" fn GetString(name: GLenum) -> *const GLubyte {
+    with(|state| state.strings.get(&name).cloned().unwrap_or(ptr::null()))
+}
+
+extern "system" fn GetStringi(name: GLenum, index: GLuint) -> *const GLubyte {
+    assert_eq!(name, gl::EXTENSIONS);
+    with(|state| state.extensions[index as usize])
+}
+
+extern "system" fn GetIntegerv(name: GLenum, data: *mut GLint) {
+    let value = with(|state| state.integers.get(&name).cloned().unwrap_or(0));
+    unsafe { *data = value };
+}
+
+extern "system" fn GetFloatv(name: GLenum, data: *mut GLfloat) {
+    let value = with(|state| state.integers.get(&name).cloned().unwrap_or(0));
+    unsafe { *data = value as GLfloat };
+}
+
+extern "system" fn GetError() -> GLenum {
+    gl::NO_ERROR
+}
+
+extern "system" fn Enable(cap: GLenum) {
+    record("Enable", &[&cap]);
+    with(|state| state.enabled.insert(cap));
+}
+
+extern "system" fn Disable(cap: GLenum) {
+    record("Disable", &[&cap]);
+    with(|state| state.enabled.remove(&cap));
+}
+
+extern "system" fn IsEnabled(cap: GLenum) -> GLboolean {
+    if is_enabled(cap) { gl::TRUE } else { gl::FALSE }
+}
+
+extern "system" fn BindBuffer(target: GLenum, buffer: GLuint) {
+    record("BindBuffer", &[&target, &buffer]);
+    with(|state| state.bound_buffers.insert(target, buffer));
+}
+
+extern "system" fn BufferStorage(target: GLenum, size: GLsizeiptr, data: *const c_void, flags: GLbitfield) {
+    record("BufferStorage", &[&target, &size, &flags]);
+    let contents = if data.is_null() {
+        vec![0; size as usize]
+    } else {
+        unsafe { slice::from_raw_parts(data as *const u8, size as usize) }.to_vec()
+    };
+    with(|state| {
+        let buffer = state.bound_buffers[&target];
+        state.buffers.insert(buffer, contents);
+    });
+}
+
+/// Map the storage itself, which is never reallocated, so every mapping is persistent.
+extern "system" fn MapBufferRange(target: GLenum, offset: GLintptr, length: GLsizeiptr,
+                                  access: GLbitfield) -> *mut c_void {
+    record("MapBufferRange", &[&target, &offset, &length, &access]);
+    with(|state| {
+        let buffer = state.bound_buffers[&target];
+        let contents = state.buffers.get_mut(&buffer).expect("The buffer has no storage");
+        assert!((offset + length) as usize <= contents.len());
+        unsafe { contents.as_mut_ptr().offset(offset) as *mut c_void }
+    })
+}
+
+macro_rules! generators {
+    ( $( $name:ident ),* ) => {
+        $(
+            extern "system" fn $name(n: GLsizei, names: *mut GLuint) {
+                let first = with(|state| {
+                    let first = state.next_name;
+                    state.next_name += n as GLuint;
+                    first
+                });
+                for i in 0 .. n as GLuint {
+                    unsafe { *names.offset(i as isize) = first + i };
+                }
+                record(stringify!($name), &[&n]);
+            }
+        )*
+    }
+}
+
+generators!(GenBuffers, GenTextures, GenVertexArrays, GenFramebuffers, GenRenderbuffers);
+
+extern "system" fn ClearBufferfv(buffer: GLenum, draw_buffer: GLint, value: *const GLfloat) {
+    let value = unsafe { *value };
+    record("ClearBufferfv", &[&buffer, &draw_buffer, &value]);
+}
+
+extern "system" fn ClearBufferiv(buffer: GLenum, draw_buffer: GLint, value: *const GLint) {
+    let value = unsafe { *value };
+    record("ClearBufferiv", &[&buffer, &draw_buffer, &value]);
+}
+
+extern "system" fn ClearBufferuiv(buffer: GLenum, draw_buffer: GLint, value: *const GLuint) {
+    let value = unsafe { *value };
+    record("ClearBufferuiv", &[&buffer, &draw_buffer, &value]);
+}
+
+extern "system" fn DeleteVertexArrays(n: GLsizei, arrays: *const GLuint) {
+    let first = unsafe { *arrays };
+    record("DeleteVertexArrays", &[&n, &first]);
+}
+
+extern "system" fn DeleteQueries(n: GLsizei, ids: *const GLuint) {
+    let first = unsafe { *ids };
+    record("DeleteQueries", &[&n, &first]);
+}
+
+/// The syncs are named like the other objects, and are always signalled.
+extern "system" fn FenceSync(condition: GLenum, flags: GLbitfield) -> GLsync {
+    let name = with(|state| {
+        state.next_name += 1;
+        state.next_name - 1
+    });
+    record("FenceSync", &[&condition, &flags]);
+    name as usize as GLsync
+}
+
+extern "system" fn ClientWaitSync(sync: GLsync, flags: GLbitfield, timeout: GLuint64) -> GLenum {
+    let name = sync as usize;
+    record("ClientWaitSync", &[&name, &flags, &timeout]);
+    gl::ALREADY_SIGNALED
+}
+
+extern "system" fn WaitSync(sync: GLsync, flags: GLbitfield, timeout: GLuint64) {
+    let name = sync as usize;
+    record("WaitSync", &[&name, &flags, &timeout]);
+}
+
+extern "system" fn DeleteSync(sync: GLsync) {
+    let name = sync as usize;
+    record("DeleteSync", &[&name]);
+}
+
+extern "system" fn UniformMatrix2x3fv(location: GLint, count: GLsizei, transpose: GLboolean,
+                                      value: *const GLfloat) {
+    let floats = (0 .. 6 * count as isize).map(|i| unsafe { *value.offset(i) }).collect::<Vec<_>>();
+    let mut args: Vec<&Display> = vec![&location, &count, &transpose];
+    args.extend(floats.iter().map(|v| v as &Display));
+    record("UniformMatrix2x3fv", &args);
+}
+
+extern "system" fn VertexAttribPointer(index: GLuint, size: GLint, ty: GLenum, normalized: GLboolean,
+                                       stride: GLsizei, pointer: *const c_void) {
+    let offset = pointer as usize;
+    record("VertexAttribPointer", &[&index, &size, &ty, &normalized, &stride, &offset]);
+}
+
+extern "system" fn VertexAttribIPointer(index: GLuint, size: GLint, ty: GLenum,
+                                        stride: GLsizei, pointer: *const c_void) {
+    let offset = pointer as usize;
+    record("VertexAttribIPointer", &[&index, &size, &ty, &stride, &offset]);
+}
+
+/// Functions taking only values, which are just recorded.
+macro_rules! recorded {
+    ( $( fn $name:ident( $( $arg:ident: $ty:ty ),* ); )* ) => {
+        $(
+            extern "system" fn $name( $( $arg: $ty ),* ) {
+                record(stringify!($name), &[ $( &$arg ),* ]);
+            }
+        )*
+
+        fn recorded(name: &str) -> *const c_void {
+            match name {
+                $( stringify!($name) => $name as *const c_void, )*
+                _ => ptr::null(),
+            }
+        }
+    }
+}
+
+recorded! {
+    fn ActiveTexture(texture: GLenum);
+    fn BindBufferBase(target: GLenum, index: GLuint, buffer: GLuint);
+    fn BindFramebuffer(target: GLenum, framebuffer: GLuint);
+    fn BindTexture(target: GLenum, texture: GLuint);
+    fn BindVertexArray(array: GLuint);
+    fn BlendColor(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat);
+    fn Clear(mask: GLbitfield);
+    fn ClearColor(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat);
+    fn ClearDepth(depth: GLdouble);
+    fn ClearDepthf(depth: GLfloat);
+    fn ClearStencil(stencil: GLint);
+    fn ColorMask(red: GLboolean, green: GLboolean, blue: GLboolean, alpha: GLboolean);
+    fn DepthMask(flag: GLboolean);
+    fn DepthRange(near: GLdouble, far: GLdouble);
+    fn DepthRangef(near: GLfloat, far: GLfloat);
+    fn DisableVertexAttribArray(index: GLuint);
+    fn EnableVertexAttribArray(index: GLuint);
+    fn Flush();
+    fn FramebufferTexture2D(target: GLenum, attachment: GLenum, textarget: GLenum, texture: GLuint, level: GLint);
+    fn PixelStorei(name: GLenum, param: GLint);
+    fn MemoryBarrier(barriers: GLbitfield);
+    fn Scissor(x: GLint, y: GLint, width: GLsizei, height: GLsizei);
+    fn ShaderStorageBlockBinding(program: GLuint, index: GLuint, binding: GLuint);
+    fn StencilMask(mask: GLuint);
+    fn TexParameteri(target: GLenum, name: GLenum, param: GLint);
+    fn TexStorage1D(target: GLenum, levels: GLsizei, format: GLenum, width: GLsizei);
+    fn TexStorage2D(target: GLenum, levels: GLsizei, format: GLenum, width: GLsizei, height: GLsizei);
+    fn TexStorage3D(target: GLenum, levels: GLsizei, format: GLenum, width: GLsizei, height: GLsizei, depth: GLsizei);
+    fn Uniform1d(location: GLint, v0: GLdouble);
+    fn Uniform1f(location: GLint, v0: GLfloat);
+    fn Uniform1i(location: GLint, v0: GLint);
+    fn Uniform1ui(location: GLint, v0: GLuint);
+    fn Uniform2i(location: GLint, v0: GLint, v1: GLint);
+    fn Uniform3i(location: GLint, v0: GLint, v1: GLint, v2: GLint);
+    fn Uniform4i(location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint);
+    fn UniformBlockBinding(program: GLuint, index: GLuint, binding: GLuint);
+    fn UseProgram(program: GLuint);
+    fn VertexAttribDivisor(index: GLuint, divisor: GLuint);
+    fn Viewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei);
+}
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::ffi;
 use std::iter::repeat;
 use core::{self as c, shade as s};
 use info::PrivateCaps;
@@ -83,10 +84,11 @@ pub fn create_shader(gl: &gl::Gl, stage: s::Stage, data: &[u8])
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 enum StorageType {
     Var(s::BaseType, s::ContainerType),
     Sampler(s::BaseType, s::TextureType, s::SamplerType),
+    Image(s::BaseType, s::TextureType),
     Unknown,
 }
 
@@ -119,6 +121,11 @@ impl StorageType {
             gl::BOOL_VEC3                    => Var(BaseType::Bool, ContainerType::Vector(3)),
             gl::BOOL_VEC4                    => Var(BaseType::Bool, ContainerType::Vector(4)),
 
+            gl::DOUBLE                       => Var(BaseType::F64,  ContainerType::Single),
+            gl::DOUBLE_VEC2                  => Var(BaseType::F64,  ContainerType::Vector(2)),
+            gl::DOUBLE_VEC3                  => Var(BaseType::F64,  ContainerType::Vector(3)),
+            gl::DOUBLE_VEC4                  => Var(BaseType::F64,  ContainerType::Vector(4)),
+
             gl::FLOAT_MAT2                   => Var(BaseType::F32,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 2, 2)),
             gl::FLOAT_MAT3                   => Var(BaseType::F32,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 3, 3)),
             gl::FLOAT_MAT4                   => Var(BaseType::F32,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 4, 4)),
@@ -129,7 +136,15 @@ impl StorageType {
             gl::FLOAT_MAT4x2                 => Var(BaseType::F32,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 4, 2)),
             gl::FLOAT_MAT4x3                 => Var(BaseType::F32,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 4, 3)),
 
-            // TODO: double matrices
+            gl::DOUBLE_MAT2                  => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 2, 2)),
+            gl::DOUBLE_MAT3                  => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 3, 3)),
+            gl::DOUBLE_MAT4                  => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 4, 4)),
+            gl::DOUBLE_MAT2x3                => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 2, 3)),
+            gl::DOUBLE_MAT2x4                => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 2, 4)),
+            gl::DOUBLE_MAT3x2                => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 3, 2)),
+            gl::DOUBLE_MAT3x4                => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 3, 4)),
+            gl::DOUBLE_MAT4x2                => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 4, 2)),
+            gl::DOUBLE_MAT4x3                => Var(BaseType::F64,  ContainerType::Matrix(MatrixFormat::ColumnMajor, 4, 3)),
 
             gl::SAMPLER_1D                   => Sampler(BaseType::F32, TextureType::D1(NoArray), SamplerType(NoCompare, NoRect)),
             gl::SAMPLER_1D_ARRAY             => Sampler(BaseType::F32, TextureType::D1(Array),   SamplerType(NoCompare, NoRect)),
@@ -150,8 +165,17 @@ impl StorageType {
             gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW=> Sampler(BaseType::F32, TextureType::Cube(Array),   SamplerType(Compare,   NoRect)),
             gl::SAMPLER_BUFFER               => Sampler(BaseType::F32, TextureType::Buffer,        SamplerType(NoCompare, NoRect)),
 
-            // TODO: int samplers
-            gl::INT_SAMPLER_BUFFER           => Sampler(BaseType::I32, TextureType::Buffer,        SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_1D                   => Sampler(BaseType::I32, TextureType::D1(NoArray), SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_1D_ARRAY             => Sampler(BaseType::I32, TextureType::D1(Array),   SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_2D                   => Sampler(BaseType::I32, TextureType::D2(NoArray, NoMultiSample), SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_2D_ARRAY             => Sampler(BaseType::I32, TextureType::D2(Array,   NoMultiSample), SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_2D_MULTISAMPLE       => Sampler(BaseType::I32, TextureType::D2(NoArray, MultiSample),   SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_2D_RECT              => Sampler(BaseType::I32, TextureType::D2(NoArray, NoMultiSample), SamplerType(NoCompare, Rect)),
+            gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY => Sampler(BaseType::I32, TextureType::D2(Array,   MultiSample),   SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_3D                   => Sampler(BaseType::I32, TextureType::D3,            SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_CUBE                 => Sampler(BaseType::I32, TextureType::Cube(NoArray), SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_CUBE_MAP_ARRAY       => Sampler(BaseType::I32, TextureType::Cube(Array),   SamplerType(NoCompare, NoRect)),
+            gl::INT_SAMPLER_BUFFER               => Sampler(BaseType::I32, TextureType::Buffer,        SamplerType(NoCompare, NoRect)),
 
             gl::UNSIGNED_INT_SAMPLER_1D                   => Sampler(BaseType::U32, TextureType::D1(NoArray), SamplerType(NoCompare, NoRect)),
             gl::UNSIGNED_INT_SAMPLER_1D_ARRAY             => Sampler(BaseType::U32, TextureType::D1(Array),   SamplerType(NoCompare, NoRect)),
@@ -165,6 +189,42 @@ impl StorageType {
             gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY       => Sampler(BaseType::U32, TextureType::Cube(Array),   SamplerType(NoCompare, NoRect)),
             gl::UNSIGNED_INT_SAMPLER_BUFFER               => Sampler(BaseType::U32, TextureType::Buffer,        SamplerType(NoCompare, NoRect)),
 
+            gl::IMAGE_1D                     => Image(BaseType::F32, TextureType::D1(NoArray)),
+            gl::IMAGE_1D_ARRAY               => Image(BaseType::F32, TextureType::D1(Array)),
+            gl::IMAGE_2D                     => Image(BaseType::F32, TextureType::D2(NoArray, NoMultiSample)),
+            gl::IMAGE_2D_ARRAY               => Image(BaseType::F32, TextureType::D2(Array,   NoMultiSample)),
+            gl::IMAGE_2D_MULTISAMPLE         => Image(BaseType::F32, TextureType::D2(NoArray, MultiSample)),
+            gl::IMAGE_2D_MULTISAMPLE_ARRAY   => Image(BaseType::F32, TextureType::D2(Array,   MultiSample)),
+            gl::IMAGE_2D_RECT                => Image(BaseType::F32, TextureType::D2(NoArray, NoMultiSample)),
+            gl::IMAGE_3D                     => Image(BaseType::F32, TextureType::D3),
+            gl::IMAGE_CUBE                   => Image(BaseType::F32, TextureType::Cube(NoArray)),
+            gl::IMAGE_CUBE_MAP_ARRAY         => Image(BaseType::F32, TextureType::Cube(Array)),
+            gl::IMAGE_BUFFER                 => Image(BaseType::F32, TextureType::Buffer),
+
+            gl::INT_IMAGE_1D                   => Image(BaseType::I32, TextureType::D1(NoArray)),
+            gl::INT_IMAGE_1D_ARRAY             => Image(BaseType::I32, TextureType::D1(Array)),
+            gl::INT_IMAGE_2D                   => Image(BaseType::I32, TextureType::D2(NoArray, NoMultiSample)),
+            gl::INT_IMAGE_2D_ARRAY             => Image(BaseType::I32, TextureType::D2(Array,   NoMultiSample)),
+            gl::INT_IMAGE_2D_MULTISAMPLE       => Image(BaseType::I32, TextureType::D2(NoArray, MultiSample)),
+            gl::INT_IMAGE_2D_MULTISAMPLE_ARRAY => Image(BaseType::I32, TextureType::D2(Array,   MultiSample)),
+            gl::INT_IMAGE_2D_RECT              => Image(BaseType::I32, TextureType::D2(NoArray, NoMultiSample)),
+            gl::INT_IMAGE_3D                   => Image(BaseType::I32, TextureType::D3),
+            gl::INT_IMAGE_CUBE                 => Image(BaseType::I32, TextureType::Cube(NoArray)),
+            gl::INT_IMAGE_CUBE_MAP_ARRAY       => Image(BaseType::I32, TextureType::Cube(Array)),
+            gl::INT_IMAGE_BUFFER               => Image(BaseType::I32, TextureType::Buffer),
+
+            gl::UNSIGNED_INT_IMAGE_1D                   => Image(BaseType::U32, TextureType::D1(NoArray)),
+            gl::UNSIGNED_INT_IMAGE_1D_ARRAY             => Image(BaseType::U32, TextureType::D1(Array)),
+            gl::UNSIGNED_INT_IMAGE_2D                   => Image(BaseType::U32, TextureType::D2(NoArray, NoMultiSample)),
+            gl::UNSIGNED_INT_IMAGE_2D_ARRAY             => Image(BaseType::U32, TextureType::D2(Array,   NoMultiSample)),
+            gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE       => Image(BaseType::U32, TextureType::D2(NoArray, MultiSample)),
+            gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE_ARRAY => Image(BaseType::U32, TextureType::D2(Array,   MultiSample)),
+            gl::UNSIGNED_INT_IMAGE_2D_RECT              => Image(BaseType::U32, TextureType::D2(NoArray, NoMultiSample)),
+            gl::UNSIGNED_INT_IMAGE_3D                   => Image(BaseType::U32, TextureType::D3),
+            gl::UNSIGNED_INT_IMAGE_CUBE                 => Image(BaseType::U32, TextureType::Cube(NoArray)),
+            gl::UNSIGNED_INT_IMAGE_CUBE_MAP_ARRAY       => Image(BaseType::U32, TextureType::Cube(Array)),
+            gl::UNSIGNED_INT_IMAGE_BUFFER               => Image(BaseType::U32, TextureType::Buffer),
+
             _ => Unknown,
         }
     }
@@ -210,8 +270,22 @@ fn query_attributes(gl: &gl::Gl, prog: super::Program) -> Vec<s::AttributeVar> {
     .collect()
 }
 
-fn query_blocks(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program,
-                block_indices: &[gl::types::GLint], block_offsets: &[gl::types::GLint])
+/// Uniform locations of the textures and images, in the order
+/// of the reflected variables.
+struct Locations {
+    textures: Vec<gl::types::GLint>,
+    images: Vec<gl::types::GLint>,
+}
+
+/// Placement of the active uniforms inside the uniform blocks,
+/// indexed by the uniform index.
+struct BlockLayout {
+    indices: Vec<gl::types::GLint>,
+    offsets: Vec<gl::types::GLint>,
+    row_major: Vec<gl::types::GLint>,
+}
+
+fn query_blocks(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program, layout: &BlockLayout)
                 -> Vec<s::ConstantBufferVar> {
     let num = if caps.constant_buffer_supported {
         get_program_iv(gl, prog, gl::ACTIVE_UNIFORM_BLOCKS)
@@ -219,21 +293,13 @@ fn query_blocks(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program,
         0
     };
 
-    let bindings: Vec<gl::types::GLuint> = (0..num).map(
-        |idx| get_block_iv(gl, prog, idx as gl::types::GLuint, gl::UNIFORM_BLOCK_BINDING) as gl::types::GLuint
-    ).collect();
-    
-    // check if the shader specifies binding points manually via
-    // `layout(binding = n)`
-    let explicit_binding = bindings.iter().any(|&i| i > 0);
-
     // Some implementations seem to return the length of the uniform name without
     // null termination. Therefore we allocate an extra byte here.
     let max_len = get_program_iv(gl, prog, gl::ACTIVE_UNIFORM_MAX_LENGTH) + 1;
     let mut el_name = String::with_capacity(max_len as usize);
     el_name.extend(repeat('\0').take(max_len as usize));
 
-    (0 .. num as gl::types::GLuint).zip(bindings.iter()).map(|(idx, &bind)| {
+    (0 .. num as gl::types::GLuint).map(|idx| {
         // the string identifier for the block
         let name = unsafe {
             let size = get_block_iv(gl, prog, idx, gl::UNIFORM_BLOCK_NAME_LENGTH);
@@ -264,14 +330,8 @@ fn query_blocks(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program,
 
         let total_size = get_block_iv(gl, prog, idx, gl::UNIFORM_BLOCK_DATA_SIZE);
 
-        // if we don't detect any explicit layout bindings in the program, we
-        // automatically assign them a binding to their respective block indices
-        let slot = if explicit_binding {
-            bind
-        } else {
-            unsafe { gl.UniformBlockBinding(prog, idx, idx); }
-            idx
-        };
+        // the binding written in the shader, or zero
+        let slot = get_block_iv(gl, prog, idx, gl::UNIFORM_BLOCK_BINDING);
 
         info!("\t\tBlock[{}] = '{}' of size {}", slot, name, total_size);
         s::ConstantBufferVar {
@@ -279,7 +339,7 @@ fn query_blocks(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program,
             slot: slot as c::ConstantBufferSlot,
             size: total_size as usize,
             usage: usage,
-            elements: block_indices.iter().zip(block_offsets.iter()).enumerate().filter_map(|(i, (parent, offset))| {
+            elements: layout.indices.iter().zip(layout.offsets.iter()).enumerate().filter_map(|(i, (parent, offset))| {
                 if *parent == idx as gl::types::GLint {
                     let mut length = 0;
                     let mut size = 0;
@@ -290,6 +350,11 @@ fn query_blocks(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program,
                     };
                     let real_name = el_name[..length as usize].to_string();
                     let (base, container) = match StorageType::new(storage) {
+                        StorageType::Var(base, s::ContainerType::Matrix(_, cols, rows)) if layout.row_major[i] != 0 => {
+                            let cont = s::ContainerType::Matrix(s::MatrixFormat::RowMajor, cols, rows);
+                            info!("\t\t\tElement at {}\t= '{}'\t{:?}\t{:?}", *offset, real_name, base, cont);
+                            (base, cont)
+                        },
                         StorageType::Var(base, cont) => {
                             info!("\t\t\tElement at {}\t= '{}'\t{:?}\t{:?}", *offset, real_name, base, cont);
                             (base, cont)
@@ -312,34 +377,87 @@ fn query_blocks(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program,
     }).collect()
 }
 
-fn query_parameters(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program, usage: s::Usage)
-                    -> (Vec<s::ConstVar>, Vec<s::TextureVar>, Vec<s::SamplerVar>, Vec<gl::types::GLint>, Vec<gl::types::GLint>) {
+fn query_storage_blocks(gl: &gl::Gl, prog: super::Program, usage: s::Usage)
+                        -> Vec<s::UnorderedVar> {
+    use std::ptr;
+
+    let mut num = 0;
+    unsafe {
+        gl.GetProgramInterfaceiv(prog, gl::SHADER_STORAGE_BLOCK, gl::ACTIVE_RESOURCES, &mut num);
+    }
+    (0 .. num as gl::types::GLuint).map(|idx| {
+        let mut length = 0;
+        unsafe {
+            gl.GetProgramResourceiv(prog, gl::SHADER_STORAGE_BLOCK, idx, 1, &gl::NAME_LENGTH, 1, ptr::null_mut(), &mut length);
+        }
+        let mut name = String::with_capacity(length as usize);
+        name.extend(repeat('\0').take(length as usize));
+        unsafe {
+            gl.GetProgramResourceName(prog, gl::SHADER_STORAGE_BLOCK, idx, length, ptr::null_mut(),
+                                      (&name[..]).as_ptr() as *mut gl::types::GLchar);
+        }
+        // remove the \0
+        name.pop();
+
+        let mut slot = 0;
+        unsafe {
+            gl.GetProgramResourceiv(prog, gl::SHADER_STORAGE_BLOCK, idx, 1, &gl::BUFFER_BINDING, 1, ptr::null_mut(), &mut slot);
+        }
+        info!("\t\tStorage[{}] = '{}'", slot, name);
+        s::UnorderedVar {
+            name: name,
+            slot: slot as c::UnorderedViewSlot,
+            usage: usage,
+        }
+    }).collect()
+}
+
+fn query_parameters(gl: &gl::Gl, caps: &c::Capabilities, private: &PrivateCaps,
+                    prog: super::Program, usage: s::Usage)
+                    -> (Vec<s::ConstVar>, Vec<s::TextureVar>, Vec<s::UnorderedVar>, Vec<s::SamplerVar>,
+                        BlockLayout, Locations) {
     let mut uniforms = Vec::new();
     let mut textures = Vec::new();
+    let mut unordereds = Vec::new();
     let mut samplers = Vec::new();
     let total_num = get_program_iv(gl, prog, gl::ACTIVE_UNIFORMS);
     let indices: Vec<_> = (0..total_num as gl::types::GLuint).collect();
-    let mut block_indices = vec![-1 as gl::types::GLint; total_num as usize];
-    let mut block_offsets = vec![-1 as gl::types::GLint; total_num as usize];
+    let mut layout = BlockLayout {
+        indices: vec![-1 as gl::types::GLint; total_num as usize],
+        offsets: vec![-1 as gl::types::GLint; total_num as usize],
+        row_major: vec![0 as gl::types::GLint; total_num as usize],
+    };
     if caps.constant_buffer_supported {
         unsafe {
             gl.GetActiveUniformsiv(prog, total_num as gl::types::GLsizei,
                 (&indices[..]).as_ptr(), gl::UNIFORM_BLOCK_INDEX,
-                block_indices.as_mut_ptr());
+                layout.indices.as_mut_ptr());
             gl.GetActiveUniformsiv(prog, total_num as gl::types::GLsizei,
                 (&indices[..]).as_ptr(), gl::UNIFORM_OFFSET,
-                block_offsets.as_mut_ptr());
+                layout.offsets.as_mut_ptr());
+            gl.GetActiveUniformsiv(prog, total_num as gl::types::GLsizei,
+                (&indices[..]).as_ptr(), gl::UNIFORM_IS_ROW_MAJOR,
+                layout.row_major.as_mut_ptr());
         }
-        //TODO: UNIFORM_IS_ROW_MAJOR
     }
     // prepare the name string
     let max_len = get_program_iv(gl, prog, gl::ACTIVE_UNIFORM_MAX_LENGTH);
     let mut name = String::with_capacity(max_len as usize);
     name.extend(repeat('\0').take(max_len as usize));
-    let mut texture_slot = 0;
-    unsafe { gl.UseProgram(prog); } //TODO: passive mode
+    let mut locations = Locations {
+        textures: Vec::new(),
+        images: Vec::new(),
+    };
+    // the unit written in the shader, or zero
+    let get_unit = |loc| {
+        let mut unit = 0;
+        if private.explicit_binding_supported {
+            unsafe { gl.GetUniformiv(prog, loc, &mut unit) };
+        }
+        unit
+    };
     // walk the indices
-    for (&i, _) in indices.iter().zip(block_indices.iter()).filter(|&(_, &b)| b<0) {
+    for (&i, _) in indices.iter().zip(layout.indices.iter()).filter(|&(_, &b)| b<0) {
         let mut length = 0;
         let mut size = 0;
         let mut storage = 0;
@@ -364,12 +482,8 @@ fn query_parameters(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program, u
                 });
             },
             StorageType::Sampler(base, tex_type, samp_type) => {
-                let slot = texture_slot;
-                texture_slot += 1;
-                unsafe {
-                    gl.Uniform1i(loc, slot as gl::types::GLint);
-                }
-                //TODO: detect the texture slot instead of trying to set it up
+                let slot = get_unit(loc);
+                locations.textures.push(loc);
                 info!("\t\tSampler[{}] = '{}'\t{:?}\t{:?}", slot, real_name, base, tex_type);
                 textures.push(s::TextureVar {
                     name: real_name.clone(),
@@ -387,15 +501,107 @@ fn query_parameters(gl: &gl::Gl, caps: &c::Capabilities, prog: super::Program, u
                     });
                 }
             },
+            StorageType::Image(base, tex_type) => {
+                let slot = get_unit(loc);
+                locations.images.push(loc);
+                info!("\t\tImage[{}] = '{}'\t{:?}\t{:?}", slot, real_name, base, tex_type);
+                unordereds.push(s::UnorderedVar {
+                    name: real_name,
+                    slot: slot as c::UnorderedViewSlot,
+                    usage: usage,
+                });
+            },
             StorageType::Unknown => {
                 error!("Unrecognized uniform storage: {}", storage);
             },
         }
     }
-    (uniforms, textures, samplers, block_indices, block_offsets)
+    (uniforms, textures, unordereds, samplers, layout, locations)
+}
+
+/// Choose the slots of the resources of a kind, in the order of their keys.
+/// The resources bound in the shaders keep their slot, the others get the
+/// lowest slots left free, so that they don't collide. Returns the indices
+/// of the latter along with their new slot, and the number of the former.
+fn pick_slots<K: Ord, I>(resources: I) -> (Vec<(usize, usize)>, usize)
+    where I: Iterator<Item = (K, usize, bool)>
+{
+    let mut taken = Vec::new();
+    let mut free = Vec::new();
+    for (i, (key, slot, fixed)) in resources.enumerate() {
+        if fixed {
+            taken.push(slot);
+        } else {
+            free.push((key, i));
+        }
+    }
+    free.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut next = 0;
+    let picked = free.into_iter().map(|(_, i)| {
+        while taken.contains(&next) {
+            next += 1;
+        }
+        next += 1;
+        (i, next - 1)
+    }).collect();
+    (picked, taken.len())
+}
+
+/// Assign the slots of the resources that aren't bound in the shaders with
+/// `layout(binding = n)`, in the order of their names. The images come first
+/// in the unordered slots, followed by the storage blocks. The image units
+/// can't be changed on GLES, so the images keep the binding of the shader there.
+/// Only the non-zero bindings can be told apart from the default ones.
+fn assign_slots(gl: &gl::Gl, private: &PrivateCaps, prog: super::Program,
+                info: &mut s::ProgramInfo, locations: &Locations) -> s::SlotAssignment {
+    let explicit = private.explicit_binding_supported;
+    let num_images = locations.images.len();
+    let (textures, fixed_textures) = pick_slots(info.textures.iter().map(|t| {
+        (&t.name, t.slot as usize, explicit && t.slot != 0)
+    }));
+    let (blocks, fixed_blocks) = pick_slots(info.constant_buffers.iter().map(|cb| {
+        (&cb.name, cb.slot as usize, explicit && cb.slot != 0)
+    }));
+    let (unordereds, fixed_unordereds) = pick_slots(info.unordereds.iter().enumerate().map(|(i, u)| {
+        let is_image = i < num_images;
+        let fixed = (explicit && u.slot != 0) || (is_image && !private.image_unit_assignment_supported);
+        ((!is_image, &u.name), u.slot as usize, fixed)
+    }));
+
+    if !textures.is_empty() || unordereds.iter().any(|&(i, _)| i < num_images) {
+        unsafe { gl.UseProgram(prog) };
+    }
+    for &(i, slot) in &textures {
+        let var = &mut info.textures[i];
+        unsafe { gl.Uniform1i(locations.textures[i], slot as gl::types::GLint) };
+        var.slot = slot as c::ResourceViewSlot;
+        for sampler in info.samplers.iter_mut().filter(|sampler| sampler.name == var.name) {
+            sampler.slot = slot as c::SamplerSlot;
+        }
+    }
+    for &(i, slot) in &blocks {
+        unsafe { gl.UniformBlockBinding(prog, i as gl::types::GLuint, slot as gl::types::GLuint) };
+        info.constant_buffers[i].slot = slot as c::ConstantBufferSlot;
+    }
+    for &(i, slot) in &unordereds {
+        if i < num_images {
+            unsafe { gl.Uniform1i(locations.images[i], slot as gl::types::GLint) };
+        } else {
+            let index = (i - num_images) as gl::types::GLuint;
+            unsafe { gl.ShaderStorageBlockBinding(prog, index, slot as gl::types::GLuint) };
+        }
+        info.unordereds[i].slot = slot as c::UnorderedViewSlot;
+    }
+
+    let assigned = textures.len() + blocks.len() + unordereds.len();
+    match (fixed_textures + fixed_blocks + fixed_unordereds, assigned) {
+        (0, _) => s::SlotAssignment::Automatic,
+        (_, 0) => s::SlotAssignment::Explicit,
+        (_, _) => s::SlotAssignment::Mixed,
+    }
 }
 
-fn query_outputs(gl: &gl::Gl, prog: super::Program) -> (Vec<s::OutputVar>, bool) {
+fn query_outputs(gl: &gl::Gl, prog: super::Program, dual_source: bool) -> (Vec<s::OutputVar>, bool) {
     use std::ptr;
 
     let mut out_depth = false;
@@ -446,6 +652,17 @@ fn query_outputs(gl: &gl::Gl, prog: super::Program) -> (Vec<s::OutputVar>, bool)
             }
         }
 
+        if dual_source {
+            let mut location_index = 0;
+            unsafe {
+                gl.GetProgramResourceiv(prog, gl::PROGRAM_OUTPUT, i, 1, &gl::LOCATION_INDEX, 1, ptr::null_mut(), &mut location_index);
+            }
+            // the second source of dual-source blending belongs to the target at the same location
+            if location_index == 1 {
+                continue;
+            }
+        }
+
         if let StorageType::Var(base, container) = StorageType::new(type_ as u32) {
             out.push(s::OutputVar{
                 name: name,
@@ -475,13 +692,24 @@ pub fn get_program_log(gl: &gl::Gl, name: super::Program) -> String {
 }
 
 pub fn create_program(gl: &gl::Gl, caps: &c::Capabilities, private: &PrivateCaps,
-                      shaders: &[super::Shader], usage: s::Usage)
+                      shaders: &[super::Shader], usage: s::Usage, varyings: &[String])
                       -> Result<(::Program, s::ProgramInfo), s::CreateProgramError> {
     let name = unsafe { gl.CreateProgram() };
     for &sh in shaders {
         unsafe { gl.AttachShader(name, sh) };
     }
 
+    if !varyings.is_empty() {
+        let names = varyings.iter()
+                            .map(|v| ffi::CString::new(v.as_bytes()).unwrap())
+                            .collect::<Vec<_>>();
+        let pointers = names.iter().map(|n| n.as_ptr()).collect::<Vec<_>>();
+        unsafe {
+            gl.TransformFeedbackVaryings(name, pointers.len() as gl::types::GLsizei,
+                                         pointers.as_ptr(), gl::INTERLEAVED_ATTRIBS);
+        }
+    }
+
     if !private.program_interface_supported && private.frag_data_location_supported {
         for i in 0..c::MAX_COLOR_TARGETS {
             let color_name = format!("Target{}\0", i);
@@ -501,21 +729,26 @@ pub fn create_program(gl: &gl::Gl, caps: &c::Capabilities, private: &PrivateCaps
             warn!("\tLog: {}", log);
         }
 
-        let (uniforms, textures, samplers, block_indices, block_offsets) =
-            query_parameters(gl, caps, name, usage);
+        let (uniforms, textures, mut unordereds, samplers, layout, locations) =
+            query_parameters(gl, caps, private, name, usage);
+        if private.storage_buffer_supported {
+            unordereds.extend(query_storage_blocks(gl, name, usage));
+        }
         let mut info = s::ProgramInfo {
             vertex_attributes: query_attributes(gl, name),
             globals: uniforms,
-            constant_buffers: query_blocks(gl, caps, name, &block_indices, &block_offsets),
+            constant_buffers: query_blocks(gl, caps, name, &layout),
             textures: textures,
-            unordereds: Vec::new(), //TODO
+            unordereds: unordereds,
             samplers: samplers,
             outputs: Vec::new(),
             output_depth: false,
             knows_outputs: false,
+            slot_assignment: s::SlotAssignment::Automatic,
         };
+        info.slot_assignment = assign_slots(gl, private, name, &mut info, &locations);
         if private.program_interface_supported {
-            let (outs, od) = query_outputs(gl, name);
+            let (outs, od) = query_outputs(gl, name, caps.dual_source_blending_supported);
             info.outputs = outs;
             info.output_depth = od;
             info.knows_outputs = true;
@@ -528,22 +761,283 @@ pub fn create_program(gl: &gl::Gl, caps: &c::Capabilities, private: &PrivateCaps
     }
 }
 
+/// Get the names of the captured varyings, filling the gaps between them.
+fn stream_output_varyings(desc: &c::pso::StreamOutputDesc, skip_supported: bool)
+                          -> Result<Vec<String>, String> {
+    fn skip(varyings: &mut Vec<String>, bytes: c::pso::ElemOffset, supported: bool)
+            -> Result<(), String> {
+        if bytes == 0 {
+            return Ok(())
+        }
+        if bytes % 4 != 0 || !supported {
+            return Err(format!("Gaps of {} bytes between the captured varyings are not supported", bytes))
+        }
+        let mut components = bytes / 4;
+        while components > 0 {
+            let count = if components > 4 { 4 } else { components };
+            varyings.push(format!("gl_SkipComponents{}", count));
+            components -= count;
+        }
+        Ok(())
+    }
+
+    let mut varyings = Vec::with_capacity(desc.varyings.len());
+    let mut offset = 0;
+    for &(ref name, ref el) in desc.varyings.iter().filter_map(|v| v.as_ref()) {
+        if el.offset < offset {
+            return Err(format!("Varying {:?} overlaps the previous one, \
+                                the varyings have to be sorted by offset", name))
+        }
+        try!(skip(&mut varyings, el.offset - offset, skip_supported));
+        varyings.push(name.as_str().to_owned());
+        offset = el.offset + el.format.0.get_total_bits() as c::pso::ElemOffset / 8;
+    }
+    let stride = desc.stride as c::pso::ElemOffset;
+    if stride > offset {
+        try!(skip(&mut varyings, stride - offset, skip_supported));
+    }
+    Ok(varyings)
+}
+
+/// A copy of a program capturing its vertex data.
+#[derive(Clone, Copy, Debug)]
+pub struct StreamOutputProgram {
+    pub program: super::Program,
+    /// Primitive mode of the capture, if it's fixed by a geometry shader.
+    pub geometry_mode: Option<gl::types::GLenum>,
+}
+
+/// Create a copy of the program capturing the vertex data. The captured varyings
+/// have to be given before linking, so the original program can't be reused.
+pub fn create_stream_output_program(gl: &gl::Gl, caps: &c::Capabilities, private: &PrivateCaps,
+                                    program: super::Program, info: &s::ProgramInfo,
+                                    desc: &c::pso::StreamOutputDesc)
+                                    -> Result<StreamOutputProgram, String> {
+    let varyings = try!(stream_output_varyings(desc, private.stream_output_skip_supported));
+
+    let mut shaders = [0; 5];
+    let mut count = 0;
+    unsafe {
+        gl.GetAttachedShaders(program, shaders.len() as gl::types::GLsizei,
+                              &mut count, shaders.as_mut_ptr());
+    }
+    let shaders = &shaders[.. count as usize];
+    let mut usage = s::Usage::empty();
+    for &sh in shaders {
+        let mut ty = 0;
+        unsafe { gl.GetShaderiv(sh, gl::SHADER_TYPE, &mut ty) };
+        usage = usage | match ty as gl::types::GLenum {
+            gl::VERTEX_SHADER => s::VERTEX,
+            gl::TESS_CONTROL_SHADER => s::HULL,
+            gl::TESS_EVALUATION_SHADER => s::DOMAIN,
+            gl::GEOMETRY_SHADER => s::GEOMETRY,
+            gl::FRAGMENT_SHADER => s::PIXEL,
+            _ => s::Usage::empty(),
+        };
+    }
+
+    let (name, new_info) = try!(create_program(gl, caps, private, shaders, usage, &varyings)
+        .map_err(|e| format!("Unable to capture {:?}: {}", varyings, e)));
+    // the PSO was linked against the reflection of the original program
+    if new_info != *info {
+        unsafe { gl.DeleteProgram(name) };
+        return Err("The capturing program is reflected differently from the original".to_owned())
+    }
+
+    let geometry_mode = if usage.contains(s::GEOMETRY) {
+        Some(match get_program_iv(gl, name, gl::GEOMETRY_OUTPUT_TYPE) as gl::types::GLenum {
+            gl::POINTS => gl::POINTS,
+            gl::LINE_STRIP => gl::LINES,
+            _ => gl::TRIANGLES,
+        })
+    } else {
+        None
+    };
+    Ok(StreamOutputProgram {
+        program: name,
+        geometry_mode: geometry_mode,
+    })
+}
+
 pub fn bind_uniform(gl: &gl::Gl, loc: gl::types::GLint, uniform: s::UniformValue) {
     use core::shade::UniformValue;
+    fn to_int(b: bool) -> gl::types::GLint { b as gl::types::GLint }
     match uniform {
         UniformValue::I32(val) => unsafe { gl.Uniform1i(loc, val) },
+        UniformValue::U32(val) => unsafe { gl.Uniform1ui(loc, val) },
         UniformValue::F32(val) => unsafe { gl.Uniform1f(loc, val) },
+        UniformValue::F64(val) => unsafe { gl.Uniform1d(loc, val) },
+        UniformValue::Bool(val) => unsafe { gl.Uniform1i(loc, to_int(val)) },
 
         UniformValue::I32Vector2(val) => unsafe { gl.Uniform2iv(loc, 1, val.as_ptr()) },
         UniformValue::I32Vector3(val) => unsafe { gl.Uniform3iv(loc, 1, val.as_ptr()) },
         UniformValue::I32Vector4(val) => unsafe { gl.Uniform4iv(loc, 1, val.as_ptr()) },
 
+        UniformValue::U32Vector2(val) => unsafe { gl.Uniform2uiv(loc, 1, val.as_ptr()) },
+        UniformValue::U32Vector3(val) => unsafe { gl.Uniform3uiv(loc, 1, val.as_ptr()) },
+        UniformValue::U32Vector4(val) => unsafe { gl.Uniform4uiv(loc, 1, val.as_ptr()) },
+
         UniformValue::F32Vector2(val) => unsafe { gl.Uniform2fv(loc, 1, val.as_ptr()) },
         UniformValue::F32Vector3(val) => unsafe { gl.Uniform3fv(loc, 1, val.as_ptr()) },
         UniformValue::F32Vector4(val) => unsafe { gl.Uniform4fv(loc, 1, val.as_ptr()) },
 
+        UniformValue::F64Vector2(val) => unsafe { gl.Uniform2dv(loc, 1, val.as_ptr()) },
+        UniformValue::F64Vector3(val) => unsafe { gl.Uniform3dv(loc, 1, val.as_ptr()) },
+        UniformValue::F64Vector4(val) => unsafe { gl.Uniform4dv(loc, 1, val.as_ptr()) },
+
+        UniformValue::BoolVector2(v) => unsafe { gl.Uniform2i(loc, to_int(v[0]), to_int(v[1])) },
+        UniformValue::BoolVector3(v) => unsafe { gl.Uniform3i(loc, to_int(v[0]), to_int(v[1]), to_int(v[2])) },
+        UniformValue::BoolVector4(v) => unsafe { gl.Uniform4i(loc, to_int(v[0]), to_int(v[1]), to_int(v[2]), to_int(v[3])) },
+
         UniformValue::F32Matrix2(val) => unsafe{ gl.UniformMatrix2fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
         UniformValue::F32Matrix3(val) => unsafe{ gl.UniformMatrix3fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
         UniformValue::F32Matrix4(val) => unsafe{ gl.UniformMatrix4fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F32Matrix2x3(val) => unsafe{ gl.UniformMatrix2x3fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F32Matrix2x4(val) => unsafe{ gl.UniformMatrix2x4fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F32Matrix3x2(val) => unsafe{ gl.UniformMatrix3x2fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F32Matrix3x4(val) => unsafe{ gl.UniformMatrix3x4fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F32Matrix4x2(val) => unsafe{ gl.UniformMatrix4x2fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F32Matrix4x3(val) => unsafe{ gl.UniformMatrix4x3fv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+
+        UniformValue::F64Matrix2(val) => unsafe{ gl.UniformMatrix2dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F64Matrix3(val) => unsafe{ gl.UniformMatrix3dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F64Matrix4(val) => unsafe{ gl.UniformMatrix4dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F64Matrix2x3(val) => unsafe{ gl.UniformMatrix2x3dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F64Matrix2x4(val) => unsafe{ gl.UniformMatrix2x4dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F64Matrix3x2(val) => unsafe{ gl.UniformMatrix3x2dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F64Matrix3x4(val) => unsafe{ gl.UniformMatrix3x4dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F64Matrix4x2(val) => unsafe{ gl.UniformMatrix4x2dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+        UniformValue::F64Matrix4x3(val) => unsafe{ gl.UniformMatrix4x3dv(loc, 1, gl::FALSE, val[0].as_ptr()) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::shade::{BaseType, ContainerType, MatrixFormat, ProgramInfo, SamplerType,
+                      SlotAssignment, TextureType, UniformValue, UnorderedVar, PIXEL};
+    use core::shade::IsArray::*;
+    use core::shade::IsComparison::*;
+    use core::shade::IsMultiSample::*;
+    use core::shade::IsRect::*;
+    use {gl, info, mock};
+    use super::{assign_slots, bind_uniform, Locations, StorageType};
+
+    #[test]
+    fn test_storage_types() {
+        assert_eq!(StorageType::new(gl::DOUBLE_MAT4x3),
+                   StorageType::Var(BaseType::F64, ContainerType::Matrix(MatrixFormat::ColumnMajor, 4, 3)));
+        assert_eq!(StorageType::new(gl::UNSIGNED_INT_VEC2),
+                   StorageType::Var(BaseType::U32, ContainerType::Vector(2)));
+        assert_eq!(StorageType::new(gl::BOOL_VEC3),
+                   StorageType::Var(BaseType::Bool, ContainerType::Vector(3)));
+        assert_eq!(StorageType::new(gl::INT_SAMPLER_2D_ARRAY),
+                   StorageType::Sampler(BaseType::I32, TextureType::D2(Array, NoMultiSample),
+                                        SamplerType(NoCompare, NoRect)));
+    }
+
+    #[test]
+    fn test_bind_uniform() {
+        mock::install(mock::Driver::default());
+        let gl = gl::Gl::load_with(mock::get_proc_address);
+        bind_uniform(&gl, 1, UniformValue::U32(7));
+        bind_uniform(&gl, 2, UniformValue::F64(0.5));
+        bind_uniform(&gl, 3, UniformValue::BoolVector3([true, false, true]));
+        bind_uniform(&gl, 4, UniformValue::F32Matrix2x3([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]));
+        assert_eq!(mock::take_calls(), vec![
+            "Uniform1ui(1, 7)".to_string(),
+            "Uniform1d(2, 0.5)".to_string(),
+            "Uniform3i(3, 1, 0, 1)".to_string(),
+            "UniformMatrix2x3fv(4, 1, 0, 1, 2, 3, 4, 5, 6)".to_string(),
+        ]);
+    }
+
+    fn make_info(images: &[(&str, u8)], storages: &[&str]) -> ProgramInfo {
+        let vars = images.iter().cloned()
+            .chain(storages.iter().map(|&name| (name, 0)))
+            .map(|(name, slot)| UnorderedVar {
+                name: name.to_string(),
+                slot: slot,
+                usage: PIXEL,
+            })
+            .collect();
+        ProgramInfo {
+            vertex_attributes: Vec::new(),
+            globals: Vec::new(),
+            constant_buffers: Vec::new(),
+            textures: Vec::new(),
+            unordereds: vars,
+            samplers: Vec::new(),
+            outputs: Vec::new(),
+            output_depth: false,
+            knows_outputs: false,
+            slot_assignment: SlotAssignment::Automatic,
+        }
+    }
+
+    fn assign(driver: mock::Driver, info: &mut ProgramInfo) -> Vec<String> {
+        mock::install(driver);
+        let gl = gl::Gl::load_with(mock::get_proc_address);
+        let (_, _, private) = info::get(&gl);
+        mock::take_calls();
+        let locations = Locations {
+            textures: Vec::new(),
+            images: vec![7, 5],
+        };
+        info.slot_assignment = assign_slots(&gl, &private, 1, info, &locations);
+        mock::take_calls()
+    }
+
+    #[test]
+    fn test_assign_image_slots() {
+        let mut info = make_info(&[("b", 0), ("a", 0)], &["c"]);
+        let calls = assign(mock::Driver::default(), &mut info);
+        assert_eq!(calls, vec![
+            "UseProgram(1)".to_string(),
+            "Uniform1i(5, 0)".to_string(),
+            "Uniform1i(7, 1)".to_string(),
+            "ShaderStorageBlockBinding(1, 0, 2)".to_string(),
+        ]);
+        let slots = info.unordereds.iter().map(|u| u.slot).collect::<Vec<_>>();
+        assert_eq!(slots, vec![1, 0, 2]);
+        assert_eq!(info.slot_assignment, SlotAssignment::Automatic);
+    }
+
+    #[test]
+    fn test_assign_mixed_slots() {
+        let mut info = make_info(&[("b", 2), ("a", 0)], &["c"]);
+        let calls = assign(mock::Driver::default(), &mut info);
+        assert_eq!(calls, vec![
+            "UseProgram(1)".to_string(),
+            "Uniform1i(5, 0)".to_string(),
+            "ShaderStorageBlockBinding(1, 0, 1)".to_string(),
+        ]);
+        let slots = info.unordereds.iter().map(|u| u.slot).collect::<Vec<_>>();
+        assert_eq!(slots, vec![2, 0, 1]);
+        assert_eq!(info.slot_assignment, SlotAssignment::Mixed);
+    }
+
+    #[test]
+    fn test_keep_image_slots_es() {
+        let driver = mock::Driver {
+            version: "OpenGL ES 3.1",
+            shading_language: "OpenGL ES GLSL ES 3.10",
+            .. mock::Driver::default()
+        };
+        let mut info = make_info(&[("b", 3), ("a", 1)], &["c"]);
+        let calls = assign(driver, &mut info);
+        assert_eq!(calls, vec![
+            "ShaderStorageBlockBinding(1, 0, 0)".to_string(),
+        ]);
+        let slots = info.unordereds.iter().map(|u| u.slot).collect::<Vec<_>>();
+        assert_eq!(slots, vec![3, 1, 0]);
+        assert_eq!(info.slot_assignment, SlotAssignment::Mixed);
+    }
+
+    #[test]
+    fn test_keep_explicit_slots() {
+        let mut info = make_info(&[("b", 1), ("a", 2)], &[]);
+        let calls = assign(mock::Driver::default(), &mut info);
+        assert!(calls.is_empty());
+        assert_eq!(info.slot_assignment, SlotAssignment::Explicit);
     }
 }
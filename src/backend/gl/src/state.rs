@@ -13,11 +13,14 @@
 // limitations under the License.
 
 use core::{MAX_COLOR_TARGETS, ColorSlot};
+use core::command::Viewport;
+use core::pso::{DualSource, LogicOp, MultisampleInfo};
 use core::state as s;
 use core::state::{BlendValue, Comparison, CullFace, Equation,
                   Offset, RasterMethod, StencilOp, FrontFace};
 use core::target::{ColorValue, Rect, Stencil};
 use gl;
+use info::PrivateCaps;
 
 
 pub fn bind_raster_method(gl: &gl::Gl, method: s::RasterMethod, offset: Option<s::Offset>) {
@@ -73,6 +76,33 @@ pub fn bind_rasterizer(gl: &gl::Gl, r: &s::Rasterizer, is_embedded: bool) {
     }
 }
 
+pub fn bind_multisample(gl: &gl::Gl, ms: &MultisampleInfo, caps: &PrivateCaps) {
+    if ms.alpha_to_coverage {
+        unsafe { gl.Enable(gl::SAMPLE_ALPHA_TO_COVERAGE) };
+    } else {
+        unsafe { gl.Disable(gl::SAMPLE_ALPHA_TO_COVERAGE) };
+    }
+    if caps.sample_mask_supported {
+        if ms.sample_mask != !0 {
+            unsafe {
+                gl.Enable(gl::SAMPLE_MASK);
+                gl.SampleMaski(0, ms.sample_mask);
+            }
+        } else {
+            unsafe { gl.Disable(gl::SAMPLE_MASK) };
+        }
+    }
+    if caps.sample_shading_supported {
+        match ms.get_sample_shading() {
+            Some(fraction) => unsafe {
+                gl.Enable(gl::SAMPLE_SHADING);
+                gl.MinSampleShading(fraction);
+            },
+            None => unsafe { gl.Disable(gl::SAMPLE_SHADING) },
+        }
+    }
+}
+
 pub fn bind_draw_color_buffers(gl: &gl::Gl, mask: usize) {
     let attachments = [
         gl::COLOR_ATTACHMENT0,  gl::COLOR_ATTACHMENT1,  gl::COLOR_ATTACHMENT2,
@@ -94,13 +124,36 @@ pub fn bind_draw_color_buffers(gl: &gl::Gl, mask: usize) {
     unsafe { gl.DrawBuffers(count as gl::types::GLint, targets.as_ptr()) };
 }
 
-pub fn bind_viewport(gl: &gl::Gl, rect: Rect) {
-    unsafe { gl.Viewport(
-        rect.x as gl::types::GLint,
-        rect.y as gl::types::GLint,
-        rect.w as gl::types::GLint,
-        rect.h as gl::types::GLint
-    )};
+/// Binds a viewport. Without `indexed`, only the viewport 0 can be set and
+/// `glViewport` resets all the others, so use it when the viewport arrays
+/// are supported.
+pub fn bind_viewport(gl: &gl::Gl, index: u8, viewport: Viewport, indexed: bool, is_embedded: bool) {
+    let rect = viewport.rect;
+    if !indexed {
+        debug_assert_eq!(index, 0);
+        unsafe { gl.Viewport(
+            rect.x as gl::types::GLint,
+            rect.y as gl::types::GLint,
+            rect.w as gl::types::GLint,
+            rect.h as gl::types::GLint
+        )};
+        if is_embedded {
+            unsafe { gl.DepthRangef(viewport.min_depth, viewport.max_depth) };
+        } else {
+            unsafe { gl.DepthRange(viewport.min_depth as f64, viewport.max_depth as f64) };
+        }
+    } else {
+        let index = index as gl::types::GLuint;
+        unsafe {
+            gl.ViewportIndexedf(index,
+                rect.x as gl::types::GLfloat,
+                rect.y as gl::types::GLfloat,
+                rect.w as gl::types::GLfloat,
+                rect.h as gl::types::GLfloat
+            );
+            gl.DepthRangeIndexed(index, viewport.min_depth as f64, viewport.max_depth as f64);
+        }
+    }
 }
 
 pub fn bind_scissor(gl: &gl::Gl, rect: Option<Rect>) {
@@ -188,8 +241,12 @@ fn map_equation(eq: Equation) -> gl::types::GLenum {
     }
 }
 
-fn map_factor(factor: s::Factor) -> gl::types::GLenum {
+fn map_factor(factor: s::Factor, dual: bool) -> gl::types::GLenum {
     match factor {
+        s::Factor::ZeroPlus(BlendValue::SourceColor) if dual => gl::SRC1_COLOR,
+        s::Factor::OneMinus(BlendValue::SourceColor) if dual => gl::ONE_MINUS_SRC1_COLOR,
+        s::Factor::ZeroPlus(BlendValue::SourceAlpha) if dual => gl::SRC1_ALPHA,
+        s::Factor::OneMinus(BlendValue::SourceAlpha) if dual => gl::ONE_MINUS_SRC1_ALPHA,
         s::Factor::Zero                              => gl::ZERO,
         s::Factor::One                               => gl::ONE,
         s::Factor::ZeroPlus(BlendValue::SourceColor) => gl::SRC_COLOR,
@@ -208,7 +265,7 @@ fn map_factor(factor: s::Factor) -> gl::types::GLenum {
     }
 }
 
-pub fn bind_blend(gl: &gl::Gl, color: s::Color) {
+pub fn bind_blend(gl: &gl::Gl, color: s::Color, dual: DualSource) {
     match color.blend {
         Some(b) => unsafe {
             gl.Enable(gl::BLEND);
@@ -217,10 +274,10 @@ pub fn bind_blend(gl: &gl::Gl, color: s::Color) {
                 map_equation(b.alpha.equation)
             );
             gl.BlendFuncSeparate(
-                map_factor(b.color.source),
-                map_factor(b.color.destination),
-                map_factor(b.alpha.source),
-                map_factor(b.alpha.destination)
+                map_factor(b.color.source, dual.color_source),
+                map_factor(b.color.destination, dual.color_destination),
+                map_factor(b.alpha.source, dual.alpha_source),
+                map_factor(b.alpha.destination, dual.alpha_destination)
             );
         },
         None => unsafe {
@@ -235,7 +292,7 @@ pub fn bind_blend(gl: &gl::Gl, color: s::Color) {
     )};
 }
 
-pub fn bind_blend_slot(gl: &gl::Gl, slot: ColorSlot, color: s::Color) {
+pub fn bind_blend_slot(gl: &gl::Gl, slot: ColorSlot, color: s::Color, dual: DualSource) {
     let buf = slot as gl::types::GLuint;
     match color.blend {
         Some(b) => unsafe {
@@ -246,10 +303,10 @@ pub fn bind_blend_slot(gl: &gl::Gl, slot: ColorSlot, color: s::Color) {
                 map_equation(b.alpha.equation)
             );
             gl.BlendFuncSeparateiARB(buf,
-                map_factor(b.color.source),
-                map_factor(b.color.destination),
-                map_factor(b.alpha.source),
-                map_factor(b.alpha.destination)
+                map_factor(b.color.source, dual.color_source),
+                map_factor(b.color.destination, dual.color_destination),
+                map_factor(b.alpha.source, dual.alpha_source),
+                map_factor(b.alpha.destination, dual.alpha_destination)
             );
         },
         None => unsafe {
@@ -264,6 +321,40 @@ pub fn bind_blend_slot(gl: &gl::Gl, slot: ColorSlot, color: s::Color) {
     )};
 }
 
+fn map_logic_op(op: LogicOp) -> gl::types::GLenum {
+    match op {
+        LogicOp::Clear        => gl::CLEAR,
+        LogicOp::And          => gl::AND,
+        LogicOp::AndReverse   => gl::AND_REVERSE,
+        LogicOp::Copy         => gl::COPY,
+        LogicOp::AndInverted  => gl::AND_INVERTED,
+        LogicOp::Noop         => gl::NOOP,
+        LogicOp::Xor          => gl::XOR,
+        LogicOp::Or           => gl::OR,
+        LogicOp::Nor          => gl::NOR,
+        LogicOp::Equivalent   => gl::EQUIV,
+        LogicOp::Invert       => gl::INVERT,
+        LogicOp::OrReverse    => gl::OR_REVERSE,
+        LogicOp::CopyInverted => gl::COPY_INVERTED,
+        LogicOp::OrInverted   => gl::OR_INVERTED,
+        LogicOp::Nand         => gl::NAND,
+        LogicOp::Set          => gl::SET,
+    }
+}
+
+pub fn bind_logic_op(gl: &gl::Gl, op: Option<LogicOp>) {
+    match op {
+        Some(op) => unsafe {
+            // the logic operation takes precedence over blending
+            gl.Enable(gl::COLOR_LOGIC_OP);
+            gl.LogicOp(map_logic_op(op));
+        },
+        None => unsafe {
+            gl.Disable(gl::COLOR_LOGIC_OP);
+        },
+    }
+}
+
 pub fn unlock_color_mask(gl: &gl::Gl) {
     unsafe { gl.ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE) };
 }
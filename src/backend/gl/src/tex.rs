@@ -19,9 +19,10 @@ use info::PrivateCaps;
 use core::memory::SHADER_RESOURCE;
 use core::format::{Format as NewFormat, ChannelType};
 use core::texture as t;
+use core::device::ResourceViewError;
 
 
-fn cube_face_to_gl(face: t::CubeFace) -> GLenum {
+pub fn cube_face_to_gl(face: t::CubeFace) -> GLenum {
     match face {
         t::CubeFace::PosZ => gl::TEXTURE_CUBE_MAP_POSITIVE_Z,
         t::CubeFace::NegZ => gl::TEXTURE_CUBE_MAP_NEGATIVE_Z,
@@ -67,7 +68,8 @@ fn format_to_glpixel(format: NewFormat) -> GLenum {
         S::R8_G8_B8_A8 | S::R16_G16_B16_A16 | S::R32_G32_B32_A32 |
         S::R4_G4_B4_A4 | S::R5_G5_B5_A1 | S::R10_G10_B10_A2 => rgba,
         S::D24_S8 | S::D32_S8 => gl::DEPTH_STENCIL,
-        S::D16 | S::D24 | S::D32 => gl::DEPTH_COMPONENT,
+        S::D16 | S::X8_D24 | S::D32 => gl::DEPTH_COMPONENT,
+        S::S8 => gl::STENCIL_INDEX,
         S::B8_G8_R8_A8 => bgra,
     }
 }
@@ -94,10 +96,11 @@ fn format_to_gltype(format: NewFormat) -> Result<GLenum, ()> {
         S::R16 | S::R16_G16 | S::R16_G16_B16 | S::R16_G16_B16_A16 => fm16,
         S::R32 | S::R32_G32 | S::R32_G32_B32 | S::R32_G32_B32_A32 => fm32,
         S::D16 => gl::UNSIGNED_SHORT,
-        S::D24 => gl::UNSIGNED_INT,
+        S::X8_D24 => gl::UNSIGNED_INT,
         S::D24_S8 => gl::UNSIGNED_INT_24_8,
         S::D32 => gl::FLOAT,
         S::D32_S8 => gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
+        S::S8 => gl::UNSIGNED_BYTE,
     })
 }
 
@@ -215,13 +218,56 @@ pub fn format_to_glfull(format: NewFormat) -> Result<GLenum, ()> {
         },
         // depth-stencil
         S::D16 => gl::DEPTH_COMPONENT16,
-        S::D24 => gl::DEPTH_COMPONENT24,
+        S::X8_D24 => gl::DEPTH_COMPONENT24,
         S::D24_S8 => gl::DEPTH24_STENCIL8,
         S::D32 => gl::DEPTH_COMPONENT32F,
         S::D32_S8 => gl::DEPTH32F_STENCIL8,
+        S::S8 => gl::STENCIL_INDEX8,
     })
 }
 
+/// The half float type of GL_OES_texture_half_float and GL_OES_vertex_half_float,
+/// differing from the desktop and GLES 3.0 one.
+pub const HALF_FLOAT_OES: GLenum = 0x8D61;
+
+/// The unsized format (also used as the internal one) and the data type
+/// of an OpenGL ES 2.0 texture.
+pub fn format_to_gles2(format: NewFormat) -> Result<(GLenum, GLenum), ()> {
+    use core::format::SurfaceType as S;
+    use core::format::ChannelType as C;
+    Ok(match (format.0, format.1) {
+        (S::R8_G8_B8_A8, C::Unorm) => (gl::RGBA, gl::UNSIGNED_BYTE),
+        (S::R8_G8_B8_A8, C::Srgb) => (gl::SRGB_ALPHA, gl::UNSIGNED_BYTE),
+        (S::B8_G8_R8_A8, C::Unorm) => (gl::BGRA, gl::UNSIGNED_BYTE),
+        (S::R5_G6_B5, C::Unorm) => (gl::RGB, gl::UNSIGNED_SHORT_5_6_5),
+        (S::R4_G4_B4_A4, C::Unorm) => (gl::RGBA, gl::UNSIGNED_SHORT_4_4_4_4),
+        (S::R5_G5_B5_A1, C::Unorm) => (gl::RGBA, gl::UNSIGNED_SHORT_5_5_5_1),
+        (S::R8, C::Unorm) => (gl::RED, gl::UNSIGNED_BYTE),
+        (S::R8_G8, C::Unorm) => (gl::RG, gl::UNSIGNED_BYTE),
+        (S::R16, C::Float) => (gl::RED, HALF_FLOAT_OES),
+        (S::R16_G16, C::Float) => (gl::RG, HALF_FLOAT_OES),
+        (S::R16_G16_B16, C::Float) => (gl::RGB, HALF_FLOAT_OES),
+        (S::R16_G16_B16_A16, C::Float) => (gl::RGBA, HALF_FLOAT_OES),
+        (S::R32, C::Float) => (gl::RED, gl::FLOAT),
+        (S::R32_G32, C::Float) => (gl::RG, gl::FLOAT),
+        (S::R32_G32_B32, C::Float) => (gl::RGB, gl::FLOAT),
+        (S::R32_G32_B32_A32, C::Float) => (gl::RGBA, gl::FLOAT),
+        (S::D16, _) => (gl::DEPTH_COMPONENT, gl::UNSIGNED_SHORT),
+        (S::X8_D24, _) => (gl::DEPTH_COMPONENT, gl::UNSIGNED_INT),
+        (S::D24_S8, _) => (gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+        _ => return Err(()),
+    })
+}
+
+/// The pixel format and the data type for the transfers of a texture.
+fn format_to_gltransfer(format: NewFormat, private_caps: &PrivateCaps) -> Result<(GLenum, GLenum), ()> {
+    if private_caps.sized_internal_format_supported {
+        format_to_gltype(format).map(|typ| (format_to_glpixel(format), typ))
+    } else {
+        format_to_gles2(format)
+    }
+}
+
 fn set_mipmap_range(gl: &gl::Gl, target: GLenum, (base, max): (u8, u8)) { unsafe {
     gl.TexParameteri(target, gl::TEXTURE_BASE_LEVEL, base as GLint);
     gl.TexParameteri(target, gl::TEXTURE_MAX_LEVEL, max as GLint);
@@ -276,8 +322,8 @@ pub fn make_surface(gl: &gl::Gl, desc: &t::Info, cty: ChannelType) ->
 }
 
 fn make_widout_storage_impl(gl: &gl::Gl, kind: t::Kind, format: GLint, pix: GLenum, typ: GLenum,
-                            levels: t::Level, fixed_sample_locations: bool)
-                            -> Result<Texture, t::CreationError> {
+                            fixed_sample_locations: bool)
+                            -> Result<(Texture, GLenum), t::CreationError> {
     let (name, target) = make_texture(gl, kind);
     match kind {
         t::Kind::D1(w) => unsafe {
@@ -389,27 +435,30 @@ fn make_widout_storage_impl(gl: &gl::Gl, kind: t::Kind, format: GLint, pix: GLen
         t::Kind::D2Array(_, _, _, aa) => return Err(t::CreationError::Samples(aa)),
     }
 
-    set_mipmap_range(gl, target, (0, levels - 1));
-    Ok(name)
+    Ok((name, target))
 }
 
 /// Create a texture, using the descriptor, assuming TexStorage* isn't available.
-pub fn make_without_storage(gl: &gl::Gl, desc: &t::Info, cty: ChannelType) ->
+/// On OpenGL ES 2.0, the internal format has to be the unsized pixel format.
+pub fn make_without_storage(gl: &gl::Gl, desc: &t::Info, cty: ChannelType, private_caps: &PrivateCaps) ->
                             Result<Texture, t::CreationError> {
     let format = NewFormat(desc.format, cty);
-    let gl_format = match format_to_glfull(format) {
-        Ok(f) => f as GLint,
-        Err(_) => return Err(t::CreationError::Format(desc.format, Some(cty))),
-    };
-    let gl_pixel_format = format_to_glpixel(format);
-    let gl_data_type = match format_to_gltype(format) {
-        Ok(t) => t,
-        Err(_) => return Err(t::CreationError::Format(desc.format, Some(cty))),
+    let format_error = t::CreationError::Format(desc.format, Some(cty));
+    let (gl_pixel_format, gl_data_type) = try!(format_to_gltransfer(format, private_caps)
+        .map_err(|_| format_error));
+    let gl_format = if private_caps.sized_internal_format_supported {
+        try!(format_to_glfull(format).map_err(|_| format_error)) as GLint
+    } else {
+        gl_pixel_format as GLint
     };
 
     let fixed_loc = desc.bind.contains(SHADER_RESOURCE);
-    make_widout_storage_impl(gl, desc.kind, gl_format, gl_pixel_format, gl_data_type,
-                             desc.levels, fixed_loc)
+    let (name, target) = try!(make_widout_storage_impl(gl, desc.kind, gl_format, gl_pixel_format,
+                                                       gl_data_type, fixed_loc));
+    if private_caps.texture_lod_supported {
+        set_mipmap_range(gl, target, (0, desc.levels - 1));
+    }
+    Ok(name)
 }
 
 /// Create a texture, assuming TexStorage is available.
@@ -540,8 +589,52 @@ pub fn make_with_storage(gl: &gl::Gl, desc: &t::Info, cty: ChannelType) ->
     make_with_storage_impl(gl, desc.kind, gl_format, desc.levels, fixed_loc)
 }
 
+/// Create a view of all the levels and layers of an immutable texture,
+/// reinterpreting it with another format of the same class.
+pub fn make_view(gl: &gl::Gl, name: Texture, desc: &t::Info, format: NewFormat) ->
+                 Result<Texture, ResourceViewError> {
+    let gl_format = try!(format_to_glfull(format)
+        .map_err(|_| ResourceViewError::Channel(format.1)));
+    let layers = match desc.kind {
+        t::Kind::D1Array(_, n) | t::Kind::D2Array(_, _, n, _) => n as GLuint,
+        t::Kind::Cube(_) => 6,
+        t::Kind::CubeArray(_, n) => 6 * n as GLuint,
+        _ => 1,
+    };
+    let mut view = 0 as GLuint;
+    unsafe {
+        gl.GenTextures(1, &mut view);
+        gl.TextureView(view, kind_to_gl(desc.kind), name, gl_format,
+                       0, desc.levels as GLuint, 0, layers);
+    }
+    // the implementation may refuse the view, e.g. for an incompatible format class
+    let err = unsafe { gl.GetError() };
+    if err != gl::NO_ERROR {
+        error!("Error {:x} creating a {:?} view of texture {}", err, format, name);
+        unsafe { gl.DeleteTextures(1, &view) };
+        return Err(ResourceViewError::Unsupported)
+    }
+    Ok(view)
+}
+
+/// Query the internal format of a texture, which the image units need to know.
+pub fn get_internal_format(gl: &gl::Gl, name: Texture, kind: t::Kind) -> GLenum {
+    let target = kind_to_gl(kind);
+    // the level parameters of a cube map are queried per face
+    let level_target = match kind {
+        t::Kind::Cube(_) => gl::TEXTURE_CUBE_MAP_POSITIVE_X,
+        _ => target,
+    };
+    let mut format = 0 as GLint;
+    unsafe {
+        gl.BindTexture(target, name);
+        gl.GetTexLevelParameteriv(level_target, 0, gl::TEXTURE_INTERNAL_FORMAT, &mut format);
+    }
+    format as GLenum
+}
+
 /// Bind a sampler using a given binding anchor.
-/// Used for GL compatibility profile only. The core profile has sampler objects
+/// Used for GL compatibility profile and GLES 2.0 only. The core profile has sampler objects
 pub fn bind_sampler(gl: &gl::Gl, target: GLenum, info: &t::SamplerInfo, private_caps: &PrivateCaps) { unsafe {
     let (min, mag) = filter_to_gl(info.filter);
 
@@ -557,19 +650,31 @@ pub fn bind_sampler(gl: &gl::Gl, target: GLenum, info: &t::SamplerInfo, private_
     let (s, t, r) = info.wrap_mode;
     gl.TexParameteri(target, gl::TEXTURE_WRAP_S, wrap_to_gl(s) as GLint);
     gl.TexParameteri(target, gl::TEXTURE_WRAP_T, wrap_to_gl(t) as GLint);
-    gl.TexParameteri(target, gl::TEXTURE_WRAP_R, wrap_to_gl(r) as GLint);
+    // only exists along with the 3D textures on OpenGL ES 2.0
+    if private_caps.texture_wrap_r_supported || target == gl::TEXTURE_3D {
+        gl.TexParameteri(target, gl::TEXTURE_WRAP_R, wrap_to_gl(r) as GLint);
+    }
 
     if private_caps.sampler_lod_bias_supported {
         gl.TexParameterf(target, gl::TEXTURE_LOD_BIAS, info.lod_bias.into());
     }
-    let border: [f32; 4] = info.border.into();
-    gl.TexParameterfv(target, gl::TEXTURE_BORDER_COLOR, &border[0]);
+    if private_caps.border_color_supported {
+        let border: [f32; 4] = info.border.into();
+        gl.TexParameterfv(target, gl::TEXTURE_BORDER_COLOR, &border[0]);
+    }
 
-    let (min, max) = info.lod_range;
-    gl.TexParameterf(target, gl::TEXTURE_MIN_LOD, min.into());
-    gl.TexParameterf(target, gl::TEXTURE_MAX_LOD, max.into());
+    if private_caps.texture_lod_supported {
+        let (min, max) = info.lod_range;
+        gl.TexParameterf(target, gl::TEXTURE_MIN_LOD, min.into());
+        gl.TexParameterf(target, gl::TEXTURE_MAX_LOD, max.into());
+    }
 
     match info.comparison {
+        _ if !private_caps.shadow_sampler_supported => {
+            if info.comparison.is_some() {
+                error!("Comparison samplers are not supported");
+            }
+        },
         None => gl.TexParameteri(target, gl::TEXTURE_COMPARE_MODE, gl::NONE as GLint),
         Some(cmp) => {
             gl.TexParameteri(target, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as GLint);
@@ -723,12 +828,11 @@ pub fn copy_to_buffer(gl: &gl::Gl,
 
 pub fn update_texture(gl: &gl::Gl, name: Texture,
                       kind: t::Kind, face: Option<t::CubeFace>,
-                      img: &t::RawImageInfo, slice: &[u8])
+                      img: &t::RawImageInfo, slice: &[u8], private_caps: &PrivateCaps)
                           -> Result<(), t::CreationError> {
     //TODO: check size
     let data = slice.as_ptr() as *const GLvoid;
-    let pixel_format = format_to_glpixel(img.format);
-    let data_type = match format_to_gltype(img.format) {
+    let (pixel_format, data_type) = match format_to_gltransfer(img.format, private_caps) {
         Ok(t) => t,
         Err(_) => return Err(t::CreationError::Format(img.format.0, Some(img.format.1))),
     };
@@ -741,7 +845,7 @@ pub fn update_texture(gl: &gl::Gl, name: Texture,
 }
 
 pub fn init_texture_data(gl: &gl::Gl, name: Texture, desc: t::Info, channel: ChannelType,
-                         data: &[&[u8]]) -> Result<(), t::CreationError> {
+                         data: &[&[u8]], private_caps: &PrivateCaps) -> Result<(), t::CreationError> {
     let opt_slices = desc.kind.get_num_slices();
     let num_slices = opt_slices.unwrap_or(1) as usize;
     let num_mips = desc.levels as usize;
@@ -769,7 +873,7 @@ pub fn init_texture_data(gl: &gl::Gl, name: Texture, desc: t::Info, channel: Cha
                     image.zoffset = i as t::Size;
                     image.depth = 1;
                 }
-                try!(update_texture(gl, name, desc.kind, face, &image, sub));
+                try!(update_texture(gl, name, desc.kind, face, &image, sub, private_caps));
             }
         }
     }
@@ -925,8 +1029,10 @@ pub fn make_sampler(gl: &gl::Gl, info: &t::SamplerInfo, private_caps: &PrivateCa
     if private_caps.sampler_lod_bias_supported {
         gl.SamplerParameterf(name, gl::TEXTURE_LOD_BIAS, info.lod_bias.into());
     }
-    let border: [f32; 4] = info.border.into();
-    gl.SamplerParameterfv(name, gl::TEXTURE_BORDER_COLOR, &border[0]);
+    if private_caps.border_color_supported {
+        let border: [f32; 4] = info.border.into();
+        gl.SamplerParameterfv(name, gl::TEXTURE_BORDER_COLOR, &border[0]);
+    }
 
     let (min, max) = info.lod_range;
     gl.SamplerParameterf(name, gl::TEXTURE_MIN_LOD, min.into());
@@ -948,3 +1054,49 @@ pub fn generate_mipmap(gl: &gl::Gl, name: Texture, target: gl::types::GLenum) {
     gl.BindTexture(target, name);
     gl.GenerateMipmap(target);
 }}
+
+#[cfg(test)]
+mod tests {
+    use core::format::{ChannelType, Format, SurfaceType};
+    use core::memory::{Usage, SHADER_RESOURCE};
+    use core::texture as t;
+    use {gl, mock};
+    use super::{format_to_gles2, format_to_glfull, make_with_storage, HALF_FLOAT_OES};
+
+    #[test]
+    fn test_format_mapping() {
+        let full = |s, c| format_to_glfull(Format(s, c));
+        assert_eq!(full(SurfaceType::R8_G8_B8_A8, ChannelType::Unorm), Ok(gl::RGBA8));
+        assert_eq!(full(SurfaceType::R8_G8_B8_A8, ChannelType::Srgb), Ok(gl::SRGB8_ALPHA8));
+        assert_eq!(full(SurfaceType::R16_G16, ChannelType::Float), Ok(gl::RG16F));
+        assert_eq!(full(SurfaceType::D24_S8, ChannelType::Unorm), Ok(gl::DEPTH24_STENCIL8));
+
+        let gles2 = |s, c| format_to_gles2(Format(s, c));
+        assert_eq!(gles2(SurfaceType::R8_G8_B8_A8, ChannelType::Srgb), Ok((gl::SRGB_ALPHA, gl::UNSIGNED_BYTE)));
+        assert_eq!(gles2(SurfaceType::R16_G16_B16_A16, ChannelType::Float), Ok((gl::RGBA, HALF_FLOAT_OES)));
+        assert_eq!(gles2(SurfaceType::D24_S8, ChannelType::Unorm), Ok((gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8)));
+        assert!(gles2(SurfaceType::R8_G8_B8_A8, ChannelType::Int).is_err());
+        assert!(gles2(SurfaceType::S8, ChannelType::Uint).is_err());
+    }
+
+    #[test]
+    fn test_make_with_storage() {
+        mock::install(mock::Driver::default());
+        let gl = gl::Gl::load_with(mock::get_proc_address);
+        let desc = t::Info {
+            kind: t::Kind::D2(16, 8, t::AaMode::Single),
+            levels: 3,
+            format: SurfaceType::R8_G8_B8_A8,
+            bind: SHADER_RESOURCE,
+            usage: Usage::Data,
+        };
+        let name = make_with_storage(&gl, &desc, ChannelType::Srgb).unwrap();
+        assert_eq!(mock::take_calls(), vec![
+            "GenTextures(1)".to_string(),
+            format!("BindTexture({}, {})", gl::TEXTURE_2D, name),
+            format!("TexStorage2D({}, 3, {}, 16, 8)", gl::TEXTURE_2D, gl::SRGB8_ALPHA8),
+            format!("TexParameteri({}, {}, 0)", gl::TEXTURE_2D, gl::TEXTURE_BASE_LEVEL),
+            format!("TexParameteri({}, {}, 2)", gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL),
+        ]);
+    }
+}
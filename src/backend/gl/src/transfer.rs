@@ -0,0 +1,245 @@
+// Copyright 2017 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transfer queue emulation: the transfer commands are executed on a worker
+//! thread, with a context sharing the objects of the main one, so that the
+//! uploads don't stall the rendering.
+
+use std::os::raw::c_void;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use core::{self as c, buffer, handle, memory};
+use command::{Command, DataBuffer};
+use info::PrivateCaps;
+use {device, gl, tex, Fence, Resources};
+
+/// An OpenGL context sharing its objects with the context of the adapter,
+/// provided by the window layer to run the transfer queue.
+pub trait SharedContext: Send {
+    /// Make the context current on the calling thread.
+    unsafe fn make_current(&self);
+    /// Get the address of an OpenGL function.
+    fn get_proc_address(&self, symbol: &str) -> *const c_void;
+}
+
+/// A step of a transfer submission, executed in order by the worker.
+pub enum Operation {
+    Wait(handle::Semaphore<Resources>),
+    Execute(Vec<Command>, DataBuffer),
+    Signal(handle::Semaphore<Resources>),
+    Fence(handle::Fence<Resources>),
+}
+
+/// Handle to the worker thread of a transfer queue.
+pub struct Worker {
+    sender: mpsc::Sender<Vec<Operation>>,
+}
+
+impl Worker {
+    /// Start the worker thread, making the context current there.
+    pub fn spawn(context: Box<SharedContext>, caps: c::Capabilities, private: PrivateCaps) -> Worker {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || run(context, caps, private, receiver));
+        Worker {
+            sender: sender,
+        }
+    }
+
+    /// Queue the operations of a submission on the worker. If it has stopped,
+    /// the semaphores and fences of the submission are released without any
+    /// sync, so that nothing waits for them.
+    pub fn submit(&mut self, operations: Vec<Operation>) {
+        if let Err(mpsc::SendError(operations)) = self.sender.send(operations) {
+            error!("The transfer worker has stopped");
+            let mut handles = handle::Manager::<Resources>::new();
+            for operation in operations {
+                match operation {
+                    Operation::Signal(ref semaphore) =>
+                        publish(&mut handles.ref_semaphore(semaphore).lock().unwrap()),
+                    Operation::Fence(ref fence) =>
+                        publish(&mut handles.ref_fence(fence).lock().unwrap()),
+                    Operation::Wait(_) | Operation::Execute(..) => (),
+                }
+            }
+        }
+    }
+}
+
+/// Mark the fence as no longer pending, waking up the threads waiting for it.
+fn publish(fence: &mut Fence) {
+    fence.pending = false;
+    fence.placed.notify_all();
+}
+
+/// Place a sync for the other contexts, waking up the threads waiting for it.
+fn place(gl: &gl::Gl, fence: &Mutex<Fence>) {
+    let sync = unsafe { gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+    // the other contexts can only wait for the flushed syncs
+    unsafe { gl.Flush() };
+    let mut fence = fence.lock().unwrap();
+    fence.set_sync(gl, sync);
+    publish(&mut fence);
+}
+
+fn run(context: Box<SharedContext>, caps: c::Capabilities, private: PrivateCaps,
+       receiver: mpsc::Receiver<Vec<Operation>>) {
+    unsafe { context.make_current() };
+    let gl = gl::Gl::load_with(|s| context.get_proc_address(s));
+    info!("Started the transfer worker");
+    // keeps the semaphores and fences referenced by the current submission
+    let mut handles = handle::Manager::<Resources>::new();
+
+    for operations in receiver.iter() {
+        for operation in operations {
+            match operation {
+                Operation::Wait(ref semaphore) => {
+                    ::wait_semaphore(&gl, handles.ref_semaphore(semaphore));
+                },
+                Operation::Execute(ref commands, ref data) => {
+                    for cmd in commands {
+                        if !execute(&gl, &caps, &private, cmd, data) {
+                            error!("{:?} is not a transfer command", cmd);
+                        }
+                    }
+                },
+                Operation::Signal(ref semaphore) => {
+                    place(&gl, handles.ref_semaphore(semaphore));
+                },
+                Operation::Fence(ref fence) => {
+                    place(&gl, handles.ref_fence(fence));
+                },
+            }
+        }
+        handles.clear();
+    }
+    info!("Stopped the transfer worker");
+}
+
+/// Execute a transfer command, returning false for the other kinds of commands.
+pub fn execute(gl: &gl::Gl, caps: &c::Capabilities, private: &PrivateCaps,
+               cmd: &Command, data_buf: &DataBuffer) -> bool {
+    match *cmd {
+        Command::CopyBuffer(src, dst, src_offset, dst_offset, size) => {
+            if caps.copy_buffer_supported {
+                unsafe {
+                    gl.BindBuffer(gl::COPY_READ_BUFFER, src);
+                    gl.BindBuffer(gl::COPY_WRITE_BUFFER, dst);
+                    gl.CopyBufferSubData(gl::COPY_READ_BUFFER,
+                                        gl::COPY_WRITE_BUFFER,
+                                        src_offset,
+                                        dst_offset,
+                                        size);
+                }
+            } else {
+                debug_assert!(private.buffer_storage_supported == false);
+
+                unsafe {
+                    let mut src_ptr = 0 as *mut c_void;
+                    device::temporary_ensure_mapped(&mut src_ptr, gl::COPY_READ_BUFFER, src, memory::READ, gl);
+                    src_ptr.offset(src_offset);
+
+                    let mut dst_ptr = 0 as *mut c_void;
+                    device::temporary_ensure_mapped(&mut dst_ptr, gl::COPY_WRITE_BUFFER, dst, memory::WRITE, gl);
+                    dst_ptr.offset(dst_offset);
+
+                    ::std::ptr::copy(src_ptr, dst_ptr, size as usize);
+
+                    device::temporary_ensure_unmapped(&mut src_ptr, gl::COPY_READ_BUFFER, src, gl);
+                    device::temporary_ensure_unmapped(&mut dst_ptr, gl::COPY_WRITE_BUFFER, dst, gl);
+                }
+            }
+        },
+        Command::CopyBufferToTexture(src, src_offset, dst, kind, face, img) => {
+            match tex::copy_from_buffer(gl, dst, kind, face, &img, src, src_offset) {
+                Ok(_) => (),
+                Err(e) => error!("GL: {:?} failed: {:?}", cmd, e)
+            }
+        },
+        Command::CopyTextureToBuffer(src, kind, face, img, dst, dst_offset) => {
+            match tex::copy_to_buffer(gl, src, kind, face, &img, dst, dst_offset) {
+                Ok(_) => (),
+                Err(e) => error!("GL: {:?} failed: {:?}", cmd, e)
+            }
+        },
+        Command::UpdateBuffer(buffer, pointer, offset) => {
+            let data = data_buf.get(pointer);
+            device::update_sub_buffer(gl, buffer,
+                data.as_ptr(), data.len(), offset, buffer::Role::Vertex);
+        },
+        Command::UpdateTexture(texture, kind, face, pointer, ref image) => {
+            let data = data_buf.get(pointer);
+            match tex::update_texture(gl, texture, kind, face, image, data, private) {
+                Ok(_) => (),
+                Err(e) => error!("GL: Texture({}) update failed: {:?}", texture, e),
+            }
+        },
+        _ => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::raw::c_void;
+    use std::ptr;
+    use std::sync::mpsc;
+    use std::time::Instant;
+    use core::handle::{self, Producer};
+    use {device, gl, info, lock_placed, mock, Fence, Resources};
+    use super::{Operation, SharedContext, Worker};
+
+    struct MockContext;
+
+    impl SharedContext for MockContext {
+        unsafe fn make_current(&self) {
+            mock::install(mock::Driver::default());
+        }
+        fn get_proc_address(&self, symbol: &str) -> *const c_void {
+            mock::get_proc_address(symbol)
+        }
+    }
+
+    #[test]
+    fn test_fences_out_of_order() {
+        mock::install(mock::Driver::default());
+        let gl = gl::Gl::load_with(mock::get_proc_address);
+        let (_, caps, private) = info::get(&gl);
+        let mut handles = handle::Manager::<Resources>::new();
+        let first = handles.make_fence(Fence::pending());
+        let second = handles.make_fence(Fence::pending());
+        let unsignalled = handles.make_fence(Fence::new(ptr::null()));
+
+        let mut worker = Worker::spawn(Box::new(MockContext), caps, private);
+        assert_eq!(device::wait_fence(handles.ref_fence(&second), &gl, 0), gl::TIMEOUT_EXPIRED);
+        worker.submit(vec![Operation::Fence(first.clone())]);
+        worker.submit(vec![Operation::Fence(second.clone())]);
+        assert_eq!(device::wait_fence(handles.ref_fence(&second), &gl, 10_000), gl::ALREADY_SIGNALED);
+        assert_eq!(device::wait_fence(handles.ref_fence(&first), &gl, 0), gl::ALREADY_SIGNALED);
+        assert_eq!(device::wait_fence(handles.ref_fence(&unsignalled), &gl, 0), gl::TIMEOUT_EXPIRED);
+    }
+
+    #[test]
+    fn test_stopped_worker() {
+        let mut handles = handle::Manager::<Resources>::new();
+        let semaphore = handles.make_semaphore(Fence::pending());
+        let fence = handles.make_fence(Fence::pending());
+
+        let (sender, _) = mpsc::channel();
+        let mut worker = Worker { sender: sender };
+        worker.submit(vec![Operation::Signal(semaphore.clone()), Operation::Fence(fence.clone())]);
+        let now = Instant::now();
+        assert!(lock_placed(handles.ref_semaphore(&semaphore), Some(now)).unwrap().sync.is_null());
+        assert!(lock_placed(handles.ref_fence(&fence), Some(now)).is_some());
+    }
+}
@@ -315,6 +315,28 @@ impl command::Buffer<Resources> for RawCommandBuffer {
         self.encoder.set_index_buffer(unsafe { *(buf.0).0 }, map_index_type(idx_type));
     }
 
+    fn bind_stream_output(&mut self, buf: Option<Buffer>) {
+        if buf.is_some() {
+            error!("Stream output is not supported");
+        }
+    }
+
+    fn set_viewports(&mut self, viewports: &[command::Viewport]) {
+        if viewports.len() > 1 {
+            error!("Viewport arrays are not supported yet, only the first viewport is used");
+        }
+        if let Some(vp) = viewports.first() {
+            self.encoder.set_viewport(MTLViewport {
+                originX: vp.rect.x as f64,
+                originY: vp.rect.y as f64,
+                width: vp.rect.w as f64,
+                height: vp.rect.h as f64,
+                znear: vp.min_depth as f64,
+                zfar: vp.max_depth as f64,
+            });
+        }
+    }
+
     fn set_scissor(&mut self, rect: target::Rect) {
         // TODO(fkaa): why are getting 1x1 scissor?
         /*self.encoder.set_scissor_rect(MTLScissorRect {
@@ -446,6 +468,16 @@ impl command::Buffer<Resources> for RawCommandBuffer {
         }
     }
 
+    fn begin_query(&mut self, _: ()) {}
+
+    fn end_query(&mut self, _: ()) {}
+
+    fn begin_conditional(&mut self, _: (), _: command::ConditionalMode) {
+        // the draw calls are executed unconditionally
+    }
+
+    fn end_conditional(&mut self) {}
+
     fn call_draw(&mut self, start: VertexCount, count: VertexCount, instances: Option<command::InstanceParams>) {
         self.ensure_render_encoder();
 
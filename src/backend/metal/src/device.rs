@@ -237,6 +237,36 @@ impl core::Device<Resources> for Device {
         &self.share.capabilities
     }
 
+    fn get_format_properties(&self, format: core::format::Format) -> core::format::FormatProperties {
+        use core::format::{ChannelType, FormatProperties};
+        use map::{map_format, map_depth_surface};
+
+        let mut props = FormatProperties::unsupported();
+        if map_depth_surface(format.0).is_some() {
+            props.bind = memory::DEPTH_STENCIL | memory::SHADER_RESOURCE;
+        } else if map_format(format, false).is_some() {
+            props.bind = memory::SHADER_RESOURCE | memory::TRANSFER_SRC | memory::TRANSFER_DST;
+            if map_format(format, true).is_some() {
+                props.bind.insert(memory::RENDER_TARGET);
+            }
+        } else {
+            return props
+        }
+        let is_integer = match format.1 {
+            ChannelType::Int | ChannelType::Uint => true,
+            _ => false,
+        };
+        props.filterable = !is_integer;
+        props.blendable = !is_integer && props.bind.contains(memory::RENDER_TARGET);
+        // 4 samples are guaranteed to be supported by all the devices
+        props.sample_counts = if props.bind.intersects(memory::RENDER_TARGET | memory::DEPTH_STENCIL) {
+            1 | 4
+        } else {
+            1
+        };
+        props
+    }
+
     fn create_buffer_raw(&mut self,
                          info: buffer::Info)
                          -> Result<handle::RawBuffer<Resources>, buffer::CreationError> {
@@ -291,7 +321,7 @@ impl core::Device<Resources> for Device {
     fn create_program(&mut self,
                       shader_set: &core::ShaderSet<Resources>)
                       -> Result<handle::Program<Resources>, core::shade::CreateProgramError> {
-        use core::shade::{ProgramInfo, Stage};
+        use core::shade::{ProgramInfo, SlotAssignment, Stage};
 
         let (prog, info) = match shader_set {
             &core::ShaderSet::Simple(ref vs, ref ps) => {
@@ -305,6 +335,7 @@ impl core::Device<Resources> for Device {
                     outputs: Vec::new(),
                     output_depth: false,
                     knows_outputs: false,
+                    slot_assignment: SlotAssignment::Explicit,
                 };
 
                 let fh = &mut self.frame_handles;
@@ -393,6 +424,10 @@ impl core::Device<Resources> for Device {
 
         use core::{MAX_COLOR_TARGETS};
 
+        if desc.stream_output.is_some() {
+            error!("Stream output is not supported");
+            return Err(core::pso::CreationError);
+        }
         let vertex_desc = MTLVertexDescriptor::new();
 
         let mut vb_count = 0;
@@ -657,7 +692,7 @@ impl core::Device<Resources> for Device {
     fn view_texture_as_shader_resource_raw
         (&mut self,
          htex: &handle::RawTexture<Resources>,
-         _desc: core::texture::ResourceDesc)
+         desc: core::texture::ResourceDesc)
          -> Result<handle::RawShaderResourceView<Resources>, device::ResourceViewError> {
         // use winapi::UINT;
         // use core::texture::{AaMode, Kind};
@@ -709,6 +744,13 @@ impl core::Device<Resources> for Device {
         // return Err(f::ResourceViewError::Unsupported);
         // }
         // Ok(self.share.handles.borrow_mut().make_texture_srv(native::Srv(raw_view), htex))
+        match desc.surface {
+            Some(surface) if surface != htex.get_info().format => {
+                error!("Reinterpreting the texture format is not supported");
+                return Err(device::ResourceViewError::Unsupported)
+            },
+            _ => (),
+        }
         let raw_tex = self.frame_handles.ref_texture(htex).0;
         Ok(self.share.handles.borrow_mut().make_texture_srv(n::Srv(raw_tex.0), htex))
     }
@@ -840,6 +882,15 @@ impl core::Device<Resources> for Device {
         self.share.handles.borrow_mut().make_fence(fence)
     }
 
+    fn create_query(&mut self) -> handle::Query<Resources> {
+        error!("Queries are not supported");
+        self.share.handles.borrow_mut().make_query(())
+    }
+
+    fn get_query_result(&mut self, _: &handle::Query<Resources>, _: bool) -> Option<u64> {
+        Some(1)
+    }
+
     fn reset_fences(&mut self, fences: &[&handle::Fence<Resources>]) {
         for fence in fences {
             let fence = &mut *self.frame_handles.ref_fence(&fence).lock().unwrap();
@@ -128,6 +128,14 @@ impl core::Adapter<Backend> for Adapter {
             capabilities: core::Capabilities {
                 max_texture_size: 0,
                 max_patch_size: 0,
+                max_viewports: 1,
+                max_color_targets: 8,
+                max_vertex_attributes: 31,
+                max_constant_buffer_size: 64 * 1024,
+                max_texture_3d_size: 2048,
+                max_texture_array_layers: 2048,
+                max_anisotropy: 16,
+                max_samples: 4,
                 instance_base_supported: false,
                 instance_call_supported: false,
                 instance_rate_supported: false,
@@ -136,7 +144,19 @@ impl core::Adapter<Backend> for Adapter {
                 constant_buffer_supported: true,
                 unordered_access_view_supported: false,
                 separate_blending_slots_supported: false,
+                dual_source_blending_supported: false,
+                logic_op_supported: false,
+                stream_output_supported: false,
+                occlusion_query_supported: false,
+                conditional_render_supported: false,
                 copy_buffer_supported: true,
+                geometry_shader_supported: false,
+                tessellation_supported: false,
+                compute_supported: false,
+                indirect_draw_supported: false,
+                srgb_decode_supported: false,
+                depth_clamp_supported: false,
+                seamless_cube_supported: true,
             },
             handles: RefCell::new(handle::Manager::new()),
             feature_set: feature_set.unwrap(),
@@ -217,7 +237,7 @@ impl CommandQueue {
         CommandQueue {
             raw: Arc::new(raw_queue),
             frame_handles: handle::Manager::new(),
-            max_resource_count: Some(999999),
+            max_resource_count: Some(handle::MAX_FRAME_RESOURCES),
         }
     }
 
@@ -314,7 +334,8 @@ impl core::CommandQueue<Backend> for CommandQueue {
         self.frame_handles.extend(man);
         match self.max_resource_count {
             Some(c) if self.frame_handles.count() > c => {
-                error!("Way too many resources in the current frame. Did you call Device::cleanup()?");
+                error!("Way too many resources in the current frame ({}). Did you call Device::cleanup()?",
+                       self.frame_handles.counts());
                 self.max_resource_count = None;
             },
             _ => (),
@@ -356,6 +377,7 @@ impl core::Resources for Resources {
     type RenderTargetView = native::Rtv;
     type DepthStencilView = native::Dsv;
     type Sampler = native::Sampler;
+    type Query = ();
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
     type Mapping = device::RawMapping;
@@ -309,9 +309,8 @@ pub fn map_format(format: Format, is_target: bool) -> Option<MTLPixelFormat> {
         }
         B8_G8_R8_A8 => return None,
         D16 => return None,
-        D24 => {
+        X8_D24 => {
             match (is_target, format.1) {
-                // TODO: stencil?
                 (true, _) => Depth24Unorm_Stencil8,
                 (false, Unorm) => Depth24Unorm_Stencil8,
                 _ => return None,
@@ -339,6 +338,12 @@ pub fn map_format(format: Format, is_target: bool) -> Option<MTLPixelFormat> {
                 _ => return None,
             }
         }
+        S8 => {
+            match format.1 {
+                Uint => Stencil8,
+                _ => return None,
+            }
+        }
     })
 }
 
@@ -354,10 +359,11 @@ pub fn map_channel_hint(hint: SurfaceType) -> Option<ChannelType> {
         R32_G32 | R32_G32_B32_A32 => Uint,
         R11_G11_B10 => Float,
         B8_G8_R8_A8 => Unorm,
-        D24 => Unorm,
+        X8_D24 => Unorm,
         D24_S8 => Unorm,
         D32 => Float,
         D32_S8 => Float,
+        S8 => Uint,
     })
 }
 
@@ -142,7 +142,7 @@ pub fn map_format(surface: SurfaceType, chan: ChannelType) -> Option<vk::Format>
             Unorm  => vk::Format::D16Unorm,
             _ => return None,
         },
-        D24 => match chan {
+        X8_D24 => match chan {
             Unorm => vk::Format::X8D24UnormPack32,
             _ => return None,
         },
@@ -158,6 +158,10 @@ pub fn map_format(surface: SurfaceType, chan: ChannelType) -> Option<vk::Format>
             Float => vk::Format::D32SfloatS8Uint,
             _ => return None,
         },
+        S8 => match chan {
+            Uint => vk::Format::S8Uint,
+            _ => return None,
+        },
     })
 }
 /*
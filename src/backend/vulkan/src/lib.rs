@@ -396,7 +396,8 @@ impl core::CommandQueue<Backend> for CommandQueue {
         self.frame_handles.extend(man);
         match self.max_resource_count {
             Some(c) if self.frame_handles.count() > c => {
-                error!("Way too many resources in the current frame. Did you call Device::cleanup()?");
+                error!("Way too many resources in the current frame ({}). Did you call Device::cleanup()?",
+                       self.frame_handles.counts());
                 self.max_resource_count = None;
             },
             _ => (),
@@ -444,6 +445,7 @@ impl core::Resources for Resources {
     type RenderTargetView = ();
     type DepthStencilView = ();
     type Sampler = ();
+    type Query = ();
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
     type Mapping = Mapping;
@@ -15,8 +15,8 @@
 //! Command Buffer device interface
 
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
-use std::collections::hash_set::{self, HashSet};
+use std::ops::{Deref, DerefMut, Range};
+use std::collections::hash_map::{self, HashMap};
 use {Backend, Resources, IndexType, InstanceCount, VertexCount,
      SubmissionResult, SubmissionError};
 use {state, target, pso, shade, texture, handle};
@@ -35,9 +35,46 @@ pub enum ClearColor {
     Uint([u32; 4]),
 }
 
+/// Viewport transformation: the target rectangle, with the origin
+/// in the top-left corner, and the range of the depth values.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Viewport {
+    /// Rectangle of the target covered by the viewport
+    pub rect: target::Rect,
+    /// Depth value of the near plane, in `[0, 1]`
+    pub min_depth: f32,
+    /// Depth value of the far plane, in `[0, 1]`
+    pub max_depth: f32,
+}
+
+impl From<target::Rect> for Viewport {
+    fn from(rect: target::Rect) -> Viewport {
+        Viewport {
+            rect: rect,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+}
+
 /// Optional instance parameters: (instance count, buffer offset)
 pub type InstanceParams = (InstanceCount, VertexCount);
 
+/// How the draw calls of a conditional block wait for the query result.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ConditionalMode {
+    /// Wait for the result, skipping the draw calls if no samples passed.
+    Wait,
+    /// Don't wait, the draw calls are executed if the result is not available yet.
+    NoWait,
+    /// Same as `Wait`, but the result may be used per screen region.
+    ByRegionWait,
+    /// Same as `NoWait`, but the result may be used per screen region.
+    ByRegionNoWait,
+}
+
 /// Thread-safe finished command buffer for submission.
 pub struct Submit<B: Backend, C>(B::SubmitInfo, PhantomData<C>);
 unsafe impl<B: Backend, C> Send for Submit<B, C> { }
@@ -206,6 +243,13 @@ pub trait Buffer<R: Resources>: 'static {
     fn bind_pixel_targets(&mut self, pso::PixelTargetSet<R>);
     /// Bind an index buffer
     fn bind_index(&mut self, R::Buffer, IndexType);
+    /// Bind the buffer capturing the vertex data of the following draw calls,
+    /// if the pipeline has a stream output.
+    fn bind_stream_output(&mut self, Option<R::Buffer>);
+    /// Set the viewports, overriding the ones derived from the pixel targets
+    /// until the next `bind_pixel_targets`. The first viewport is used by default,
+    /// the others are selected by the geometry shader.
+    fn set_viewports(&mut self, &[Viewport]);
     /// Set scissor rectangle
     fn set_scissor(&mut self, target::Rect);
     /// Set reference values for the blending and stencil front/back
@@ -234,6 +278,14 @@ pub trait Buffer<R: Resources>: 'static {
     fn clear_color(&mut self, R::RenderTargetView, ClearColor);
     fn clear_depth_stencil(&mut self, R::DepthStencilView,
                            Option<target::Depth>, Option<target::Stencil>);
+    /// Start counting the samples passing the depth and stencil tests
+    fn begin_query(&mut self, R::Query);
+    /// Stop counting the samples of a query
+    fn end_query(&mut self, R::Query);
+    /// Skip the following draw calls if no samples passed in the query
+    fn begin_conditional(&mut self, R::Query, ConditionalMode);
+    /// End the conditional block
+    fn end_conditional(&mut self);
     /// Draw a primitive
     fn call_draw(&mut self, VertexCount, VertexCount, Option<InstanceParams>);
     /// Draw a primitive with index buffer
@@ -281,18 +333,30 @@ impl From<u32> for ClearColor {
 }
 
 /// Informations about what is accessed by a bunch of commands.
+/// The mapped buffers are tracked with the byte range that is accessed.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AccessInfo<R: Resources> {
-    mapped_reads: HashSet<handle::RawBuffer<R>>,
-    mapped_writes: HashSet<handle::RawBuffer<R>>,
+    mapped_reads: HashMap<handle::RawBuffer<R>, Range<usize>>,
+    mapped_writes: HashMap<handle::RawBuffer<R>, Range<usize>>,
+}
+
+fn register_range<R: Resources>(accesses: &mut HashMap<handle::RawBuffer<R>, Range<usize>>,
+                                buffer: &handle::RawBuffer<R>, range: Range<usize>) {
+    if !buffer.is_mapped() || range.start >= range.end {
+        return
+    }
+    let entry = accesses.entry(buffer.clone()).or_insert(range.clone());
+    // keep a single range covering all the accesses
+    entry.start = entry.start.min(range.start);
+    entry.end = entry.end.max(range.end);
 }
 
 impl<R: Resources> AccessInfo<R> {
     /// Creates empty access informations
     pub fn new() -> Self {
         AccessInfo {
-            mapped_reads: HashSet::new(),
-            mapped_writes: HashSet::new(),
+            mapped_reads: HashMap::new(),
+            mapped_writes: HashMap::new(),
         }
     }
 
@@ -304,24 +368,32 @@ impl<R: Resources> AccessInfo<R> {
 
     /// Register a buffer read access
     pub fn buffer_read(&mut self, buffer: &handle::RawBuffer<R>) {
-        if buffer.is_mapped() {
-            self.mapped_reads.insert(buffer.clone());
-        }
+        self.buffer_read_range(buffer, 0 .. buffer.get_info().size);
     }
 
     /// Register a buffer write access
     pub fn buffer_write(&mut self, buffer: &handle::RawBuffer<R>) {
-        if buffer.is_mapped() {
-            self.mapped_writes.insert(buffer.clone());
-        }
+        self.buffer_write_range(buffer, 0 .. buffer.get_info().size);
+    }
+
+    /// Register a read access of the bytes in `range`
+    pub fn buffer_read_range(&mut self, buffer: &handle::RawBuffer<R>, range: Range<usize>) {
+        register_range(&mut self.mapped_reads, buffer, range);
+    }
+
+    /// Register a write access of the bytes in `range`
+    pub fn buffer_write_range(&mut self, buffer: &handle::RawBuffer<R>, range: Range<usize>) {
+        register_range(&mut self.mapped_writes, buffer, range);
     }
 
-    /// Returns the mapped buffers that The GPU will read from
+    /// Returns the mapped buffers that The GPU will read from,
+    /// with the byte range that is read
     pub fn mapped_reads(&self) -> AccessInfoBuffers<R> {
         self.mapped_reads.iter()
     }
 
-    /// Returns the mapped buffers that The GPU will write to
+    /// Returns the mapped buffers that The GPU will write to,
+    /// with the byte range that is written
     pub fn mapped_writes(&self) -> AccessInfoBuffers<R> {
         self.mapped_writes.iter()
     }
@@ -338,7 +410,7 @@ impl<R: Resources> AccessInfo<R> {
 
     /// Takes all the accesses necessary for submission
     pub fn take_accesses(&self) -> SubmissionResult<AccessGuard<R>> {
-        for buffer in self.mapped_reads().chain(self.mapped_writes()) {
+        for (buffer, _) in self.mapped_reads().chain(self.mapped_writes()) {
             unsafe {
                 if !buffer.mapping().unwrap().take_access() {
                     return Err(SubmissionError::AccessOverlap);
@@ -350,7 +422,7 @@ impl<R: Resources> AccessInfo<R> {
 }
 
 #[allow(missing_docs)]
-pub type AccessInfoBuffers<'a, R> = hash_set::Iter<'a, handle::RawBuffer<R>>;
+pub type AccessInfoBuffers<'a, R> = hash_map::Iter<'a, handle::RawBuffer<R>, Range<usize>>;
 
 #[allow(missing_docs)]
 #[derive(Debug)]
@@ -393,7 +465,7 @@ impl<'a, R: Resources> Deref for AccessGuard<'a, R> {
 
 impl<'a, R: Resources> Drop for AccessGuard<'a, R> {
     fn drop(&mut self) {
-        for buffer in self.inner.mapped_reads().chain(self.inner.mapped_writes()) {
+        for (buffer, _) in self.inner.mapped_reads().chain(self.inner.mapped_writes()) {
             unsafe {
                 buffer.mapping().unwrap().release_access();
             }
@@ -408,11 +480,11 @@ pub struct AccessGuardBuffers<'a, R: Resources> {
 }
 
 impl<'a, R: Resources> Iterator for AccessGuardBuffers<'a, R> {
-    type Item = (&'a handle::RawBuffer<R>, &'a mut R::Mapping);
+    type Item = (&'a handle::RawBuffer<R>, &'a Range<usize>, &'a mut R::Mapping);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.buffers.next().map(|buffer| unsafe {
-            (buffer, buffer.mapping().unwrap().use_access())
+        self.buffers.next().map(|(buffer, range)| unsafe {
+            (buffer, range, buffer.mapping().unwrap().use_access())
         })
     }
 }
@@ -425,12 +497,12 @@ pub struct AccessGuardBuffersChain<'a, R: Resources> {
 }
 
 impl<'a, R: Resources> Iterator for AccessGuardBuffersChain<'a, R> {
-    type Item = (&'a handle::RawBuffer<R>, &'a mut R::Mapping);
+    type Item = (&'a handle::RawBuffer<R>, &'a Range<usize>, &'a mut R::Mapping);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.fst.next().or_else(|| self.snd.next())
-            .map(|buffer| unsafe {
-                (buffer, buffer.mapping().unwrap().use_access())
+            .map(|(buffer, range)| unsafe {
+                (buffer, range, buffer.mapping().unwrap().use_access())
             })
     }
 }
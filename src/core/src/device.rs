@@ -19,6 +19,7 @@
 
 use std::error::Error;
 use std::{mem, fmt};
+use std::ops::Range;
 use {buffer, handle, format, mapping, pso, shade, target, texture};
 use {Capabilities, Resources, ShaderSet,
      VertexShader, HullShader, DomainShader, GeometryShader, PixelShader};
@@ -32,6 +33,8 @@ pub enum ResourceViewError {
     NoBindFlag,
     /// Selected channel type is not supported for this texture.
     Channel(format::ChannelType),
+    /// Selected surface type is not compatible with the one of the texture.
+    Surface(format::SurfaceType),
     /// Selected layer can not be viewed for this texture.
     Layer(texture::LayerError),
     /// The backend was refused for some reason.
@@ -42,6 +45,7 @@ impl fmt::Display for ResourceViewError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ResourceViewError::Channel(ref channel_type) => write!(f, "{}: {:?}", self.description(), channel_type),
+            ResourceViewError::Surface(ref surface_type) => write!(f, "{}: {:?}", self.description(), surface_type),
             ResourceViewError::Layer(ref le) => write!(f, "{}: {}", self.description(), le),
             _ => write!(f, "{}", self.description())
         }
@@ -53,6 +57,7 @@ impl Error for ResourceViewError {
         match *self {
             ResourceViewError::NoBindFlag => "The corresponding bind flag is not present in the texture",
             ResourceViewError::Channel(_) => "Selected channel type is not supported for this texture",
+            ResourceViewError::Surface(_) => "Selected surface type is not compatible with the one of the texture",
             ResourceViewError::Layer(_) => "Selected layer can not be viewed for this texture",
             ResourceViewError::Unsupported => "The backend was refused for some reason",
         }
@@ -236,6 +241,10 @@ pub trait Device<R: Resources> {
     /// used.
     fn get_capabilities(&self) -> &Capabilities;
 
+    /// Returns the capabilities of a texture format, allowing to choose
+    /// a fallback before trying to create the resources.
+    fn get_format_properties(&self, format::Format) -> format::FormatProperties;
+
     // resource creation
     fn create_buffer_raw(&mut self, buffer::Info) -> Result<handle::RawBuffer<R>, buffer::CreationError>;
     fn create_buffer_immutable_raw(&mut self, data: &[u8], stride: usize, buffer::Role, Bind)
@@ -301,6 +310,14 @@ pub trait Device<R: Resources> {
     ///
     fn create_fence(&mut self, signalled: bool) -> handle::Fence<R>;
 
+    /// Create an occlusion query, see `Capabilities::occlusion_query_supported`.
+    fn create_query(&mut self) -> handle::Query<R>;
+
+    /// Get the number of samples that passed in a submitted query, or `None` if
+    /// the result is not available yet and `wait` is false. Some implementations
+    /// only report whether any samples passed, as 0 or 1.
+    fn get_query_result(&mut self, query: &handle::Query<R>, wait: bool) -> Option<u64>;
+
     ///
     fn reset_fences(&mut self, fences: &[&handle::Fence<R>]);
 
@@ -328,6 +345,29 @@ pub trait Device<R: Resources> {
                                           mapping::Error>
         where T: Copy;
 
+    /// Acquire a mapping Reader of the elements in `range`.
+    ///
+    /// Backends tracking the GPU accesses per region only wait for the
+    /// ones overlapping the range, the others map the whole buffer.
+    fn read_mapping_range<'a, 'b, T>(&'a mut self, buf: &'b handle::Buffer<R, T>, range: Range<usize>)
+                                     -> Result<mapping::Reader<'b, R, T>,
+                                               mapping::Error>
+        where T: Copy
+    {
+        self.read_mapping(buf).and_then(|reader| reader.into_range(range))
+    }
+
+    /// Acquire a mapping Writer of the elements in `range`.
+    ///
+    /// See `read_mapping_range` for more information.
+    fn write_mapping_range<'a, 'b, T>(&'a mut self, buf: &'b handle::Buffer<R, T>, range: Range<usize>)
+                                      -> Result<mapping::Writer<'b, R, T>,
+                                                mapping::Error>
+        where T: Copy
+    {
+        self.write_mapping(buf).and_then(|writer| writer.into_range(range))
+    }
+
     /// Create a new empty raw texture with no data. The channel type parameter is a hint,
     /// required to assist backends that have no concept of typeless formats (OpenGL).
     /// The initial data, if given, has to be provided for all mip levels and slices:
@@ -387,6 +427,34 @@ pub trait Device<R: Resources> {
         }
         assert!(levels.0 <= levels.1);
         let desc = texture::ResourceDesc {
+            surface: None,
+            channel: <T::Channel as format::ChannelTyped>::get_channel_type(),
+            layer: None,
+            min: levels.0,
+            max: levels.1,
+            swizzle: swizzle,
+        };
+        self.view_texture_as_shader_resource_raw(tex.raw(), desc)
+            .map(Typed::new)
+    }
+
+    /// Create a shader resource view reinterpreting the texture as another format,
+    /// which surface type has to be compatible with the one of the texture.
+    fn view_texture_as_shader_resource_as<T: format::TextureFormat, S: format::SurfaceTyped>(
+                                          &mut self, tex: &handle::Texture<R, S>,
+                                          levels: (target::Level, target::Level), swizzle: format::Swizzle)
+                                          -> Result<handle::ShaderResourceView<R, T::View>, ResourceViewError>
+    {
+        if !tex.get_info().bind.contains(SHADER_RESOURCE) {
+            return Err(ResourceViewError::NoBindFlag)
+        }
+        let surface = <T::Surface as format::SurfaceTyped>::get_surface_type();
+        if !S::get_surface_type().is_view_compatible(surface) {
+            return Err(ResourceViewError::Surface(surface))
+        }
+        assert!(levels.0 <= levels.1);
+        let desc = texture::ResourceDesc {
+            surface: Some(surface),
             channel: <T::Channel as format::ChannelTyped>::get_channel_type(),
             layer: None,
             min: levels.0,
@@ -83,6 +83,9 @@ impl Device<DummyResources> for DummyDevice {
     fn get_capabilities(&self) -> &Capabilities {
         unimplemented!()
     }
+    fn get_format_properties(&self, _: format::Format) -> format::FormatProperties {
+        unimplemented!()
+    }
     fn create_buffer_raw(
         &mut self,
         _: buffer::Info,
@@ -130,6 +133,12 @@ impl Device<DummyResources> for DummyDevice {
     fn create_fence(&mut self, _: bool) -> handle::Fence<DummyResources> {
         unimplemented!()
     }
+    fn create_query(&mut self) -> handle::Query<DummyResources> {
+        unimplemented!()
+    }
+    fn get_query_result(&mut self, _: &handle::Query<DummyResources>, _: bool) -> Option<u64> {
+        unimplemented!()
+    }
     fn reset_fences(&mut self, _: &[&handle::Fence<DummyResources>]) {
         unimplemented!()
     }
@@ -275,6 +284,7 @@ impl Resources for DummyResources {
     type RenderTargetView     = ();
     type DepthStencilView     = ();
     type Sampler              = ();
+    type Query                = ();
     type Semaphore            = ();
     type Fence                = DummyFence;
     type Mapping              = DummyMapping;
@@ -318,6 +328,8 @@ impl command::Buffer<DummyResources> for DummyCommandBuffer {
     fn bind_samplers(&mut self, _: &[pso::SamplerParam<DummyResources>]) {}
     fn bind_pixel_targets(&mut self, _: pso::PixelTargetSet<DummyResources>) {}
     fn bind_index(&mut self, _: (), _: IndexType) {}
+    fn bind_stream_output(&mut self, _: Option<()>) {}
+    fn set_viewports(&mut self, _: &[command::Viewport]) {}
     fn set_scissor(&mut self, _: target::Rect) {}
     fn set_ref_values(&mut self, _: state::RefValues) {}
     fn copy_buffer(&mut self, _: (), _: (), _: usize, _: usize, _: usize) {}
@@ -355,6 +367,10 @@ impl command::Buffer<DummyResources> for DummyCommandBuffer {
     fn clear_color(&mut self, _: (), _: command::ClearColor) {}
     fn clear_depth_stencil(&mut self, _: (), _: Option<target::Depth>, _: Option<target::Stencil>) {
     }
+    fn begin_query(&mut self, _: ()) {}
+    fn end_query(&mut self, _: ()) {}
+    fn begin_conditional(&mut self, _: (), _: command::ConditionalMode) {}
+    fn end_conditional(&mut self) {}
     fn call_draw(&mut self, _: VertexCount, _: VertexCount, _: Option<command::InstanceParams>) {}
     fn call_draw_indexed(
         &mut self,
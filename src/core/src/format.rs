@@ -20,7 +20,8 @@
 //  ETC2_RGB, // Use the EXT2 algorithm on 3 components.
 //  ETC2_SRGB, // Use the EXT2 algorithm on 4 components (RGBA) in the sRGB color space.
 //  ETC2_EAC_RGBA8, // Use the EXT2 EAC algorithm on 4 components.
-use memory::Pod;
+use memory::{Bind, Pod};
+use texture::NumSamples;
 
 macro_rules! impl_channel_type {
     { $($name:ident = $shader_type:ident [ $($imp_trait:ident),* ] ,)* } => {
@@ -33,6 +34,13 @@ macro_rules! impl_channel_type {
         pub enum ChannelType {
             $( $name, )*
         }
+        impl ChannelType {
+            /// Return all the channel types, in declaration order.
+            pub fn all() -> &'static [ChannelType] {
+                const ALL: &'static [ChannelType] = &[ $( ChannelType::$name, )* ];
+                ALL
+            }
+        }
         $(
             #[allow(missing_docs)]
             #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -74,6 +82,11 @@ macro_rules! impl_formats {
             $( $name, )*
         }
         impl SurfaceType {
+            /// Return all the surface types, in declaration order.
+            pub fn all() -> &'static [SurfaceType] {
+                const ALL: &'static [SurfaceType] = &[ $( SurfaceType::$name, )* ];
+                ALL
+            }
             /// Return the total number of bits for this format.
             pub fn get_total_bits(&self) -> u8 {
                 use std::mem::size_of;
@@ -148,10 +161,52 @@ impl_formats! {
     B8_G8_R8_A8     : Vec4<Unorm, Srgb> = [u8; 4] {32}
         [BufferSurface, TextureSurface, RenderSurface],
     D16             : Vec1<Unorm> = F16 {0} [TextureSurface, DepthSurface],
-    D24             : Vec1<Unorm> = f32 {8} [TextureSurface, DepthSurface], //hacky stencil bits
+    X8_D24          : Vec1<Unorm> = u32 {0} [TextureSurface, DepthSurface],
     D24_S8          : Vec1<Unorm, Uint> = u32 {8} [TextureSurface, DepthSurface, StencilSurface],
     D32             : Vec1<Float> = f32 {0} [TextureSurface, DepthSurface],
     D32_S8          : Vec1<Unorm, Float, Uint> = (f32, u32) {32} [TextureSurface, DepthSurface, StencilSurface],
+    S8              : Vec1<Uint> = u8 {8} [TextureSurface, StencilSurface],
+}
+
+/// Former name of `X8_D24`, which used to pretend having stencil bits.
+#[deprecated(note = "use `X8_D24`, or `D24_S8` for a stencil component")]
+pub type D24 = X8_D24;
+
+impl SurfaceType {
+    /// Former name of `X8_D24`, which used to pretend having stencil bits.
+    #[deprecated(note = "use `X8_D24`, or `D24_S8` for a stencil component")]
+    pub const D24: SurfaceType = SurfaceType::X8_D24;
+
+    /// Check if the surface has a depth component.
+    pub fn has_depth(&self) -> bool {
+        match *self {
+            SurfaceType::D16 | SurfaceType::X8_D24 | SurfaceType::D24_S8 |
+            SurfaceType::D32 | SurfaceType::D32_S8 => true,
+            _ => false,
+        }
+    }
+
+    /// Check if the surface has a stencil component.
+    pub fn has_stencil(&self) -> bool {
+        match *self {
+            SurfaceType::D24_S8 | SurfaceType::D32_S8 | SurfaceType::S8 => true,
+            _ => false,
+        }
+    }
+
+    /// Check if a texture of this surface type can be viewed as the other one.
+    /// Color surfaces are compatible when they have the same size per texel,
+    /// except for the packed 16-bit ones. Depth and stencil surfaces are only
+    /// compatible with themselves.
+    pub fn is_view_compatible(&self, other: SurfaceType) -> bool {
+        use self::SurfaceType::*;
+        let is_special = |s: SurfaceType| match s {
+            R4_G4 | R4_G4_B4_A4 | R5_G5_B5_A1 | R5_G6_B5 => true,
+            _ => s.has_depth() || s.has_stencil(),
+        };
+        *self == other || (!is_special(*self) && !is_special(other) &&
+                           self.get_total_bits() == other.get_total_bits())
+    }
 }
 
 
@@ -189,6 +244,56 @@ impl Swizzle {
 #[cfg_attr(feature="serialize", derive(Serialize, Deserialize))]
 pub struct Format(pub SurfaceType, pub ChannelType);
 
+impl Format {
+    /// Check if a shader resource view of this format reads the stencil values.
+    /// Combined depth-stencil surfaces are read as stencil with the `Uint` channel,
+    /// and as depth with any other.
+    pub fn is_stencil_view(&self) -> bool {
+        match *self {
+            Format(SurfaceType::S8, _) => true,
+            Format(surface, ChannelType::Uint) => surface.has_depth() && surface.has_stencil(),
+            _ => false,
+        }
+    }
+}
+
+/// Capabilities of a format on the device, see `Device::get_format_properties`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature="serialize", derive(Serialize, Deserialize))]
+pub struct FormatProperties {
+    /// Ways a texture of this format can be bound. Empty if the format is not supported.
+    pub bind: Bind,
+    /// Mask of the supported sample counts: `count` samples are supported
+    /// if `sample_counts & count != 0`, 1 being always set for a supported format.
+    pub sample_counts: NumSamples,
+    /// Can be sampled with linear filtering.
+    pub filterable: bool,
+    /// Can be blended when rendered into.
+    pub blendable: bool,
+}
+
+impl FormatProperties {
+    /// Properties of an unsupported format.
+    pub fn unsupported() -> FormatProperties {
+        FormatProperties {
+            bind: Bind::empty(),
+            sample_counts: 0,
+            filterable: false,
+            blendable: false,
+        }
+    }
+
+    /// Check if the format can be used at all.
+    pub fn is_supported(&self) -> bool {
+        !self.bind.is_empty()
+    }
+
+    /// Check if the format can be multisampled with the given number of samples.
+    pub fn supports_samples(&self, count: NumSamples) -> bool {
+        count.is_power_of_two() && self.sample_counts & count != 0
+    }
+}
+
 /// Compile-time surface type trait.
 pub trait SurfaceTyped {
     /// The corresponding data type to be passed from CPU.
@@ -355,11 +460,15 @@ pub type Rgba32F = (R32_G32_B32_A32, Float);
 /// Standard 8bits BGRA format.
 pub type Bgra8 = (B8_G8_R8_A8, Unorm);
 /// Standard 24-bit depth format.
-pub type Depth = (D24, Unorm);
+pub type Depth = (X8_D24, Unorm);
 /// Standard 24-bit depth format with 8-bit stencil.
 pub type DepthStencil = (D24_S8, Unorm);
 /// Standard 32-bit floating-point depth format.
 pub type Depth32F = (D32, Float);
+/// Standard 8-bit stencil format.
+pub type Stencil = (S8, Uint);
+/// Stencil values of the standard depth-stencil format, as read by a shader.
+pub type DepthStencilAsStencil = (D24_S8, Uint);
 
 macro_rules! impl_simple_formats {
     { $( $container:ident< $ty:ty > = $channel:ident $surface:ident, )* } => {
@@ -16,9 +16,13 @@
 
 //! Resource handles
 
+use std::collections::HashMap;
+use std::fmt;
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
+#[cfg(feature = "backtrace")]
+use backtrace::Backtrace;
 use {buffer, shade, texture, Resources};
 use memory::Typed;
 
@@ -244,6 +248,10 @@ impl<R: Resources> Sampler<R> {
     pub fn get_info(&self) -> &texture::SamplerInfo { &self.1 }
 }
 
+/// Query Handle
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Query<R: Resources>(Arc<R::Query>);
+
 /// Fence Handle
 #[derive(Clone, Debug)]
 pub struct Fence<R: Resources>(Arc<Mutex<R::Fence>>);
@@ -252,6 +260,194 @@ pub struct Fence<R: Resources>(Arc<Mutex<R::Fence>>);
 #[derive(Clone, Debug)]
 pub struct Semaphore<R: Resources>(Arc<Mutex<R::Semaphore>>);
 
+/// Type of a resource behind a handle.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ResourceType {
+    Buffer,
+    Shader,
+    Program,
+    PipelineState,
+    Texture,
+    ShaderResourceView,
+    UnorderedAccessView,
+    RenderTargetView,
+    DepthStencilView,
+    Sampler,
+    Query,
+    Fence,
+    Semaphore,
+}
+
+/// Number of handles of each resource type.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Counts {
+    pub buffers: usize,
+    pub shaders: usize,
+    pub programs: usize,
+    pub psos: usize,
+    pub textures: usize,
+    pub srvs: usize,
+    pub uavs: usize,
+    pub rtvs: usize,
+    pub dsvs: usize,
+    pub samplers: usize,
+    pub queries: usize,
+    pub fences: usize,
+    pub semaphores: usize,
+}
+
+impl Counts {
+    /// Get the number of handles of a given type.
+    pub fn get(&self, ty: ResourceType) -> usize {
+        match ty {
+            ResourceType::Buffer => self.buffers,
+            ResourceType::Shader => self.shaders,
+            ResourceType::Program => self.programs,
+            ResourceType::PipelineState => self.psos,
+            ResourceType::Texture => self.textures,
+            ResourceType::ShaderResourceView => self.srvs,
+            ResourceType::UnorderedAccessView => self.uavs,
+            ResourceType::RenderTargetView => self.rtvs,
+            ResourceType::DepthStencilView => self.dsvs,
+            ResourceType::Sampler => self.samplers,
+            ResourceType::Query => self.queries,
+            ResourceType::Fence => self.fences,
+            ResourceType::Semaphore => self.semaphores,
+        }
+    }
+
+    fn get_mut(&mut self, ty: ResourceType) -> &mut usize {
+        match ty {
+            ResourceType::Buffer => &mut self.buffers,
+            ResourceType::Shader => &mut self.shaders,
+            ResourceType::Program => &mut self.programs,
+            ResourceType::PipelineState => &mut self.psos,
+            ResourceType::Texture => &mut self.textures,
+            ResourceType::ShaderResourceView => &mut self.srvs,
+            ResourceType::UnorderedAccessView => &mut self.uavs,
+            ResourceType::RenderTargetView => &mut self.rtvs,
+            ResourceType::DepthStencilView => &mut self.dsvs,
+            ResourceType::Sampler => &mut self.samplers,
+            ResourceType::Query => &mut self.queries,
+            ResourceType::Fence => &mut self.fences,
+            ResourceType::Semaphore => &mut self.semaphores,
+        }
+    }
+
+    /// Get the total number of handles.
+    pub fn total(&self) -> usize {
+        self.buffers + self.shaders + self.programs + self.psos +
+        self.textures + self.srvs + self.uavs + self.rtvs +
+        self.dsvs + self.samplers + self.queries + self.fences + self.semaphores
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entries = [
+            ("buffers", self.buffers),
+            ("shaders", self.shaders),
+            ("programs", self.programs),
+            ("psos", self.psos),
+            ("textures", self.textures),
+            ("srvs", self.srvs),
+            ("uavs", self.uavs),
+            ("rtvs", self.rtvs),
+            ("dsvs", self.dsvs),
+            ("samplers", self.samplers),
+            ("queries", self.queries),
+            ("fences", self.fences),
+            ("semaphores", self.semaphores),
+        ];
+        let mut first = true;
+        for &(name, count) in entries.iter().filter(|&&(_, count)| count != 0) {
+            if !first {
+                try!(write!(f, ", "));
+            }
+            try!(write!(f, "{}: {}", name, count));
+            first = false;
+        }
+        if first {
+            try!(write!(f, "none"));
+        }
+        Ok(())
+    }
+}
+
+/// Statistics of the resources owned by a handle manager.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Report {
+    /// Handles currently alive, including the ones waiting for a cleanup.
+    pub live: Counts,
+    /// Handles created since the previous report.
+    pub created: Counts,
+    /// Handles destroyed since the previous report.
+    pub destroyed: Counts,
+    /// Estimated size of all the buffers, in bytes.
+    pub buffer_memory: usize,
+    /// Estimated size of all the textures, in bytes.
+    pub texture_memory: usize,
+    /// Creation backtraces of the live handles. Only collected in debug builds,
+    /// with the `backtrace` feature enabled.
+    pub origins: Vec<(ResourceType, String)>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Live resources: {}", self.live));
+        try!(writeln!(f, "Created since the last report: {}", self.created));
+        try!(writeln!(f, "Destroyed since the last report: {}", self.destroyed));
+        try!(writeln!(f, "Buffer memory: {} KiB", self.buffer_memory >> 10));
+        try!(writeln!(f, "Texture memory: {} KiB", self.texture_memory >> 10));
+        for &(ty, ref origin) in self.origins.iter() {
+            try!(writeln!(f, "{:?} created at:\n{}", ty, origin));
+        }
+        Ok(())
+    }
+}
+
+/// Number of handles referenced by the submissions of a single frame,
+/// above which the queue assumes that `Device::cleanup()` is never called.
+pub const MAX_FRAME_RESOURCES: usize = 999999;
+
+fn address<X>(r: &Arc<X>) -> usize {
+    &**r as *const X as usize
+}
+
+/// Where a handle was created.
+#[cfg(feature = "backtrace")]
+type Origin = Backtrace;
+#[cfg(not(feature = "backtrace"))]
+type Origin = ();
+
+#[cfg(feature = "backtrace")]
+fn capture_origin() -> Option<Origin> {
+    if cfg!(debug_assertions) {
+        Some(Backtrace::new_unresolved())
+    } else {
+        None
+    }
+}
+
+#[cfg(not(feature = "backtrace"))]
+fn capture_origin() -> Option<Origin> {
+    None
+}
+
+#[cfg(feature = "backtrace")]
+fn describe_origin(origin: &Origin) -> String {
+    let mut backtrace = origin.clone();
+    backtrace.resolve();
+    format!("{:?}", backtrace)
+}
+
+#[cfg(not(feature = "backtrace"))]
+fn describe_origin(_: &Origin) -> String {
+    String::new()
+}
+
 /// Stores reference-counted resources used in a command buffer.
 /// Seals actual resource names behind the interface, automatically
 /// referencing them both by the Factory on resource creation
@@ -269,8 +465,12 @@ pub struct Manager<R: Resources> {
     rtvs:          Vec<Arc<R::RenderTargetView>>,
     dsvs:          Vec<Arc<R::DepthStencilView>>,
     samplers:      Vec<Arc<R::Sampler>>,
+    queries:       Vec<Arc<R::Query>>,
     fences:        Vec<Arc<Mutex<R::Fence>>>,
     semaphores:    Vec<Arc<Mutex<R::Semaphore>>>,
+    created:       Counts,
+    destroyed:     Counts,
+    origins:       HashMap<usize, (ResourceType, Origin)>,
 }
 
 /// A service trait to be used by the device implementation
@@ -291,6 +491,7 @@ pub trait Producer<R: Resources> {
     fn make_rtv(&mut self, R::RenderTargetView, &RawTexture<R>, texture::Dimensions) -> RawRenderTargetView<R>;
     fn make_dsv(&mut self, R::DepthStencilView, &RawTexture<R>, texture::Dimensions) -> RawDepthStencilView<R>;
     fn make_sampler(&mut self, R::Sampler, texture::SamplerInfo) -> Sampler<R>;
+    fn make_query(&mut self, R::Query) -> Query<R>;
     fn make_fence(&mut self, name: R::Fence) -> Fence<R>;
     fn make_semaphore(&mut self, R::Semaphore) -> Semaphore<R>;
 
@@ -309,7 +510,8 @@ pub trait Producer<R: Resources> {
         J: Fn(&mut T, &mut R::Sampler),
         K: Fn(&mut T, &mut Mutex<R::Fence>),
         L: Fn(&mut T, &mut Mutex<R::Semaphore>),
-    >(&mut self, &mut T, A, B, C, D, E, F, G, H, I, J, K, L);
+        M: Fn(&mut T, &mut R::Query),
+    >(&mut self, &mut T, A, B, C, D, E, F, G, H, I, J, K, L, M);
 }
 
 impl<R: Resources> Producer<R> for Manager<R> {
@@ -319,84 +521,105 @@ impl<R: Resources> Producer<R> for Manager<R> {
                    mapping: Option<R::Mapping>) -> RawBuffer<R> {
         let r = Arc::new(buffer::Raw::new(res, info, mapping));
         self.buffers.push(r.clone());
+        self.track(ResourceType::Buffer, &r);
         RawBuffer(r)
     }
 
     fn make_shader(&mut self, res: R::Shader) -> Shader<R> {
         let r = Arc::new(res);
         self.shaders.push(r.clone());
+        self.track(ResourceType::Shader, &r);
         Shader(r)
     }
 
     fn make_program(&mut self, res: R::Program, info: shade::ProgramInfo) -> Program<R> {
         let r = Arc::new(shade::Program::new(res, info));
         self.programs.push(r.clone());
+        self.track(ResourceType::Program, &r);
         Program(r)
     }
 
     fn make_pso(&mut self, res: R::PipelineStateObject, program: &Program<R>) -> RawPipelineState<R> {
         let r = Arc::new(res);
         self.psos.push(r.clone());
+        self.track(ResourceType::PipelineState, &r);
         RawPipelineState(r, program.clone())
     }
 
     fn make_texture(&mut self, res: R::Texture, info: texture::Info) -> RawTexture<R> {
         let r = Arc::new(texture::Raw::new(res, info));
         self.textures.push(r.clone());
+        self.track(ResourceType::Texture, &r);
         RawTexture(r)
     }
 
     fn make_buffer_srv(&mut self, res: R::ShaderResourceView, buf: &RawBuffer<R>) -> RawShaderResourceView<R> {
         let r = Arc::new(res);
         self.srvs.push(r.clone());
+        self.track(ResourceType::ShaderResourceView, &r);
         RawShaderResourceView(r, ViewSource::Buffer(buf.clone()))
     }
 
     fn make_texture_srv(&mut self, res: R::ShaderResourceView, tex: &RawTexture<R>) -> RawShaderResourceView<R> {
         let r = Arc::new(res);
         self.srvs.push(r.clone());
+        self.track(ResourceType::ShaderResourceView, &r);
         RawShaderResourceView(r, ViewSource::Texture(tex.clone()))
     }
 
     fn make_buffer_uav(&mut self, res: R::UnorderedAccessView, buf: &RawBuffer<R>) -> RawUnorderedAccessView<R> {
         let r = Arc::new(res);
         self.uavs.push(r.clone());
+        self.track(ResourceType::UnorderedAccessView, &r);
         RawUnorderedAccessView(r, ViewSource::Buffer(buf.clone()))
     }
 
     fn make_texture_uav(&mut self, res: R::UnorderedAccessView, tex: &RawTexture<R>) -> RawUnorderedAccessView<R> {
         let r = Arc::new(res);
         self.uavs.push(r.clone());
+        self.track(ResourceType::UnorderedAccessView, &r);
         RawUnorderedAccessView(r, ViewSource::Texture(tex.clone()))
     }
 
     fn make_rtv(&mut self, res: R::RenderTargetView, tex: &RawTexture<R>, dim: texture::Dimensions) -> RawRenderTargetView<R> {
         let r = Arc::new(res);
         self.rtvs.push(r.clone());
+        self.track(ResourceType::RenderTargetView, &r);
         RawRenderTargetView(r, tex.clone(), dim)
     }
 
     fn make_dsv(&mut self, res: R::DepthStencilView, tex: &RawTexture<R>, dim: texture::Dimensions) -> RawDepthStencilView<R> {
         let r = Arc::new(res);
         self.dsvs.push(r.clone());
+        self.track(ResourceType::DepthStencilView, &r);
         RawDepthStencilView(r, tex.clone(), dim)
     }
 
     fn make_sampler(&mut self, res: R::Sampler, info: texture::SamplerInfo) -> Sampler<R> {
         let r = Arc::new(res);
         self.samplers.push(r.clone());
+        self.track(ResourceType::Sampler, &r);
         Sampler(r, info)
     }
 
+    fn make_query(&mut self, res: R::Query) -> Query<R> {
+        let r = Arc::new(res);
+        self.queries.push(r.clone());
+        self.track(ResourceType::Query, &r);
+        Query(r)
+    }
+
     fn make_fence(&mut self, res: R::Fence) -> Fence<R> {
         let r = Arc::new(Mutex::new(res));
         self.fences.push(r.clone());
+        self.track(ResourceType::Fence, &r);
         Fence(r)
     }
 
     fn make_semaphore(&mut self, res: R::Semaphore) -> Semaphore<R> {
         let r = Arc::new(Mutex::new(res));
         self.semaphores.push(r.clone());
+        self.track(ResourceType::Semaphore, &r);
         Semaphore(r)
     }
 
@@ -413,8 +636,11 @@ impl<R: Resources> Producer<R> for Manager<R> {
         J: Fn(&mut T, &mut R::Sampler),
         K: Fn(&mut T, &mut Mutex<R::Fence>),
         L: Fn(&mut T, &mut Mutex<R::Semaphore>),
-    >(&mut self, param: &mut T, fa: A, fb: B, fc: C, fd: D, fe: E, ff: F, fg: G, fh: H, fi: I, fj: J, fk: K, fl: L) {
-        fn clean_vec<X, Param, Fun>(param: &mut Param, vector: &mut Vec<Arc<X>>, fun: Fun)
+        M: Fn(&mut T, &mut R::Query),
+    >(&mut self, param: &mut T, fa: A, fb: B, fc: C, fd: D, fe: E, ff: F, fg: G, fh: H, fi: I, fj: J, fk: K, fl: L, fm: M) {
+        fn clean_vec<X, Param, Fun>(param: &mut Param, vector: &mut Vec<Arc<X>>,
+                                    destroyed: &mut usize, origins: &mut HashMap<usize, (ResourceType, Origin)>,
+                                    fun: Fun)
             where Fun: Fn(&mut Param, &mut X)
         {
             let mut temp = Vec::new();
@@ -428,21 +654,24 @@ impl<R: Resources> Producer<R> for Manager<R> {
             // update the resource vector by removing the elements
             // starting from the last one
             for t in temp.iter().rev() {
-                vector.swap_remove(*t);
+                let r = vector.swap_remove(*t);
+                origins.remove(&address(&r));
+                *destroyed += 1;
             }
         }
-        clean_vec(param, &mut self.buffers,       fa);
-        clean_vec(param, &mut self.shaders,       fb);
-        clean_vec(param, &mut self.programs,      fc);
-        clean_vec(param, &mut self.psos,          fd);
-        clean_vec(param, &mut self.textures,      fe);
-        clean_vec(param, &mut self.srvs,          ff);
-        clean_vec(param, &mut self.uavs,          fg);
-        clean_vec(param, &mut self.rtvs,          fh);
-        clean_vec(param, &mut self.dsvs,          fi);
-        clean_vec(param, &mut self.samplers,      fj);
-        clean_vec(param, &mut self.fences,        fk);
-        clean_vec(param, &mut self.semaphores,    fl);
+        clean_vec(param, &mut self.buffers,    &mut self.destroyed.buffers,    &mut self.origins, fa);
+        clean_vec(param, &mut self.shaders,    &mut self.destroyed.shaders,    &mut self.origins, fb);
+        clean_vec(param, &mut self.programs,   &mut self.destroyed.programs,   &mut self.origins, fc);
+        clean_vec(param, &mut self.psos,       &mut self.destroyed.psos,       &mut self.origins, fd);
+        clean_vec(param, &mut self.textures,   &mut self.destroyed.textures,   &mut self.origins, fe);
+        clean_vec(param, &mut self.srvs,       &mut self.destroyed.srvs,       &mut self.origins, ff);
+        clean_vec(param, &mut self.uavs,       &mut self.destroyed.uavs,       &mut self.origins, fg);
+        clean_vec(param, &mut self.rtvs,       &mut self.destroyed.rtvs,       &mut self.origins, fh);
+        clean_vec(param, &mut self.dsvs,       &mut self.destroyed.dsvs,       &mut self.origins, fi);
+        clean_vec(param, &mut self.samplers,   &mut self.destroyed.samplers,   &mut self.origins, fj);
+        clean_vec(param, &mut self.fences,     &mut self.destroyed.fences,     &mut self.origins, fk);
+        clean_vec(param, &mut self.semaphores, &mut self.destroyed.semaphores, &mut self.origins, fl);
+        clean_vec(param, &mut self.queries,    &mut self.destroyed.queries,    &mut self.origins, fm);
     }
 }
 
@@ -460,8 +689,12 @@ impl<R: Resources> Manager<R> {
             rtvs: Vec::new(),
             dsvs: Vec::new(),
             samplers: Vec::new(),
+            queries: Vec::new(),
             fences: Vec::new(),
             semaphores: Vec::new(),
+            created: Counts::default(),
+            destroyed: Counts::default(),
+            origins: HashMap::new(),
         }
     }
     /// Clear all references
@@ -476,8 +709,10 @@ impl<R: Resources> Manager<R> {
         self.rtvs.clear();
         self.dsvs.clear();
         self.samplers.clear();
+        self.queries.clear();
         self.fences.clear();
         self.semaphores.clear();
+        self.origins.clear();
     }
     /// Extend with all references of another handle manager
     pub fn extend(&mut self, other: &Manager<R>) {
@@ -491,23 +726,54 @@ impl<R: Resources> Manager<R> {
         self.rtvs      .extend(other.rtvs      .iter().map(|h| h.clone()));
         self.dsvs      .extend(other.dsvs      .iter().map(|h| h.clone()));
         self.samplers  .extend(other.samplers  .iter().map(|h| h.clone()));
+        self.queries   .extend(other.queries   .iter().map(|h| h.clone()));
         self.fences    .extend(other.fences    .iter().map(|h| h.clone()));
         self.semaphores.extend(other.semaphores.iter().map(|h| h.clone()));
     }
     /// Count the total number of referenced resources
     pub fn count(&self) -> usize {
-        self.buffers.len() +
-        self.shaders.len() +
-        self.programs.len() +
-        self.psos.len() +
-        self.textures.len() +
-        self.srvs.len() +
-        self.uavs.len() +
-        self.rtvs.len() +
-        self.dsvs.len() +
-        self.samplers.len() +
-        self.fences.len() +
-        self.semaphores.len()
+        self.counts().total()
+    }
+    /// Count the referenced resources of each type
+    pub fn counts(&self) -> Counts {
+        Counts {
+            buffers: self.buffers.len(),
+            shaders: self.shaders.len(),
+            programs: self.programs.len(),
+            psos: self.psos.len(),
+            textures: self.textures.len(),
+            srvs: self.srvs.len(),
+            uavs: self.uavs.len(),
+            rtvs: self.rtvs.len(),
+            dsvs: self.dsvs.len(),
+            samplers: self.samplers.len(),
+            queries: self.queries.len(),
+            fences: self.fences.len(),
+            semaphores: self.semaphores.len(),
+        }
+    }
+    /// Gather the resource statistics, resetting the creation and
+    /// destruction counters. Calling it once per frame gives per-frame deltas.
+    pub fn report(&mut self) -> Report {
+        use std::mem::replace;
+        let mut origins: Vec<_> = self.origins.values().map(|&(ty, ref origin)| {
+            (ty, describe_origin(origin))
+        }).collect();
+        origins.sort();
+        Report {
+            live: self.counts(),
+            created: replace(&mut self.created, Counts::default()),
+            destroyed: replace(&mut self.destroyed, Counts::default()),
+            buffer_memory: self.buffers.iter().map(|b| b.get_info().size).sum(),
+            texture_memory: self.textures.iter().map(|t| t.get_info().get_memory_size()).sum(),
+            origins: origins,
+        }
+    }
+    fn track<X>(&mut self, ty: ResourceType, r: &Arc<X>) {
+        *self.created.get_mut(ty) += 1;
+        if let Some(origin) = capture_origin() {
+            self.origins.insert(address(r), (ty, origin));
+        }
     }
     /// Reference a buffer
     pub fn ref_buffer<'a>(&mut self, handle: &'a RawBuffer<R>) -> &'a R::Buffer {
@@ -562,6 +828,11 @@ impl<R: Resources> Manager<R> {
         self.samplers.push(handle.0.clone());
         &handle.0
     }
+    /// Reference a query
+    pub fn ref_query<'a>(&mut self, handle: &'a Query<R>) -> &'a R::Query {
+        self.queries.push(handle.0.clone());
+        &handle.0
+    }
     /// Reference a fence
     pub fn ref_fence<'a>(&mut self, fence: &'a Fence<R>) -> &'a Mutex<R::Fence> {
         self.fences.push(fence.0.clone());
@@ -17,6 +17,8 @@
 //! Low-level graphics abstraction for Rust. Mostly operates on data, not types.
 //! Designed for use by libraries and higher-level abstractions only.
 
+#[cfg(feature = "backtrace")]
+extern crate backtrace;
 #[macro_use]
 extern crate bitflags;
 #[macro_use]
@@ -80,6 +82,8 @@ pub const MAX_RESOURCE_VIEWS: usize = 32;
 pub const MAX_UNORDERED_VIEWS: usize = 4;
 /// Compile-time maximum number of samplers.
 pub const MAX_SAMPLERS: usize = 16;
+/// Compile-time maximum number of viewports.
+pub const MAX_VIEWPORTS: usize = 16;
 
 /// Draw vertex count.
 pub type VertexCount = u32;
@@ -159,6 +163,22 @@ pub struct Capabilities {
     pub max_texture_size: usize,
     /// Maximum number of vertices for each patch.
     pub max_patch_size: PatchSize,
+    /// Maximum number of viewports that can be set at once.
+    pub max_viewports: usize,
+    /// Maximum number of color targets that can be bound at once, up to `MAX_COLOR_TARGETS`.
+    pub max_color_targets: usize,
+    /// Maximum number of vertex attributes a shader can consume, up to `MAX_VERTEX_ATTRIBUTES`.
+    pub max_vertex_attributes: usize,
+    /// Maximum size of a constant buffer, in bytes.
+    pub max_constant_buffer_size: usize,
+    /// Maximum supported size of a 3D texture, per dimension.
+    pub max_texture_3d_size: usize,
+    /// Maximum number of layers of an array texture.
+    pub max_texture_array_layers: usize,
+    /// Maximum anisotropic filtering factor, 1 if not supported.
+    pub max_anisotropy: u8,
+    /// Maximum number of samples per pixel of a multisampled target.
+    pub max_samples: usize,
 
     /// Support offsets for instanced drawing.
     pub instance_base_supported: bool,
@@ -176,8 +196,33 @@ pub struct Capabilities {
     pub unordered_access_view_supported: bool,
     /// Support specifying the blend function and equation for each color target.
     pub separate_blending_slots_supported: bool,
+    /// Support blend factors taking the second color output of the pixel shader.
+    pub dual_source_blending_supported: bool,
+    /// Support logic operations on the color targets.
+    pub logic_op_supported: bool,
+    /// Support capturing the vertex data into a buffer (transform feedback).
+    pub stream_output_supported: bool,
+    /// Support occlusion queries. Without it, the queries report all their
+    /// samples as passed, so that nothing gets culled.
+    pub occlusion_query_supported: bool,
+    /// Support skipping draw calls based on the result of an occlusion query.
+    pub conditional_render_supported: bool,
     /// Support accelerated buffer copy.
     pub copy_buffer_supported: bool,
+    /// Support geometry shaders.
+    pub geometry_shader_supported: bool,
+    /// Support hull and domain (tessellation) shaders.
+    pub tessellation_supported: bool,
+    /// Support compute shaders.
+    pub compute_supported: bool,
+    /// Support draw calls with the arguments sourced from a buffer.
+    pub indirect_draw_supported: bool,
+    /// Support disabling the sRGB decoding when sampling from sRGB textures.
+    pub srgb_decode_supported: bool,
+    /// Support clamping the depth instead of clipping the primitives.
+    pub depth_clamp_supported: bool,
+    /// Support filtering across the faces of cube maps.
+    pub seamless_cube_supported: bool,
 }
 
 /// Describes what geometric primitives are created from vertex data.
@@ -276,6 +321,7 @@ pub trait Resources:          Clone + Hash + Debug + Eq + PartialEq + Any {
     type RenderTargetView:    Clone + Hash + Debug + Eq + PartialEq + Any + Send + Sync + Copy;
     type DepthStencilView:    Clone + Hash + Debug + Eq + PartialEq + Any + Send + Sync;
     type Sampler:             Clone + Hash + Debug + Eq + PartialEq + Any + Send + Sync + Copy;
+    type Query:               Clone + Hash + Debug + Eq + PartialEq + Any + Send + Sync + Copy;
     type Fence:               Debug + Any + Send + Sync;
     type Semaphore:           Debug + Any + Send + Sync;
     type Mapping:             Debug + Any + Send + Sync + mapping::Gate<Self>;
@@ -19,7 +19,7 @@
 use std::error::Error as StdError;
 use std::fmt;
 use std::cell::UnsafeCell;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
 use std::sync::atomic::{self, AtomicBool};
 use Resources;
 use {memory, buffer, handle};
@@ -42,6 +42,8 @@ pub enum Error {
     InvalidAccess(memory::Access, memory::Usage),
     /// The requested mapping access overlaps with another.
     AccessOverlap,
+    /// The requested range is outside the buffer.
+    OutOfBounds,
 }
 
 impl fmt::Display for Error {
@@ -51,7 +53,7 @@ impl fmt::Display for Error {
             InvalidAccess(ref access, ref usage) => {
                 write!(f, "{}: access = {:?}, usage = {:?}", self.description(), access, usage)
             }
-            AccessOverlap => write!(f, "{}", self.description())
+            AccessOverlap | OutOfBounds => write!(f, "{}", self.description())
         }
     }
 }
@@ -61,7 +63,8 @@ impl StdError for Error {
         use self::Error::*;
         match *self {
             InvalidAccess(..) => "The requested mapping access did not match the expected usage",
-            AccessOverlap => "The requested mapping access overlaps with another"
+            AccessOverlap => "The requested mapping access overlaps with another",
+            OutOfBounds => "The requested range is outside the buffer",
         }
     }
 }
@@ -144,12 +147,26 @@ fn take_access_checked<R>(access: memory::Access, buffer: &buffer::Raw<R>)
     let usage = buffer.get_info().usage;
     use memory::Usage::*;
     match usage {
-        Upload if access == memory::WRITE => (),
+        Upload | Dynamic if access == memory::WRITE => (),
         Download if access == memory::READ => (),
         _ => return Err(Error::InvalidAccess(access, usage)),
     }
 
-    Guard::new(buffer.mapping().unwrap())
+    match buffer.mapping() {
+        Some(raw) => Guard::new(raw),
+        // the backend doesn't map buffers of this usage
+        None => Err(Error::InvalidAccess(access, usage)),
+    }
+}
+
+unsafe fn check_range<R, T>(buffer: &buffer::Raw<R>, range: &Range<usize>) -> Result<(), Error>
+    where R: Resources
+{
+    if range.start <= range.end && range.end <= buffer.len::<T>() {
+        Ok(())
+    } else {
+        Err(Error::OutOfBounds)
+    }
 }
 
 #[doc(hidden)]
@@ -157,25 +174,45 @@ pub unsafe fn read<R, T, S>(buffer: &buffer::Raw<R>, sync: S)
                             -> Result<Reader<R, T>, Error>
     where R: Resources, T: Copy, S: FnOnce(&mut R::Mapping)
 {
+    read_range(buffer, 0 .. buffer.len::<T>(), sync)
+}
+
+#[doc(hidden)]
+pub unsafe fn write<R, T, S>(buffer: &buffer::Raw<R>, sync: S)
+                             -> Result<Writer<R, T>, Error>
+    where R: Resources, T: Copy, S: FnOnce(&mut R::Mapping)
+{
+    write_range(buffer, 0 .. buffer.len::<T>(), sync)
+}
+
+/// Like `read`, but only gives access to the elements in `range`.
+#[doc(hidden)]
+pub unsafe fn read_range<R, T, S>(buffer: &buffer::Raw<R>, range: Range<usize>, sync: S)
+                                  -> Result<Reader<R, T>, Error>
+    where R: Resources, T: Copy, S: FnOnce(&mut R::Mapping)
+{
+    try!(check_range::<R, T>(buffer, &range));
     let mut mapping = try!(take_access_checked(memory::READ, buffer));
     sync(&mut mapping);
 
     Ok(Reader {
-        slice: mapping.slice(buffer.len::<T>()),
+        slice: &mapping.slice(buffer.len::<T>())[range],
         mapping: mapping,
     })
 }
 
+/// Like `write`, but only gives access to the elements in `range`.
 #[doc(hidden)]
-pub unsafe fn write<R, T, S>(buffer: &buffer::Raw<R>, sync: S)
-                             -> Result<Writer<R, T>, Error>
+pub unsafe fn write_range<R, T, S>(buffer: &buffer::Raw<R>, range: Range<usize>, sync: S)
+                                   -> Result<Writer<R, T>, Error>
     where R: Resources, T: Copy, S: FnOnce(&mut R::Mapping)
 {
+    try!(check_range::<R, T>(buffer, &range));
     let mut mapping = try!(take_access_checked(memory::WRITE, buffer));
     sync(&mut mapping);
 
     Ok(Writer {
-        slice: mapping.mut_slice(buffer.len::<T>()),
+        slice: &mut mapping.mut_slice(buffer.len::<T>())[range],
         mapping: mapping,
     })
 }
@@ -187,6 +224,21 @@ pub struct Reader<'a, R: Resources, T: 'a + Copy> {
     #[allow(dead_code)] mapping: Guard<'a, R>,
 }
 
+#[doc(hidden)]
+impl<'a, R: Resources, T: 'a + Copy> Reader<'a, R, T> {
+    /// Restrict the reader to the elements in `range`.
+    pub fn into_range(self, range: Range<usize>) -> Result<Self, Error> {
+        if range.start > range.end || range.end > self.slice.len() {
+            return Err(Error::OutOfBounds);
+        }
+        let slice = self.slice;
+        Ok(Reader {
+            slice: &slice[range],
+            mapping: self.mapping,
+        })
+    }
+}
+
 impl<'a, R: Resources, T: 'a + Copy> Deref for Reader<'a, R, T> {
     type Target = [T];
 
@@ -202,6 +254,21 @@ pub struct Writer<'a, R: Resources, T: 'a + Copy> {
     #[allow(dead_code)] mapping: Guard<'a, R>,
 }
 
+#[doc(hidden)]
+impl<'a, R: Resources, T: 'a + Copy> Writer<'a, R, T> {
+    /// Restrict the writer to the elements in `range`.
+    pub fn into_range(self, range: Range<usize>) -> Result<Self, Error> {
+        if range.start > range.end || range.end > self.slice.len() {
+            return Err(Error::OutOfBounds);
+        }
+        let Writer { slice, mapping } = self;
+        Ok(Writer {
+            slice: &mut slice[range],
+            mapping: mapping,
+        })
+    }
+}
+
 impl<'a, R: Resources, T: 'a + Copy> Deref for Writer<'a, R, T> {
     type Target = [T];
 
@@ -212,46 +279,43 @@ impl<'a, R: Resources, T: 'a + Copy> DerefMut for Writer<'a, R, T> {
     fn deref_mut(&mut self) -> &mut [T] { self.slice }
 }
 
-/// A service struct that can be used by backends to track the mapping status
+/// A service struct that can be used by backends to track the mapping status.
+/// The GPU accesses are tracked per byte range, so that the CPU only has to wait
+/// for the ones overlapping the region it accesses.
 #[derive(Debug)]
 #[doc(hidden)]
 pub struct Status<R: Resources> {
-    cpu_wrote: bool,
-    gpu_access: Option<handle::Fence<R>>,
+    gpu_access: Vec<(Range<usize>, handle::Fence<R>)>,
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
 }
 
 #[doc(hidden)]
 impl<R: Resources> Status<R> {
     pub fn clean() -> Self {
         Status {
-            cpu_wrote: false,
-            gpu_access: None,
+            gpu_access: Vec::new(),
         }
     }
 
-    pub fn cpu_access<F>(&mut self, wait_fence: F)
-        where F: FnOnce(handle::Fence<R>)
+    pub fn cpu_access<F>(&mut self, range: Range<usize>, mut wait_fence: F)
+        where F: FnMut(handle::Fence<R>)
     {
-        self.gpu_access.take().map(wait_fence);
-    }
-
-    pub fn cpu_write_access<F>(&mut self, wait_fence: F)
-        where F: FnOnce(handle::Fence<R>)
-    {
-        self.cpu_access(wait_fence);
-        self.cpu_wrote = true;
-    }
-
-    pub fn gpu_access(&mut self, fence: handle::Fence<R>) {
-        self.gpu_access = Some(fence);
+        let mut i = 0;
+        while i < self.gpu_access.len() {
+            if overlaps(&self.gpu_access[i].0, &range) {
+                wait_fence(self.gpu_access.swap_remove(i).1);
+            } else {
+                i += 1;
+            }
+        }
     }
 
-    pub fn ensure_flushed<F>(&mut self, flush: F)
-        where F: FnOnce()
-    {
-        if self.cpu_wrote {
-            flush();
-            self.cpu_wrote = false;
-        }
+    pub fn gpu_access(&mut self, range: Range<usize>, fence: handle::Fence<R>) {
+        // the new fence is signalled after the older ones it covers
+        self.gpu_access.retain(|&(ref r, _)| r.start < range.start || r.end > range.end);
+        self.gpu_access.push((range, fence));
     }
 }
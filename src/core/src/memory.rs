@@ -73,6 +73,7 @@ bitflags!(
     /// - [`UNORDERED_ACCESS`](constant.UNORDERED_ACCESS.html)
     /// - [`TRANSFER_SRC`](constant.TRANSFER_SRC.html)
     /// - [`TRANSFER_DST`](constant.TRANSFER_DST.html)
+    /// - [`STREAM_OUTPUT`](constant.STREAM_OUTPUT.html)
     ///
     ///
     /// This information is used to create resources
@@ -91,13 +92,15 @@ bitflags!(
         const TRANSFER_SRC     = 0x10,
         /// Can be transfered into.
         const TRANSFER_DST     = 0x20,
+        /// Can capture the vertex data produced by the shaders.
+        const STREAM_OUTPUT    = 0x40,
     }
 );
 
 impl Bind {
     /// Is this memory bound to be mutated ?
     pub fn is_mutable(&self) -> bool {
-        let mutable = TRANSFER_DST | UNORDERED_ACCESS | RENDER_TARGET | DEPTH_STENCIL;
+        let mutable = TRANSFER_DST | UNORDERED_ACCESS | RENDER_TARGET | DEPTH_STENCIL | STREAM_OUTPUT;
         self.intersects(mutable)
     }
 }
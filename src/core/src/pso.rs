@@ -31,6 +31,12 @@ use std::fmt;
 /// Maximum number of vertex buffers used in a PSO definition.
 pub const MAX_VERTEX_BUFFERS: usize = 4;
 
+/// Maximum number of varyings captured by the stream output of a PSO.
+pub const MAX_STREAM_OUTPUT_VARYINGS: usize = 8;
+
+/// Maximum length of the name of a captured varying, in bytes.
+pub const MAX_VARYING_NAME: usize = 32;
+
 /// An offset inside a vertex buffer, in bytes.
 pub type BufferOffset = usize;
 
@@ -50,6 +56,80 @@ impl Error for CreationError {
     }
 }
 
+/// Blend factors that take the second color output of the pixel shader
+/// instead of the first one, for dual-source blending.
+///
+/// For each enabled factor, the `SourceColor` and `SourceAlpha` blend values
+/// are read from the output at index 1 of the same color slot.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct DualSource {
+    /// Source factor of the color channel
+    pub color_source: bool,
+    /// Destination factor of the color channel
+    pub color_destination: bool,
+    /// Source factor of the alpha channel
+    pub alpha_source: bool,
+    /// Destination factor of the alpha channel
+    pub alpha_destination: bool,
+}
+impl DualSource {
+    /// Take the second output in the destination factors of both channels,
+    /// which is the common setup for subpixel text rendering.
+    pub fn destination() -> DualSource {
+        DualSource {
+            color_destination: true,
+            alpha_destination: true,
+            .. DualSource::default()
+        }
+    }
+    /// Check if any of the factors takes the second output.
+    pub fn is_used(&self) -> bool {
+        self.color_source || self.color_destination ||
+        self.alpha_source || self.alpha_destination
+    }
+}
+
+/// Bitwise logic operation between the pixel shader output (`S`)
+/// and the contents of the color target (`D`).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[repr(u8)]
+pub enum LogicOp {
+    /// `0`
+    Clear,
+    /// `S & D`
+    And,
+    /// `S & !D`
+    AndReverse,
+    /// `S`
+    Copy,
+    /// `!S & D`
+    AndInverted,
+    /// `D`
+    Noop,
+    /// `S ^ D`
+    Xor,
+    /// `S | D`
+    Or,
+    /// `!(S | D)`
+    Nor,
+    /// `!(S ^ D)`
+    Equivalent,
+    /// `!D`
+    Invert,
+    /// `S | !D`
+    OrReverse,
+    /// `!S`
+    CopyInverted,
+    /// `!S | D`
+    OrInverted,
+    /// `!(S & D)`
+    Nand,
+    /// `!0`
+    Set,
+}
+
 /// Color output configuration of the PSO.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
@@ -60,6 +140,10 @@ pub struct ColorInfo {
     pub color: Option<s::BlendChannel>,
     /// Optional alpha blending
     pub alpha: Option<s::BlendChannel>,
+    /// Blend factors taking the second pixel shader output
+    pub dual_source: DualSource,
+    /// Optional logic operation, replacing the blending
+    pub logic_op: Option<LogicOp>,
 }
 impl From<s::ColorMask> for ColorInfo {
     fn from(mask: s::ColorMask) -> ColorInfo {
@@ -67,6 +151,8 @@ impl From<s::ColorMask> for ColorInfo {
             mask: mask,
             color: None,
             alpha: None,
+            dual_source: DualSource::default(),
+            logic_op: None,
         }
     }
 }
@@ -76,6 +162,24 @@ impl From<s::Blend> for ColorInfo {
             mask: s::MASK_ALL,
             color: Some(blend.color),
             alpha: Some(blend.alpha),
+            dual_source: DualSource::default(),
+            logic_op: None,
+        }
+    }
+}
+impl From<(s::Blend, DualSource)> for ColorInfo {
+    fn from((blend, dual): (s::Blend, DualSource)) -> ColorInfo {
+        ColorInfo {
+            dual_source: dual,
+            .. blend.into()
+        }
+    }
+}
+impl From<LogicOp> for ColorInfo {
+    fn from(op: LogicOp) -> ColorInfo {
+        ColorInfo {
+            logic_op: Some(op),
+            .. s::MASK_ALL.into()
         }
     }
 }
@@ -119,6 +223,34 @@ impl From<(s::Depth, s::Stencil)> for DepthStencilInfo {
     }
 }
 
+/// Multisampling state of the PSO.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct MultisampleInfo {
+    /// Derive the sample coverage from the alpha of the first color target
+    pub alpha_to_coverage: bool,
+    /// Mask of the samples allowed to be written
+    pub sample_mask: u32,
+    /// Optional minimum fraction of the samples to be shaded individually,
+    /// in 1/255 units. With `None`, the pixel shader runs once per pixel.
+    pub sample_shading: Option<u8>,
+}
+impl MultisampleInfo {
+    /// Get the minimum fraction of the individually shaded samples.
+    pub fn get_sample_shading(&self) -> Option<f32> {
+        self.sample_shading.map(|fraction| fraction as f32 / 255.0)
+    }
+}
+impl Default for MultisampleInfo {
+    fn default() -> MultisampleInfo {
+        MultisampleInfo {
+            alpha_to_coverage: false,
+            sample_mask: !0,
+            sample_shading: None,
+        }
+    }
+}
+
 /// Index of a vertex buffer.
 pub type BufferIndex = u8;
 /// Offset of an attribute from the start of the buffer, in bytes
@@ -163,6 +295,52 @@ pub type ColorTargetDesc = (format::Format, ColorInfo);
 /// PSO depth-stencil target descriptor
 pub type DepthStencilDesc = (format::Format, DepthStencilInfo);
 
+/// Name of a captured varying. It's stored inline, so that
+/// the PSO descriptor stays `Copy`.
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct VaryingName {
+    length: u8,
+    bytes: [u8; MAX_VARYING_NAME],
+}
+
+impl VaryingName {
+    /// Store the name, unless it's longer than `MAX_VARYING_NAME`.
+    pub fn new(name: &str) -> Option<VaryingName> {
+        if name.len() > MAX_VARYING_NAME {
+            return None;
+        }
+        let mut bytes = [0; MAX_VARYING_NAME];
+        bytes[.. name.len()].copy_from_slice(name.as_bytes());
+        Some(VaryingName {
+            length: name.len() as u8,
+            bytes: bytes,
+        })
+    }
+
+    /// Get the stored name.
+    pub fn as_str(&self) -> &str {
+        ::std::str::from_utf8(&self.bytes[.. self.length as usize]).unwrap()
+    }
+}
+
+impl fmt::Debug for VaryingName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+/// PSO stream output descriptor. The varyings are captured
+/// in the given order, interleaved in a single buffer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct StreamOutputDesc {
+    /// Captured varyings, by name
+    pub varyings: [Option<(VaryingName, Element<format::Format>)>; MAX_STREAM_OUTPUT_VARYINGS],
+    /// Total size of the captured vertex, in bytes
+    pub stride: ElemStride,
+}
+
 /// All the information surrounding a shader program that is required
 /// for PSO creation, including the formats of vertex buffers and pixel targets;
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -190,6 +368,10 @@ pub struct Descriptor {
     pub color_targets: [Option<ColorTargetDesc>; MAX_COLOR_TARGETS],
     /// Depth stencil view (DSV)
     pub depth_stencil: Option<DepthStencilDesc>,
+    /// Multisampling state
+    pub multisample: MultisampleInfo,
+    /// Optional capture of the vertex data
+    pub stream_output: Option<StreamOutputDesc>,
 }
 
 impl Descriptor {
@@ -207,6 +389,8 @@ impl Descriptor {
             samplers: [None; MAX_SAMPLERS],
             color_targets: [None; MAX_COLOR_TARGETS],
             depth_stencil: None,
+            multisample: MultisampleInfo::default(),
+            stream_output: None,
         }
     }
 }
@@ -140,61 +140,170 @@ pub type Location = usize;
 
 // unable to derive anything for fixed arrays
 /// A value that can be uploaded to the device as a uniform.
+///
+/// The matrices are stored as arrays of columns, so `F32Matrix2x3`
+/// has 2 columns of 3 rows, like `mat2x3` in GLSL.
 #[allow(missing_docs)]
 #[derive(Clone, Copy, PartialEq, PartialOrd)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum UniformValue {
     I32(i32),
+    U32(u32),
     F32(f32),
+    F64(f64),
+    Bool(bool),
 
     I32Vector2([i32; 2]),
     I32Vector3([i32; 3]),
     I32Vector4([i32; 4]),
 
+    U32Vector2([u32; 2]),
+    U32Vector3([u32; 3]),
+    U32Vector4([u32; 4]),
+
     F32Vector2([f32; 2]),
     F32Vector3([f32; 3]),
     F32Vector4([f32; 4]),
 
+    F64Vector2([f64; 2]),
+    F64Vector3([f64; 3]),
+    F64Vector4([f64; 4]),
+
+    BoolVector2([bool; 2]),
+    BoolVector3([bool; 3]),
+    BoolVector4([bool; 4]),
+
     F32Matrix2([[f32; 2]; 2]),
     F32Matrix3([[f32; 3]; 3]),
     F32Matrix4([[f32; 4]; 4]),
+    F32Matrix2x3([[f32; 3]; 2]),
+    F32Matrix2x4([[f32; 4]; 2]),
+    F32Matrix3x2([[f32; 2]; 3]),
+    F32Matrix3x4([[f32; 4]; 3]),
+    F32Matrix4x2([[f32; 2]; 4]),
+    F32Matrix4x3([[f32; 3]; 4]),
+
+    F64Matrix2([[f64; 2]; 2]),
+    F64Matrix3([[f64; 3]; 3]),
+    F64Matrix4([[f64; 4]; 4]),
+    F64Matrix2x3([[f64; 3]; 2]),
+    F64Matrix2x4([[f64; 4]; 2]),
+    F64Matrix3x2([[f64; 2]; 3]),
+    F64Matrix3x4([[f64; 4]; 3]),
+    F64Matrix4x2([[f64; 2]; 4]),
+    F64Matrix4x3([[f64; 3]; 4]),
+}
+
+impl UniformValue {
+    /// Get the constant format of this value, with the matrices in the column-major order.
+    pub fn get_format(&self) -> ConstFormat {
+        use self::BaseType::*;
+        use self::ContainerType::*;
+        use self::UniformValue as V;
+        let mf = MatrixFormat::ColumnMajor;
+        match *self {
+            V::I32(_)  => (I32,  Single),
+            V::U32(_)  => (U32,  Single),
+            V::F32(_)  => (F32,  Single),
+            V::F64(_)  => (F64,  Single),
+            V::Bool(_) => (Bool, Single),
+
+            V::I32Vector2(_)  => (I32,  Vector(2)),
+            V::I32Vector3(_)  => (I32,  Vector(3)),
+            V::I32Vector4(_)  => (I32,  Vector(4)),
+            V::U32Vector2(_)  => (U32,  Vector(2)),
+            V::U32Vector3(_)  => (U32,  Vector(3)),
+            V::U32Vector4(_)  => (U32,  Vector(4)),
+            V::F32Vector2(_)  => (F32,  Vector(2)),
+            V::F32Vector3(_)  => (F32,  Vector(3)),
+            V::F32Vector4(_)  => (F32,  Vector(4)),
+            V::F64Vector2(_)  => (F64,  Vector(2)),
+            V::F64Vector3(_)  => (F64,  Vector(3)),
+            V::F64Vector4(_)  => (F64,  Vector(4)),
+            V::BoolVector2(_) => (Bool, Vector(2)),
+            V::BoolVector3(_) => (Bool, Vector(3)),
+            V::BoolVector4(_) => (Bool, Vector(4)),
+
+            V::F32Matrix2(_)   => (F32, Matrix(mf, 2, 2)),
+            V::F32Matrix3(_)   => (F32, Matrix(mf, 3, 3)),
+            V::F32Matrix4(_)   => (F32, Matrix(mf, 4, 4)),
+            V::F32Matrix2x3(_) => (F32, Matrix(mf, 2, 3)),
+            V::F32Matrix2x4(_) => (F32, Matrix(mf, 2, 4)),
+            V::F32Matrix3x2(_) => (F32, Matrix(mf, 3, 2)),
+            V::F32Matrix3x4(_) => (F32, Matrix(mf, 3, 4)),
+            V::F32Matrix4x2(_) => (F32, Matrix(mf, 4, 2)),
+            V::F32Matrix4x3(_) => (F32, Matrix(mf, 4, 3)),
+            V::F64Matrix2(_)   => (F64, Matrix(mf, 2, 2)),
+            V::F64Matrix3(_)   => (F64, Matrix(mf, 3, 3)),
+            V::F64Matrix4(_)   => (F64, Matrix(mf, 4, 4)),
+            V::F64Matrix2x3(_) => (F64, Matrix(mf, 2, 3)),
+            V::F64Matrix2x4(_) => (F64, Matrix(mf, 2, 4)),
+            V::F64Matrix3x2(_) => (F64, Matrix(mf, 3, 2)),
+            V::F64Matrix3x4(_) => (F64, Matrix(mf, 3, 4)),
+            V::F64Matrix4x2(_) => (F64, Matrix(mf, 4, 2)),
+            V::F64Matrix4x3(_) => (F64, Matrix(mf, 4, 3)),
+        }
+    }
+}
+
+macro_rules! fmt_matrix {
+    ($f:expr, $name:expr, $m:expr) => {{
+        try!(write!($f, "{}(", $name));
+        for v in $m.iter() {
+            try!(write!($f, "{:?}", &v[..]));
+        }
+        write!($f, ")")
+    }}
 }
 
 impl fmt::Debug for UniformValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            UniformValue::I32(x)            => write!(f, "ValueI32({:?})", x),
-            UniformValue::F32(x)            => write!(f, "ValueF32({:?})", x),
-
-            UniformValue::I32Vector2(ref v) => write!(f, "ValueI32Vector2({:?})", &v[..]),
-            UniformValue::I32Vector3(ref v) => write!(f, "ValueI32Vector3({:?})", &v[..]),
-            UniformValue::I32Vector4(ref v) => write!(f, "ValueI32Vector4({:?})", &v[..]),
-
-            UniformValue::F32Vector2(ref v) => write!(f, "ValueF32Vector2({:?})", &v[..]),
-            UniformValue::F32Vector3(ref v) => write!(f, "ValueF32Vector3({:?})", &v[..]),
-            UniformValue::F32Vector4(ref v) => write!(f, "ValueF32Vector4({:?})", &v[..]),
-
-            UniformValue::F32Matrix2(ref m) => {
-                try!(write!(f, "ValueF32Matrix2("));
-                for v in m.iter() {
-                    try!(write!(f, "{:?}", &v[..]));
-                }
-                write!(f, ")")
-            },
-            UniformValue::F32Matrix3(ref m) => {
-                try!(write!(f, "ValueF32Matrix3("));
-                for v in m.iter() {
-                    try!(write!(f, "{:?}", &v[..]));
-                }
-                write!(f, ")")
-            },
-            UniformValue::F32Matrix4(ref m) => {
-                try!(write!(f, "ValueF32Matrix4("));
-                for v in m.iter() {
-                    try!(write!(f, "{:?}", &v[..]));
-                }
-                write!(f, ")")
-            },
+            UniformValue::I32(x)             => write!(f, "ValueI32({:?})", x),
+            UniformValue::U32(x)             => write!(f, "ValueU32({:?})", x),
+            UniformValue::F32(x)             => write!(f, "ValueF32({:?})", x),
+            UniformValue::F64(x)             => write!(f, "ValueF64({:?})", x),
+            UniformValue::Bool(x)            => write!(f, "ValueBool({:?})", x),
+
+            UniformValue::I32Vector2(ref v)  => write!(f, "ValueI32Vector2({:?})", &v[..]),
+            UniformValue::I32Vector3(ref v)  => write!(f, "ValueI32Vector3({:?})", &v[..]),
+            UniformValue::I32Vector4(ref v)  => write!(f, "ValueI32Vector4({:?})", &v[..]),
+
+            UniformValue::U32Vector2(ref v)  => write!(f, "ValueU32Vector2({:?})", &v[..]),
+            UniformValue::U32Vector3(ref v)  => write!(f, "ValueU32Vector3({:?})", &v[..]),
+            UniformValue::U32Vector4(ref v)  => write!(f, "ValueU32Vector4({:?})", &v[..]),
+
+            UniformValue::F32Vector2(ref v)  => write!(f, "ValueF32Vector2({:?})", &v[..]),
+            UniformValue::F32Vector3(ref v)  => write!(f, "ValueF32Vector3({:?})", &v[..]),
+            UniformValue::F32Vector4(ref v)  => write!(f, "ValueF32Vector4({:?})", &v[..]),
+
+            UniformValue::F64Vector2(ref v)  => write!(f, "ValueF64Vector2({:?})", &v[..]),
+            UniformValue::F64Vector3(ref v)  => write!(f, "ValueF64Vector3({:?})", &v[..]),
+            UniformValue::F64Vector4(ref v)  => write!(f, "ValueF64Vector4({:?})", &v[..]),
+
+            UniformValue::BoolVector2(ref v) => write!(f, "ValueBoolVector2({:?})", &v[..]),
+            UniformValue::BoolVector3(ref v) => write!(f, "ValueBoolVector3({:?})", &v[..]),
+            UniformValue::BoolVector4(ref v) => write!(f, "ValueBoolVector4({:?})", &v[..]),
+
+            UniformValue::F32Matrix2(ref m)   => fmt_matrix!(f, "ValueF32Matrix2", m),
+            UniformValue::F32Matrix3(ref m)   => fmt_matrix!(f, "ValueF32Matrix3", m),
+            UniformValue::F32Matrix4(ref m)   => fmt_matrix!(f, "ValueF32Matrix4", m),
+            UniformValue::F32Matrix2x3(ref m) => fmt_matrix!(f, "ValueF32Matrix2x3", m),
+            UniformValue::F32Matrix2x4(ref m) => fmt_matrix!(f, "ValueF32Matrix2x4", m),
+            UniformValue::F32Matrix3x2(ref m) => fmt_matrix!(f, "ValueF32Matrix3x2", m),
+            UniformValue::F32Matrix3x4(ref m) => fmt_matrix!(f, "ValueF32Matrix3x4", m),
+            UniformValue::F32Matrix4x2(ref m) => fmt_matrix!(f, "ValueF32Matrix4x2", m),
+            UniformValue::F32Matrix4x3(ref m) => fmt_matrix!(f, "ValueF32Matrix4x3", m),
+
+            UniformValue::F64Matrix2(ref m)   => fmt_matrix!(f, "ValueF64Matrix2", m),
+            UniformValue::F64Matrix3(ref m)   => fmt_matrix!(f, "ValueF64Matrix3", m),
+            UniformValue::F64Matrix4(ref m)   => fmt_matrix!(f, "ValueF64Matrix4", m),
+            UniformValue::F64Matrix2x3(ref m) => fmt_matrix!(f, "ValueF64Matrix2x3", m),
+            UniformValue::F64Matrix2x4(ref m) => fmt_matrix!(f, "ValueF64Matrix2x4", m),
+            UniformValue::F64Matrix3x2(ref m) => fmt_matrix!(f, "ValueF64Matrix3x2", m),
+            UniformValue::F64Matrix3x4(ref m) => fmt_matrix!(f, "ValueF64Matrix3x4", m),
+            UniformValue::F64Matrix4x2(ref m) => fmt_matrix!(f, "ValueF64Matrix4x2", m),
+            UniformValue::F64Matrix4x3(ref m) => fmt_matrix!(f, "ValueF64Matrix4x3", m),
         }
     }
 }
@@ -283,6 +392,7 @@ impl_base_type! {
     i32 = I32,
     u32 = U32,
     f32 = F32,
+    f64 = F64,
     bool = Bool,
 }
 
@@ -448,6 +558,21 @@ pub struct OutputVar {
     pub container: ContainerType,
 }
 
+/// How the resource slots of a program were chosen.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum SlotAssignment {
+    /// The slots are the ones written in the shaders, such as the HLSL
+    /// registers or the GLSL `layout(binding = n)` qualifiers.
+    Explicit,
+    /// The backend has assigned the slots itself, in the order of the names
+    /// of the resources within each kind of resource.
+    Automatic,
+    /// Some slots are written in the shaders, the backend has assigned the
+    /// others to the slots left free, in the order of the names.
+    Mixed,
+}
+
 /// Metadata about a program.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
@@ -471,6 +596,9 @@ pub struct ProgramInfo {
     /// A hacky flag to make sure the clients know we are
     /// unable to actually get the output variable info
     pub knows_outputs: bool,
+    /// Origin of the slots of the constant buffers, textures,
+    /// unordered views and samplers.
+    pub slot_assignment: SlotAssignment,
 }
 
 /// A program
@@ -551,23 +679,16 @@ impl ConstVar {
         if self.count != 1 {
             return Err(CompatibilityError::ErrorArraySize)
         }
-        match (self.base_type, self.container, *value) {
-            (BaseType::I32, ContainerType::Single,         UniformValue::I32(_))        => Ok(()),
-            (BaseType::F32, ContainerType::Single,         UniformValue::F32(_))        => Ok(()),
-
-            (BaseType::F32, ContainerType::Vector(2),      UniformValue::F32Vector2(_)) => Ok(()),
-            (BaseType::F32, ContainerType::Vector(3),      UniformValue::F32Vector3(_)) => Ok(()),
-            (BaseType::F32, ContainerType::Vector(4),      UniformValue::F32Vector4(_)) => Ok(()),
-
-            (BaseType::I32, ContainerType::Vector(2),      UniformValue::I32Vector2(_)) => Ok(()),
-            (BaseType::I32, ContainerType::Vector(3),      UniformValue::I32Vector3(_)) => Ok(()),
-            (BaseType::I32, ContainerType::Vector(4),      UniformValue::I32Vector4(_)) => Ok(()),
-
-            (BaseType::F32, ContainerType::Matrix(_, 2,2), UniformValue::F32Matrix2(_)) => Ok(()),
-            (BaseType::F32, ContainerType::Matrix(_, 3,3), UniformValue::F32Matrix3(_)) => Ok(()),
-            (BaseType::F32, ContainerType::Matrix(_, 4,4), UniformValue::F32Matrix4(_)) => Ok(()),
-
-            _ => Err(CompatibilityError::ErrorBaseType)
+        let (base_type, container) = value.get_format();
+        if base_type != self.base_type {
+            return Err(CompatibilityError::ErrorBaseType)
+        }
+        match (self.container, container) {
+            // the values are uploaded in either order
+            (ContainerType::Matrix(_, c1, r1), ContainerType::Matrix(_, c2, r2))
+                if c1 == c2 && r1 == r2 => Ok(()),
+            (c1, c2) if c1 == c2 => Ok(()),
+            _ => Err(CompatibilityError::ErrorContainer),
         }
     }
 }
@@ -505,6 +505,26 @@ impl Info {
         let format = format::Format(self.format, cty.into());
         self.to_image_info(mip).convert(format)
     }
+
+    /// Estimate the amount of memory occupied by all the levels, in bytes.
+    /// The actual allocation size depends on the implementation.
+    pub fn get_memory_size(&self) -> usize {
+        let (_, _, depth, aa) = self.kind.get_dimensions();
+        // array slices and cube faces are not reduced by mipmapping
+        let (layers, is_volume) = match self.kind {
+            Kind::D3(..) => (1, true),
+            _ => (cmp::max(depth, 1) as usize, false),
+        };
+        let texel_size = (self.format.get_total_bits() as usize + 7) / 8;
+        let texels = (0 .. self.levels).map(|mip| {
+            let image = self.to_image_info(mip);
+            let depth = if is_volume { cmp::max(image.depth, 1) } else { 1 };
+            cmp::max(image.width, 1) as usize *
+            cmp::max(image.height, 1) as usize *
+            depth as usize
+        }).sum::<usize>();
+        texels * layers * texel_size * aa.get_num_fragments() as usize
+    }
 }
 
 /// Texture resource view descriptor.
@@ -512,6 +532,9 @@ impl Info {
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ResourceDesc {
+    /// Surface type to reinterpret the texture as, `None` keeping its own.
+    /// Has to be compatible, see `SurfaceType::is_view_compatible`.
+    pub surface: Option<format::SurfaceType>,
     pub channel: format::ChannelType,
     pub layer: Option<Layer>,
     pub min: Level,
@@ -0,0 +1,34 @@
+extern crate gfx_core as core;
+
+use core::format::SurfaceType;
+
+#[test]
+fn test_view_compatible() {
+    use core::format::SurfaceType::*;
+    // same size per texel
+    assert!(R8_G8_B8_A8.is_view_compatible(B8_G8_R8_A8));
+    assert!(R8_G8_B8_A8.is_view_compatible(R10_G10_B10_A2));
+    assert!(R32.is_view_compatible(R16_G16));
+    assert!(R32_G32_B32_A32.is_view_compatible(R32_G32_B32_A32));
+    // different size per texel
+    assert!(!R8_G8_B8_A8.is_view_compatible(R16_G16_B16_A16));
+    assert!(!R8.is_view_compatible(R16));
+    // packed 16-bit surfaces
+    assert!(R5_G6_B5.is_view_compatible(R5_G6_B5));
+    assert!(!R5_G6_B5.is_view_compatible(R16));
+    assert!(!R4_G4_B4_A4.is_view_compatible(R5_G5_B5_A1));
+    // depth and stencil surfaces
+    assert!(D24_S8.is_view_compatible(D24_S8));
+    assert!(!D24_S8.is_view_compatible(R32));
+    assert!(!R32.is_view_compatible(X8_D24));
+    assert!(!D16.is_view_compatible(R16));
+}
+
+#[test]
+fn test_view_compatible_symmetric() {
+    for &a in SurfaceType::all() {
+        for &b in SurfaceType::all() {
+            assert_eq!(a.is_view_compatible(b), b.is_view_compatible(a), "{:?} and {:?}", a, b);
+        }
+    }
+}
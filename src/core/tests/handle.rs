@@ -49,6 +49,44 @@ fn test_cleanup() {
         |_,_| (),
         |_,_| (),
         |_,_| (),
+        |_,_| (),
         );
     assert_eq!(count, 1);
 }
+
+#[test]
+fn test_report() {
+    use core::texture;
+    let mut man: Manager<DummyResources> = Manager::new();
+    let _ = man.make_shader(());
+    let _ = man.make_buffer((), buffer::Info {
+        role: buffer::Role::Vertex,
+        usage: Usage::Data,
+        size: 16,
+        stride: 0,
+        bind: Bind::empty(),
+    }, None);
+    let _ = man.make_texture((), texture::Info {
+        kind: texture::Kind::D2(4, 4, texture::AaMode::Single),
+        levels: 3,
+        format: core::format::SurfaceType::R8_G8_B8_A8,
+        bind: Bind::empty(),
+        usage: Usage::Data,
+    });
+    let report = man.report();
+    assert_eq!(report.live.shaders, 1);
+    assert_eq!(report.live.textures, 1);
+    assert_eq!(report.created.total(), 3);
+    assert_eq!(report.buffer_memory, 16);
+    // 4x4 + 2x2 + 1x1 texels, 4 bytes each
+    assert_eq!(report.texture_memory, 84);
+    man.clean_with(&mut (),
+        |_,_| (), |_,_| (), |_,_| (), |_,_| (), |_,_| (), |_,_| (),
+        |_,_| (), |_,_| (), |_,_| (), |_,_| (), |_,_| (), |_,_| (),
+        |_,_| ());
+    let report = man.report();
+    assert_eq!(report.live.total(), 0);
+    assert_eq!(report.created.total(), 0);
+    assert_eq!(report.destroyed.total(), 3);
+    assert!(report.origins.is_empty());
+}
@@ -0,0 +1,70 @@
+extern crate gfx_core as core;
+
+use core::dummy::{DummyFence, DummyMapping, DummyResources};
+use core::buffer;
+use core::command::AccessInfo;
+use core::mapping::Status;
+use core::memory::{Bind, Usage};
+use core::handle::{Manager, Producer, RawBuffer};
+
+fn mapped_buffer(man: &mut Manager<DummyResources>, size: usize) -> RawBuffer<DummyResources> {
+    man.make_buffer((), buffer::Info {
+        role: buffer::Role::Vertex,
+        usage: Usage::Upload,
+        size: size,
+        stride: 0,
+        bind: Bind::empty(),
+    }, Some(DummyMapping))
+}
+
+#[test]
+fn test_access_ranges() {
+    let mut man = Manager::new();
+    let buffer = mapped_buffer(&mut man, 64);
+    let mut access = AccessInfo::new();
+    access.buffer_read_range(&buffer, 16 .. 32);
+    access.buffer_read_range(&buffer, 8 .. 12);
+    access.buffer_read_range(&buffer, 40 .. 40);
+    access.buffer_write(&buffer);
+    let reads = access.mapped_reads().map(|(_, r)| r.clone()).collect::<Vec<_>>();
+    let writes = access.mapped_writes().map(|(_, r)| r.clone()).collect::<Vec<_>>();
+    assert_eq!(reads, vec![8 .. 32]);
+    assert_eq!(writes, vec![0 .. 64]);
+}
+
+#[test]
+fn test_unmapped_access() {
+    let mut man: Manager<DummyResources> = Manager::new();
+    let buffer = man.make_buffer((), buffer::Info {
+        role: buffer::Role::Vertex,
+        usage: Usage::Data,
+        size: 64,
+        stride: 0,
+        bind: Bind::empty(),
+    }, None);
+    let mut access = AccessInfo::new();
+    access.buffer_read(&buffer);
+    assert!(!access.has_mapped_reads());
+}
+
+#[test]
+fn test_status_regions() {
+    let mut man: Manager<DummyResources> = Manager::new();
+    let mut status = Status::clean();
+    status.gpu_access(0 .. 16, man.make_fence(DummyFence));
+    status.gpu_access(16 .. 32, man.make_fence(DummyFence));
+
+    let mut waited = 0;
+    status.cpu_access(32 .. 64, |_| waited += 1);
+    assert_eq!(waited, 0);
+    status.cpu_access(8 .. 24, |_| waited += 1);
+    assert_eq!(waited, 2);
+    status.cpu_access(0 .. 32, |_| waited += 1);
+    assert_eq!(waited, 2);
+
+    // a fence covering older ones replaces them
+    status.gpu_access(0 .. 8, man.make_fence(DummyFence));
+    status.gpu_access(0 .. 32, man.make_fence(DummyFence));
+    status.cpu_access(0 .. 64, |_| waited += 1);
+    assert_eq!(waited, 3);
+}
@@ -0,0 +1,106 @@
+// Copyright 2017 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{self, Write};
+use core::{Adapter, Headless, QueueType};
+use device_gl::{PlatformName, PrivateCaps, Version};
+use gfx_window_glutin;
+use glutin::HeadlessRendererBuilder;
+#[cfg(feature = "serialize")]
+use serde_json;
+use {Output, Report};
+
+/// Report of an OpenGL adapter, with the driver information.
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct GlReport {
+    pub report: Report,
+    pub platform_name: PlatformName,
+    pub version: Version,
+    pub shading_language: Version,
+    /// Sorted, to keep the reports comparable.
+    pub extensions: Vec<&'static str>,
+    pub private_caps: PrivateCaps,
+}
+
+fn version_name(api: &str, version: &Version) -> String {
+    if version.is_embedded {
+        format!("{} ES {:?}", api, version)
+    } else {
+        format!("{} {:?}", api, version)
+    }
+}
+
+impl GlReport {
+    pub fn print(&self) {
+        self.report.print();
+        println!("Driver:");
+        println!("  Vendor: {}", self.platform_name.vendor);
+        println!("  Renderer: {}", self.platform_name.renderer);
+        println!("  Version: {}", version_name("OpenGL", &self.version));
+        println!("  Shading language: {}", version_name("GLSL", &self.shading_language));
+        println!("Private capabilities: {:#?}", self.private_caps);
+        println!("Extensions ({}):", self.extensions.len());
+        for ext in &self.extensions {
+            println!("  {}", ext);
+        }
+    }
+}
+
+/// Open the adapters of a headless context, which uses OSMesa on Linux.
+pub fn report(output: Output) {
+    let context = match HeadlessRendererBuilder::new(256, 256).build() {
+        Ok(context) => context,
+        Err(e) => {
+            let _ = writeln!(io::stderr(), "Failed to create a headless GL context: {:?}", e);
+            return
+        }
+    };
+    let mut headless = gfx_window_glutin::Headless(context);
+
+    let reports = headless.get_adapters().into_iter().map(|adapter| {
+        let adapter_info = adapter.get_info().clone();
+        let device = adapter.open_with(|_, _| (1, QueueType::Graphics)).device;
+        let report = Report::new(adapter_info, &device);
+        let info = device.get_info();
+        let mut extensions = info.extensions.iter().cloned().collect::<Vec<_>>();
+        extensions.sort();
+        GlReport {
+            report: report,
+            platform_name: info.platform_name,
+            version: info.version,
+            shading_language: info.shading_language,
+            extensions: extensions,
+            private_caps: *device.get_private_caps(),
+        }
+    }).collect::<Vec<_>>();
+
+    match output {
+        Output::Text => {
+            for report in &reports {
+                report.print();
+            }
+        }
+        Output::Json => print_json(&reports),
+    }
+}
+
+#[cfg(feature = "serialize")]
+fn print_json(reports: &[GlReport]) {
+    println!("{}", serde_json::to_string_pretty(reports).unwrap());
+}
+
+#[cfg(not(feature = "serialize"))]
+fn print_json(_: &[GlReport]) {
+    unreachable!()
+}
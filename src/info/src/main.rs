@@ -0,0 +1,131 @@
+// Copyright 2017 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Opens every available adapter and prints its information, capabilities
+//! and format support, either human-readable or as JSON.
+
+extern crate gfx_core as core;
+#[cfg(feature = "gl")]
+extern crate gfx_device_gl as device_gl;
+#[cfg(feature = "gl")]
+extern crate gfx_window_glutin;
+#[cfg(feature = "gl")]
+extern crate glutin;
+#[cfg(feature = "serialize")]
+extern crate serde;
+#[cfg(feature = "serialize")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "serialize")]
+extern crate serde_json;
+
+use std::io::{self, Write};
+use std::{env, process};
+use core::{AdapterInfo, Capabilities, Device};
+use core::format::{ChannelType, Format, FormatProperties, SurfaceType};
+
+#[cfg(feature = "gl")]
+mod gl;
+
+/// Support of a single format.
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct FormatReport {
+    pub format: Format,
+    pub properties: FormatProperties,
+}
+
+/// Everything known about an adapter that is common to all the backends.
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct Report {
+    pub adapter: AdapterInfo,
+    pub capabilities: Capabilities,
+    pub formats: Vec<FormatReport>,
+}
+
+impl Report {
+    /// Query the device, listing only the formats that are supported.
+    pub fn new<D: Device<R>, R: core::Resources>(adapter: AdapterInfo, device: &D) -> Report {
+        let mut formats = Vec::new();
+        for &surface in SurfaceType::all() {
+            for &channel in ChannelType::all() {
+                let format = Format(surface, channel);
+                let properties = device.get_format_properties(format);
+                if properties.is_supported() {
+                    formats.push(FormatReport {
+                        format: format,
+                        properties: properties,
+                    });
+                }
+            }
+        }
+        Report {
+            adapter: adapter,
+            capabilities: *device.get_capabilities(),
+            formats: formats,
+        }
+    }
+
+    /// Print the report in a human-readable form.
+    pub fn print(&self) {
+        println!("Adapter: {}", self.adapter.name);
+        println!("  Vendor ID: 0x{:04x}", self.adapter.vendor);
+        println!("  Device ID: 0x{:04x}", self.adapter.device);
+        println!("  Software rendering: {}", self.adapter.software_rendering);
+        println!("Capabilities: {:#?}", self.capabilities);
+        println!("Formats:");
+        for f in &self.formats {
+            let samples = (0..8).map(|i| 1 << i)
+                                .filter(|&count| f.properties.supports_samples(count))
+                                .map(|count| count.to_string())
+                                .collect::<Vec<_>>();
+            println!("  {:?} {:?}: {:?}, samples [{}]{}{}",
+                f.format.0, f.format.1, f.properties.bind, samples.join(", "),
+                if f.properties.filterable { ", filterable" } else { "" },
+                if f.properties.blendable { ", blendable" } else { "" });
+        }
+    }
+}
+
+/// Output style of the reports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    Text,
+    Json,
+}
+
+fn usage() -> ! {
+    let _ = writeln!(io::stderr(), "Usage: gfx-info [--json]");
+    process::exit(1)
+}
+
+fn main() {
+    let mut output = Output::Text;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => output = Output::Json,
+            "-h" | "--help" => usage(),
+            _ => {
+                let _ = writeln!(io::stderr(), "Unknown argument: {}", arg);
+                usage()
+            }
+        }
+    }
+    if output == Output::Json && cfg!(not(feature = "serialize")) {
+        let _ = writeln!(io::stderr(), "JSON output requires the `serialize` feature");
+        process::exit(1);
+    }
+
+    #[cfg(feature = "gl")]
+    gl::report(output);
+}
@@ -0,0 +1,251 @@
+//! Checking of the constant buffer layouts against the GLSL packing rules.
+
+use quote;
+use syn;
+use gfx_attributes;
+
+
+/// Memory layout of a GLSL interface block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// The default layout of uniform blocks.
+    Std140,
+    /// The layout of shader storage blocks, with tighter arrays.
+    Std430,
+}
+
+impl Layout {
+    pub fn parse(name: &str) -> Option<Layout> {
+        match name {
+            "std140" => Some(Layout::Std140),
+            "std430" => Some(Layout::Std430),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Layout::Std140 => "std140",
+            Layout::Std430 => "std430",
+        }
+    }
+
+    /// Base alignment of an array element or a matrix column.
+    fn array_align(&self, align: usize) -> usize {
+        match *self {
+            Layout::Std140 => round_up(align, 16),
+            Layout::Std430 => align,
+        }
+    }
+}
+
+fn round_up(value: usize, align: usize) -> usize {
+    (value + align - 1) / align * align
+}
+
+/// Size and alignment of a field, both on the Rust and the GLSL side.
+struct Member {
+    size: usize,
+    rust_align: usize,
+    glsl_align: usize,
+}
+
+fn scalar_size(ty: &syn::Ty) -> Option<(usize, usize)> {
+    match *ty {
+        syn::Ty::Path(None, ref path) if path.segments.len() == 1 => {
+            match path.segments[0].ident.as_ref() {
+                "f32" | "i32" | "u32" => Some((4, 4)),
+                "f64" => Some((8, 8)),
+                // GLSL booleans take 4 bytes
+                "bool" => Some((1, 4)),
+                _ => None,
+            }
+        },
+        _ => None,
+    }
+}
+
+fn array(ty: &syn::Ty) -> Option<(&syn::Ty, usize)> {
+    match *ty {
+        syn::Ty::Array(ref elem, syn::ConstExpr::Lit(syn::Lit::Int(count, _))) =>
+            Some((elem, count as usize)),
+        _ => None,
+    }
+}
+
+/// Returns (size, GLSL alignment, scalar size) of a vector made of 2 to 4 scalars.
+fn vector(ty: &syn::Ty) -> Option<(usize, usize, usize)> {
+    match array(ty) {
+        Some((elem, count)) if 2 <= count && count <= 4 => {
+            scalar_size(elem).and_then(|(rust, glsl)| if rust == glsl {
+                // 3-component vectors are aligned as 4-component ones
+                let lanes = if count == 3 { 4 } else { count };
+                Some((count * rust, lanes * rust, rust))
+            } else {
+                None
+            })
+        },
+        _ => None,
+    }
+}
+
+/// Classify a field type, returning `Ok(None)` for unknown types.
+fn member(ty: &syn::Ty, layout: Layout, field: &syn::Ident) -> Result<Option<Member>, String> {
+    if let Some((rust, glsl)) = scalar_size(ty) {
+        if rust != glsl {
+            return Err(format!("field `{}` is {} byte(s) long in Rust but {} bytes in GLSL, \
+                               consider using `u32` instead", field, rust, glsl));
+        }
+        return Ok(Some(Member { size: rust, rust_align: rust, glsl_align: glsl }));
+    }
+    if let Some((size, align, scalar)) = vector(ty) {
+        return Ok(Some(Member { size: size, rust_align: scalar, glsl_align: align }));
+    }
+    // matrices are arrays of column vectors
+    if let Some((column, count)) = array(ty) {
+        if let Some((size, align, scalar)) = vector(column) {
+            let stride = round_up(size, layout.array_align(align));
+            if stride != size {
+                return Err(format!("field `{}` has a column stride of {} bytes in Rust \
+                                   but {} requires {}, use 4-component columns instead",
+                                   field, size, layout.name(), stride));
+            }
+            return Ok(Some(Member {
+                size: stride * count,
+                rust_align: scalar,
+                glsl_align: layout.array_align(align),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// A padding that needs to be inserted before a field.
+pub struct Padding<'a> {
+    pub field: &'a syn::Ident,
+    pub bytes: usize,
+}
+
+/// Returns (size, alignment) of a hand-written padding, made of scalars.
+fn padding(ty: &syn::Ty) -> Option<(usize, usize)> {
+    match array(ty) {
+        Some((elem, count)) => scalar_size(elem).map(|(size, _)| (count * size, size)),
+        None => scalar_size(ty),
+    }
+}
+
+fn unknown_type(field: &syn::Ident, ty: &syn::Ty, layout: Layout) -> String {
+    format!("the {} layout of field `{}` can't be checked, its type `{}` is not a scalar, \
+             a vector or a matrix", layout.name(), field, quote!(#ty))
+}
+
+/// Check the field offsets of a `#[repr(C)]` struct against the layout rules.
+/// Returns the paddings that need to be inserted for the offsets to match,
+/// or an error for the first field of an unknown type.
+pub fn check<'a>(fields: &'a [syn::Field], layout: Layout) -> Result<Vec<Padding<'a>>, String> {
+    let mut paddings = Vec::new();
+    let mut offset = 0;
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let skipped = gfx_attributes(&field.attrs, &format!("field `{}`", ident))
+            .iter().any(|&(ref key, _)| key == "skip");
+        if skipped {
+            match padding(&field.ty) {
+                Some((size, align)) => {
+                    offset = round_up(offset, align) + size;
+                    continue
+                },
+                None => return Err(unknown_type(ident, &field.ty, layout)),
+            }
+        }
+        let member = match try!(member(&field.ty, layout, ident)) {
+            Some(m) => m,
+            None => return Err(unknown_type(ident, &field.ty, layout)),
+        };
+        let rust_offset = round_up(offset, member.rust_align);
+        let glsl_offset = round_up(offset, member.glsl_align);
+        if glsl_offset > rust_offset {
+            paddings.push(Padding {
+                field: ident,
+                bytes: glsl_offset - rust_offset,
+            });
+        }
+        offset = glsl_offset + member.size;
+    }
+    Ok(paddings)
+}
+
+/// Describe the paddings as an error message.
+pub fn describe(name: &syn::Ident, layout: Layout, paddings: &[Padding]) -> String {
+    let mut message = format!("gfx: the fields of `{}` don't follow the {} layout:", name, layout.name());
+    for p in paddings {
+        message.push_str(&format!("\n - field `{}` needs {} bytes of padding before it",
+                                  p.field, p.bytes));
+    }
+    message.push_str(&format!("\nInsert the padding by hand or add `#[gfx(padded = \"{}Padded\")]` \
+                               to generate a padded copy of the struct", name));
+    message
+}
+
+/// Generate a padded copy of the struct, convertible from the original.
+pub fn padded_struct(ast: &syn::DeriveInput, padded_name: &syn::Ident, paddings: &[Padding])
+                     -> (syn::DeriveInput, quote::Tokens) {
+    let name = &ast.ident;
+    let fields = match ast.body {
+        syn::Body::Struct(syn::VariantData::Struct(ref fields)) => fields,
+        _ => unreachable!(),
+    };
+    let mut padded_fields = Vec::new();
+    let mut conversions = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        if let Some(p) = paddings.iter().find(|p| p.field == ident) {
+            let pad_ident = syn::Ident::new(format!("_pad_{}", ident));
+            let words = p.bytes / 4;
+            padded_fields.push(syn::Field {
+                ident: Some(pad_ident.clone()),
+                vis: syn::Visibility::Inherited,
+                attrs: vec![syn::parse_outer_attr("#[gfx(skip)]").unwrap()],
+                ty: syn::parse_type(&format!("[u32; {}]", words)).unwrap(),
+            });
+            conversions.push(quote!( #pad_ident: [0; #words], ));
+        }
+        // the attributes are kept for `structure()` but not emitted
+        padded_fields.push(field.clone());
+        conversions.push(quote!( #ident: value.#ident, ));
+    }
+    let padded = syn::DeriveInput {
+        ident: padded_name.clone(),
+        vis: ast.vis.clone(),
+        attrs: Vec::new(),
+        generics: syn::Generics::default(),
+        body: syn::Body::Struct(syn::VariantData::Struct(padded_fields)),
+    };
+    let definition = {
+        let vis = &padded.vis;
+        let fields = match padded.body {
+            syn::Body::Struct(syn::VariantData::Struct(ref fields)) => fields,
+            _ => unreachable!(),
+        };
+        let fields = fields.iter().map(|f| {
+            let (vis, ident, ty) = (&f.vis, &f.ident, &f.ty);
+            quote!( #vis #ident: #ty, )
+        }).collect::<Vec<_>>();
+        quote! {
+            #[repr(C)]
+            #[derive(Clone, Copy, Debug, PartialEq)]
+            #vis struct #padded_name {
+                #(#fields)*
+            }
+
+            impl From<#name> for #padded_name {
+                fn from(value: #name) -> #padded_name {
+                    #padded_name {
+                        #(#conversions)*
+                    }
+                }
+            }
+        }
+    };
+    (padded, definition)
+}
@@ -6,8 +6,10 @@ extern crate syn;
 
 use proc_macro::TokenStream;
 
+mod layout;
+mod pipeline;
 
-#[proc_macro_derive(VertexData)]
+#[proc_macro_derive(VertexData, attributes(gfx))]
 pub fn vertex(input: TokenStream) -> TokenStream {
     let s = input.to_string();
     let ast = syn::parse_macro_input(&s).unwrap();
@@ -15,14 +17,109 @@ pub fn vertex(input: TokenStream) -> TokenStream {
     gen.parse().unwrap()
 }
 
-#[proc_macro_derive(ConstantBuffer)]
+#[proc_macro_derive(ConstantBuffer, attributes(gfx))]
 pub fn constant(input: TokenStream) -> TokenStream {
     let s = input.to_string();
     let ast = syn::parse_macro_input(&s).unwrap();
+    let padded = constant_layout(&ast);
     let gen = structure(ast, quote!(gfx::shade::Formatted), quote!(gfx::shade::ConstFormat));
+    quote!(#gen #padded).parse().unwrap()
+}
+
+fn is_repr_c(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| match attr.value {
+        syn::MetaItem::List(ref ident, ref items) if ident.as_ref() == "repr" => {
+            items.iter().any(|item| match *item {
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref w)) => w.as_ref() == "C",
+                _ => false,
+            })
+        },
+        _ => false,
+    })
+}
+
+/// Check the constant buffer against the layout requested by `#[gfx(layout = "std140")]`
+/// or `#[gfx(layout = "std430")]`, generating the padded copy of it if requested by
+/// `#[gfx(padded = "Name")]`, which implies the std140 layout by default.
+fn constant_layout(ast: &syn::DeriveInput) -> quote::Tokens {
+    let name = &ast.ident;
+    let mut requested = None;
+    let mut padded = None;
+    for (key, value) in gfx_attributes(&ast.attrs, &format!("struct `{}`", name)) {
+        match key.as_str() {
+            "layout" if value == "none" => requested = Some(None),
+            "layout" => requested = Some(Some(layout::Layout::parse(&value).unwrap_or_else(||
+                panic!("gfx: unknown layout `{}` of `{}`, expected `std140`, `std430` or `none`",
+                       value, name)))),
+            "padded" => padded = Some(syn::Ident::new(value)),
+            _ => panic!("gfx: unknown attribute `{}` on struct `{}`, expected `layout` or `padded`",
+                        key, name),
+        }
+    }
+    let layout = match (requested, &padded) {
+        (Some(Some(layout)), _) => layout,
+        (None, &Some(_)) => layout::Layout::Std140,
+        _ => return quote::Tokens::new(),
+    };
+    let fields = match ast.body {
+        syn::Body::Struct(syn::VariantData::Struct(ref fields)) => fields,
+        _ => panic!("gfx-rs custom derives can only be casted on structs"),
+    };
+    let paddings = match layout::check(fields, layout) {
+        Ok(paddings) => paddings,
+        Err(e) => panic!("gfx: {}", e),
+    };
+    match padded {
+        Some(ref padded_name) => {
+            if !ast.generics.ty_params.is_empty() || !ast.generics.lifetimes.is_empty() {
+                panic!("gfx: padded copies of generic structs like `{}` are not supported", name);
+            }
+            let (padded_ast, definition) = layout::padded_struct(ast, padded_name, &paddings);
+            let imp = structure(padded_ast, quote!(gfx::shade::Formatted), quote!(gfx::shade::ConstFormat));
+            quote!(#definition #imp)
+        },
+        None if fields.len() > 1 && !is_repr_c(&ast.attrs) => {
+            panic!("gfx: `{}` needs `#[repr(C)]` for its fields to stay in order \
+                    when checking its layout", name)
+        },
+        None if paddings.is_empty() => quote::Tokens::new(),
+        None => panic!("{}", layout::describe(name, layout, &paddings)),
+    }
+}
+
+#[proc_macro_derive(PipelineData, attributes(gfx))]
+pub fn pipeline(input: TokenStream) -> TokenStream {
+    let s = input.to_string();
+    let ast = syn::parse_macro_input(&s).unwrap();
+    let gen = pipeline::derive(ast);
     gen.parse().unwrap()
 }
 
+/// Collect the `key = "value"` pairs of the `#[gfx(...)]` attributes.
+/// A single `key` is returned with an empty value.
+fn gfx_attributes(attrs: &[syn::Attribute], context: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    for attr in attrs.iter().filter(|a| a.name() == "gfx") {
+        let items = match attr.value {
+            syn::MetaItem::List(_, ref items) => items,
+            _ => panic!("gfx: expected `#[gfx(key = \"value\")]` on {}", context),
+        };
+        for item in items {
+            match *item {
+                syn::NestedMetaItem::MetaItem(
+                    syn::MetaItem::NameValue(ref key, syn::Lit::Str(ref value, _))) => {
+                    pairs.push((key.to_string(), value.clone()));
+                },
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref key)) => {
+                    pairs.push((key.to_string(), String::new()));
+                },
+                _ => panic!("gfx: expected `key = \"value\"` pairs in the attribute of {}", context),
+            }
+        }
+    }
+    pairs
+}
+
 fn structure(ast: syn::DeriveInput, ty_compile: quote::Tokens, ty_run: quote::Tokens)
              -> quote::Tokens {
     let name = &ast.ident;
@@ -30,26 +127,81 @@ fn structure(ast: syn::DeriveInput, ty_compile: quote::Tokens, ty_run: quote::To
         syn::Body::Struct(syn::VariantData::Struct(ref fields)) => fields,
         _ => panic!("gfx-rs custom derives can only be casted on structs"),
     };
-    let match_name = fields.iter().map(|field| {
-        let ident = &field.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let mut predicates = where_clause.predicates.iter().map(|p| quote!(#p)).collect::<Vec<_>>();
+    let mut pod_predicates = predicates.clone();
+    for param in &ast.generics.ty_params {
+        let ident = &param.ident;
+        pod_predicates.push(quote!(#ident: gfx::traits::Pod));
+    }
+
+    let mut queries = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
         let ty = &field.ty;
-        quote! {
-            stringify!(#ident) => Some(Element {
-                format: <#ty as #ty_compile>::get_format(),
-                offset: ((&tmp.#ident as *const _ as usize) - base) as ElemOffset + big_offset,
-            }),
+        let mut shader_name = ident.to_string();
+        let mut format = None;
+        let mut nested = false;
+        let mut skip = false;
+        for (key, value) in gfx_attributes(&field.attrs, &format!("field `{}`", ident)) {
+            match key.as_str() {
+                "name" => shader_name = value,
+                "format" => format = Some(syn::parse_type(&value).unwrap_or_else(|e|
+                    panic!("gfx: invalid format type `{}` of field `{}`: {}", value, ident, e))),
+                "nested" => nested = true,
+                "skip" => skip = true,
+                _ => panic!("gfx: unknown attribute `{}` on field `{}`, \
+                             expected `name`, `format`, `nested` or `skip`", key, ident),
+            }
         }
-    });
+        if skip {
+            continue;
+        }
+        if nested {
+            if format.is_some() {
+                panic!("gfx: nested field `{}` can't have a format", ident);
+            }
+            // nested structures are flattened with dotted names
+            let prefix = format!("{}.", shader_name);
+            predicates.push(quote!(#ty: gfx::pso::buffer::Structure<#ty_run>));
+            queries.push(quote! {
+                if sub_name.starts_with(#prefix) {
+                    let offset = (&tmp.#ident as *const _ as usize - base) as ElemOffset;
+                    return <#ty as gfx::pso::buffer::Structure<#ty_run>>::query(&sub_name[#prefix.len() ..])
+                        .map(|el| Element {
+                            format: el.format,
+                            offset: el.offset + offset + big_offset,
+                        });
+                }
+            });
+        } else {
+            let format = format.as_ref().unwrap_or(ty);
+            predicates.push(quote!(#format: #ty_compile));
+            queries.push(quote! {
+                if sub_name == #shader_name {
+                    return Some(Element {
+                        format: <#format as #ty_compile>::get_format(),
+                        offset: (&tmp.#ident as *const _ as usize - base) as ElemOffset + big_offset,
+                    });
+                }
+            });
+        }
+    }
+
     quote! {
-        unsafe impl gfx::traits::Pod for #name {}
-        
-        impl gfx::pso::buffer::Structure<#ty_run> for #name {
+        unsafe impl #impl_generics gfx::traits::Pod for #name #ty_generics
+            where #(#pod_predicates),* {}
+
+        impl #impl_generics gfx::pso::buffer::Structure<#ty_run> for #name #ty_generics
+            where #(#predicates),*
+        {
             fn query(field_name: &str) -> Option<gfx::pso::buffer::Element<#ty_run>> {
                 use std::mem::{size_of, transmute};
                 use gfx::pso::buffer::{Element, ElemOffset};
                 // using an address of 1 as a simplest non-zero pointer to avoid UB
-                let tmp: &#name = unsafe { transmute(1usize) };
+                let tmp: &Self = unsafe { transmute(1usize) };
                 let base = tmp as *const _ as usize;
+                // array queries look like `name[i].field`
                 let (sub_name, big_offset) = {
                     let mut split = field_name.split(|c| c == '[' || c == ']');
                     let _ = split.next().unwrap();
@@ -60,15 +212,13 @@ fn structure(ast: syn::DeriveInput, ty_compile: quote::Tokens, ty_run: quote::To
                                 Some(s) if s.starts_with('.') => &s[1..],
                                 _ => field_name,
                             };
-                            (sub_name, array_id * (size_of::<#name>() as ElemOffset))
+                            (sub_name, array_id * (size_of::<Self>() as ElemOffset))
                         },
                         None => (field_name, 0),
                     }
                 };
-                match sub_name {
-                    #(#match_name)*
-                    _ => None,
-                }
+                #(#queries)*
+                None
             }
         }
     }
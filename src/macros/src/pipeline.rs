@@ -0,0 +1,335 @@
+//! Implementation of `#[derive(PipelineData)]`, an alternative
+//! to the `gfx_pipeline!` macro.
+
+use quote;
+use syn;
+use gfx_attributes;
+
+
+struct PipeField<'a> {
+    ident: &'a syn::Ident,
+    ty: &'a syn::Ty,
+    docs: Vec<quote::Tokens>,
+    init: Option<quote::Tokens>,
+}
+
+impl<'a> PipeField<'a> {
+    fn new(field: &'a syn::Field) -> PipeField<'a> {
+        let ident = field.ident.as_ref().unwrap();
+        let mut init = None;
+        for (key, value) in gfx_attributes(&field.attrs, &format!("field `{}`", ident)) {
+            let tokens = match key.as_str() {
+                "name" => quote!(#value),
+                // the expression is pasted as is
+                "init" => { let mut t = quote::Tokens::new(); t.append(&value); t },
+                _ => panic!("gfx: unknown attribute `{}` on field `{}`, expected `name` or `init`",
+                            key, ident),
+            };
+            if init.is_some() {
+                panic!("gfx: field `{}` has more than one of `name` and `init`", ident);
+            }
+            init = Some(tokens);
+        }
+        PipeField {
+            ident: ident,
+            ty: &field.ty,
+            docs: doc_attributes(&field.attrs),
+            init: init,
+        }
+    }
+}
+
+/// Re-emit the doc comments as plain `#[doc]` attributes.
+fn doc_attributes(attrs: &[syn::Attribute]) -> Vec<quote::Tokens> {
+    attrs.iter().filter_map(|attr| match attr.value {
+        syn::MetaItem::NameValue(_, syn::Lit::Str(ref text, _)) if attr.name() == "doc" => {
+            let text = if attr.is_sugared_doc {
+                text.trim_left_matches("///")
+            } else {
+                text.as_str()
+            };
+            Some(quote!(#[doc = #text]))
+        },
+        _ => None,
+    }).collect()
+}
+
+/// Generate code that links each field with the given method,
+/// expecting `Option<Result<D, E>>` in return.
+fn link_section(fields: &[PipeField], method: &str, iter: &str, slot: &str,
+                error: &str, keep_error: bool) -> quote::Tokens {
+    let method = syn::Ident::new(method);
+    let iter = syn::Ident::new(iter);
+    let slot = syn::Ident::new(slot);
+    let error = syn::Ident::new(error);
+    let matches = fields.iter().map(|f| {
+        let ident = f.ident;
+        // view errors are not reported in detail
+        let fail = if keep_error {
+            quote!( Some(Err(e)) => return Err(InitError::#error(&var.name, Some(e))), )
+        } else {
+            quote!( Some(Err(_)) => return Err(InitError::#error(&var.name, Some(()))), )
+        };
+        quote! {
+            match meta.#ident.#method(var, &self.#ident) {
+                Some(Ok(d)) => {
+                    assert!(meta.#ident.is_active());
+                    desc.#slot[var.slot as usize] = Some(d);
+                    continue;
+                },
+                #fail
+                None => (),
+            }
+        }
+    }).collect::<Vec<_>>();
+    quote! {
+        for var in &info.#iter {
+            #(#matches)*
+            return Err(InitError::#error(&var.name, None));
+        }
+    }
+}
+
+pub fn derive(ast: syn::DeriveInput) -> quote::Tokens {
+    let name = &ast.ident;
+    let vis = &ast.vis;
+    let fields = match ast.body {
+        syn::Body::Struct(syn::VariantData::Struct(ref fields)) => fields,
+        _ => panic!("gfx: `PipelineData` can only be derived for structs with named fields"),
+    };
+    if !ast.generics.lifetimes.is_empty() {
+        panic!("gfx: `PipelineData` can't be derived for `{}` with lifetime parameters", name);
+    }
+
+    let mut data_name = syn::Ident::new(format!("{}Data", name));
+    let mut init_name = syn::Ident::new(format!("{}Init", name));
+    for (key, value) in gfx_attributes(&ast.attrs, &format!("struct `{}`", name)) {
+        match key.as_str() {
+            "data" => data_name = syn::Ident::new(value),
+            "init" => init_name = syn::Ident::new(value),
+            _ => panic!("gfx: unknown attribute `{}` on struct `{}`, expected `data` or `init`",
+                        key, name),
+        }
+    }
+
+    let fields: Vec<_> = fields.iter().map(PipeField::new).collect();
+
+    let (_, meta_ty, _) = ast.generics.split_for_impl();
+    let mut data_generics = ast.generics.clone();
+    data_generics.ty_params.insert(0, syn::TyParam {
+        attrs: Vec::new(),
+        ident: syn::Ident::new("R"),
+        bounds: vec![syn::parse_ty_param_bound("gfx::Resources").unwrap()],
+        default: None,
+    });
+    let (data_impl, data_ty, data_where) = data_generics.split_for_impl();
+    let mut init_generics = ast.generics.clone();
+    init_generics.lifetimes.push(syn::LifetimeDef::new("'a"));
+    let (init_impl, init_ty, init_where) = init_generics.split_for_impl();
+
+    let data_fields = fields.iter().map(|f| {
+        let (ident, ty, docs) = (f.ident, f.ty, &f.docs);
+        quote! {
+            #(#docs)*
+            pub #ident: <#ty as gfx::pso::DataBind<R>>::Data,
+        }
+    }).collect::<Vec<_>>();
+    let init_fields = fields.iter().map(|f| {
+        let (ident, ty, docs) = (f.ident, f.ty, &f.docs);
+        quote! {
+            #(#docs)*
+            pub #ident: <#ty as gfx::pso::DataLink<'a>>::Init,
+        }
+    }).collect::<Vec<_>>();
+    let new_meta = fields.iter().map(|f| {
+        let (ident, ty) = (f.ident, f.ty);
+        quote!( #ident: <#ty as gfx::pso::DataLink<'a>>::new(), )
+    }).collect::<Vec<_>>();
+    let bind = fields.iter().map(|f| {
+        let ident = f.ident;
+        quote!( meta.#ident.bind_to(out, &self.#ident, man, access); )
+    }).collect::<Vec<_>>();
+
+    let link_vb = fields.iter().map(|f| {
+        let ident = f.ident;
+        quote! {
+            if let Some(d) = meta.#ident.link_vertex_buffer(_num_vb, &self.#ident) {
+                assert!(meta.#ident.is_active());
+                desc.vertex_buffers[_num_vb as usize] = Some(d);
+                _num_vb += 1;
+            }
+        }
+    }).collect::<Vec<_>>();
+    let link_attributes = link_section(&fields, "link_input", "vertex_attributes",
+                                       "attributes", "VertexImport", true);
+    let link_cb = link_section(&fields, "link_constant_buffer", "constant_buffers",
+                               "constant_buffers", "ConstantBuffer", true);
+    let link_srv = link_section(&fields, "link_resource_view", "textures",
+                                "resource_views", "ResourceView", false);
+    let link_uav = link_section(&fields, "link_unordered_view", "unordereds",
+                                "unordered_views", "UnorderedView", false);
+    let link_outputs = link_section(&fields, "link_output", "outputs",
+                                    "color_targets", "PixelExport", true);
+    let link_globals = fields.iter().map(|f| {
+        let ident = f.ident;
+        quote! {
+            match meta.#ident.link_global_constant(var, &self.#ident) {
+                Some(Ok(())) => {
+                    assert!(meta.#ident.is_active());
+                    continue;
+                },
+                Some(Err(e)) => return Err(InitError::GlobalConstant(&var.name, Some(e))),
+                None => (),
+            }
+        }
+    }).collect::<Vec<_>>();
+    let link_samplers = fields.iter().map(|f| {
+        let ident = f.ident;
+        quote! {
+            if let Some(d) = meta.#ident.link_sampler(var, &self.#ident) {
+                assert!(meta.#ident.is_active());
+                desc.samplers[var.slot as usize] = Some(d);
+                continue;
+            }
+        }
+    }).collect::<Vec<_>>();
+    let link_unknown_outputs = fields.iter().map(|f| {
+        let ident = f.ident;
+        quote! {
+            match meta.#ident.link_output(&out, &self.#ident) {
+                Some(Ok(d)) => {
+                    assert!(meta.#ident.is_active());
+                    desc.color_targets[out.slot as usize] = Some(d);
+                    out.slot += 1;
+                },
+                Some(Err(fm)) => return Err(InitError::PixelExport(&"!known", Some(fm))),
+                None => (),
+            }
+        }
+    }).collect::<Vec<_>>();
+    let link_rest = fields.iter().map(|f| {
+        let ident = f.ident;
+        quote! {
+            if let Some(d) = meta.#ident.link_depth_stencil(&self.#ident) {
+                assert!(meta.#ident.is_active());
+                desc.depth_stencil = Some(d);
+            }
+            if meta.#ident.link_scissor() {
+                assert!(meta.#ident.is_active());
+                desc.scissor = true;
+            }
+            if let Some(ms) = meta.#ident.link_multisample(&self.#ident) {
+                assert!(meta.#ident.is_active());
+                desc.multisample = ms;
+            }
+            match meta.#ident.link_stream_output(&self.#ident) {
+                Some(Ok(d)) => {
+                    assert!(meta.#ident.is_active());
+                    desc.stream_output = Some(d);
+                },
+                Some(Err(index)) => return Err(InitError::StreamOutput(index)),
+                None => (),
+            }
+        }
+    }).collect::<Vec<_>>();
+
+    // `new()` is only provided when every field knows its initial value
+    let constructor = if fields.iter().all(|f| f.init.is_some()) {
+        let values = fields.iter().map(|f| {
+            let ident = f.ident;
+            let init = f.init.as_ref().unwrap();
+            quote!( #ident: #init, )
+        }).collect::<Vec<_>>();
+        let mut static_generics = ast.generics.clone();
+        static_generics.lifetimes.push(syn::LifetimeDef::new("'static"));
+        let (_, static_ty, _) = static_generics.split_for_impl();
+        let (meta_impl, _, meta_where) = ast.generics.split_for_impl();
+        quote! {
+            impl #meta_impl #init_name #static_ty #meta_where {
+                /// Create the initializer from the values given in the field attributes.
+                pub fn new() -> Self {
+                    #init_name {
+                        #(#values)*
+                    }
+                }
+            }
+        }
+    } else {
+        quote::Tokens::new()
+    };
+
+    quote! {
+        #[derive(Clone, Debug, PartialEq)]
+        #vis struct #data_name #data_impl #data_where {
+            #(#data_fields)*
+        }
+
+        #[derive(Clone, Debug, PartialEq)]
+        #vis struct #init_name #init_impl #init_where {
+            #(#init_fields)*
+        }
+
+        #constructor
+
+        impl #init_impl gfx::pso::PipelineInit for #init_name #init_ty #init_where {
+            type Meta = #name #meta_ty;
+            fn link_to<'s>(&self, desc: &mut gfx::pso::Descriptor, info: &'s gfx::ProgramInfo)
+                       -> ::std::result::Result<Self::Meta, gfx::pso::InitError<&'s str>>
+            {
+                #[allow(unused_imports)]
+                use gfx::pso::{DataLink, InitError};
+                let mut meta = #name {
+                    #(#new_meta)*
+                };
+                // v#
+                let mut _num_vb = 0;
+                #(#link_vb)*
+                #link_attributes
+                // c#
+                #link_cb
+                // global constants
+                for var in &info.globals {
+                    #(#link_globals)*
+                    return Err(InitError::GlobalConstant(&var.name, None));
+                }
+                // t#
+                #link_srv
+                // u#
+                #link_uav
+                // s#
+                for var in &info.samplers {
+                    #(#link_samplers)*
+                    return Err(InitError::Sampler(&var.name, None));
+                }
+                // color targets
+                #link_outputs
+                if !info.knows_outputs {
+                    use gfx::shade::core as s;
+                    let mut out = s::OutputVar {
+                        name: String::new(),
+                        slot: 0,
+                        base_type: s::BaseType::F32,
+                        container: s::ContainerType::Vector(4),
+                    };
+                    #(#link_unknown_outputs)*
+                }
+                // depth-stencil, scissor, multisample, stream output
+                #(#link_rest)*
+                Ok(meta)
+            }
+        }
+
+        impl #data_impl gfx::pso::PipelineData<R> for #data_name #data_ty #data_where {
+            type Meta = #name #meta_ty;
+            fn bake_to(&self,
+                       out: &mut gfx::pso::RawDataSet<R>,
+                       meta: &Self::Meta,
+                       man: &mut gfx::handle::Manager<R>,
+                       access: &mut gfx::pso::AccessInfo<R>) {
+                #[allow(unused_imports)]
+                use gfx::pso::DataBind;
+                #(#bind)*
+            }
+        }
+    }
+}
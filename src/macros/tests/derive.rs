@@ -11,3 +11,106 @@ struct Vertex {
 struct Constant {
     transform: [[f32; 4]; 4],
 }
+
+#[derive(Clone, Debug, PartialEq, PipelineData)]
+#[gfx(data = "PipeData", init = "PipeInit")]
+struct Pipe {
+    /// Vertex buffer, the doc comment is carried over.
+    #[gfx(init = "()")]
+    vbuf: gfx::VertexBuffer<Vertex>,
+    #[gfx(name = "Locals")]
+    locals: gfx::ConstantBuffer<Constant>,
+    #[gfx(name = "Target0")]
+    out: gfx::RenderTarget<gfx::format::Rgba8>,
+    #[gfx(init = "gfx::preset::depth::LESS_EQUAL_WRITE")]
+    depth: gfx::DepthTarget<gfx::format::DepthStencil>,
+}
+
+fn _test_pipeline<R, D>(device: &mut D) -> gfx::PipelineState<R, Pipe>
+    where R: gfx::Resources,
+          D: gfx::traits::DeviceExt<R>
+{
+    device.create_pipeline_simple(&[], &[], PipeInit::new()).unwrap()
+}
+
+fn _test_pipeline_data<R: gfx::Resources>(data: &PipeData<R>) -> PipeData<R> {
+    data.clone()
+}
+
+#[repr(C)]
+#[derive(ConstantBuffer)]
+#[gfx(layout = "std430")]
+struct Particle {
+    pos: [f32; 2],
+    vel: [f32; 2],
+    mass: f32,
+}
+
+// the layout is only checked when requested
+#[derive(ConstantBuffer)]
+struct Unchecked {
+    scale: f32,
+    offset: [f32; 3],
+}
+
+#[derive(Clone, Copy, ConstantBuffer)]
+#[gfx(padded = "LightPadded")]
+struct Light {
+    intensity: f32,
+    color: [f32; 3],
+    transform: [[f32; 4]; 4],
+}
+
+#[test]
+fn test_padded_constant() {
+    use std::mem::size_of;
+    use gfx::pso::buffer::Structure;
+    assert_eq!(size_of::<LightPadded>(), 96);
+    assert_eq!(LightPadded::query("color").unwrap().offset, 16);
+    assert_eq!(LightPadded::query("transform").unwrap().offset, 32);
+    let light = Light {
+        intensity: 1.0,
+        color: [1.0, 0.5, 0.0],
+        transform: [[0.0; 4]; 4],
+    };
+    let padded = LightPadded::from(light);
+    assert_eq!(padded.color, light.color);
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, VertexData)]
+struct Material {
+    #[gfx(name = "a_Color", format = "gfx::format::Rgba8")]
+    color: [u8; 4],
+    shininess: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, VertexData)]
+struct MeshVertex {
+    #[gfx(name = "a_Pos")]
+    pos: [f32; 3],
+    #[gfx(nested)]
+    material: Material,
+}
+
+#[derive(Clone, Copy, VertexData)]
+struct Generic<T> {
+    value: T,
+    #[gfx(skip)]
+    _pad: u32,
+}
+
+#[test]
+fn test_vertex_attributes() {
+    use gfx::format::{ChannelType, Format, SurfaceType};
+    use gfx::pso::buffer::Structure;
+    let color = MeshVertex::query("material.a_Color").unwrap();
+    assert_eq!(color.offset, 12);
+    assert_eq!(color.format, Format(SurfaceType::R8_G8_B8_A8, ChannelType::Unorm));
+    assert_eq!(MeshVertex::query("material.shininess").unwrap().offset, 16);
+    assert_eq!(MeshVertex::query("a_Pos").unwrap().offset, 0);
+    assert!(MeshVertex::query("pos").is_none());
+    assert!(Generic::<[f32; 2]>::query("value").is_some());
+    assert!(Generic::<[f32; 2]>::query("_pad").is_none());
+}
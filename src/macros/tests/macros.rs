@@ -37,10 +37,16 @@ gfx_defines!{
         pixel_color: gfx::RenderTarget<fm::Rgba8> = "Color",
         blend_target: gfx::BlendTarget<Rg16> =
             ("o_Color1", gfx::state::MASK_ALL, gfx::preset::blend::ADD),
+        dual_target: gfx::BlendTarget<Rg16, (gfx::state::Blend, gfx::pso::DualSource)> =
+            ("o_Color2", gfx::state::MASK_ALL, (gfx::preset::blend::ALPHA,
+             gfx::pso::DualSource { color_destination: true, .. Default::default() })),
+        logic_target: gfx::BlendTarget<fm::Rgba8, gfx::pso::LogicOp> =
+            ("o_Color3", gfx::state::MASK_ALL, gfx::pso::LogicOp::Xor),
         depth: gfx::DepthTarget<gfx::format::DepthStencil> =
             gfx::preset::depth::LESS_EQUAL_TEST,
         blend_ref: gfx::BlendRef = (),
         scissor: gfx::Scissor = (),
+        feedback: gfx::StreamOutput<Instance> = &["pos", "color"],
     }
 }
 
@@ -165,6 +165,7 @@ pub struct GraphicsEncoder<'a, B: Backend + 'a> {
     raw_pso_data: pso::RawDataSet<B::Resources>,
     access_info: command::AccessInfo<B::Resources>,
     handles: handle::Manager<B::Resources>,
+    viewports: Vec<command::Viewport>,
 }
 
 impl<'a, B: Backend> From<Encoder<B, GraphicsCommandBuffer<'a, B>>> for GraphicsEncoder<'a, B> {
@@ -174,6 +175,7 @@ impl<'a, B: Backend> From<Encoder<B, GraphicsCommandBuffer<'a, B>>> for Graphics
             raw_pso_data: pso::RawDataSet::new(),
             access_info: command::AccessInfo::new(),
             handles: handle::Manager::new(),
+            viewports: Vec::new(),
         }
     }
 }
@@ -280,8 +282,8 @@ impl<'a, B: Backend> GraphicsEncoder<'a, B> {
                 size: size_bytes,
             });
         }
-        self.access_info.buffer_read(src.raw());
-        self.access_info.buffer_write(dst.raw());
+        self.access_info.buffer_read_range(src.raw(), src_offset_bytes .. src_copy_end);
+        self.access_info.buffer_write_range(dst.raw(), dst_offset_bytes .. dst_copy_end);
 
         self.command_buffer.copy_buffer(
             self.handles.ref_buffer(src.raw()).clone(),
@@ -323,7 +325,7 @@ impl<'a, B: Backend> GraphicsEncoder<'a, B> {
             });
         }
 
-        self.access_info.buffer_read(src);
+        self.access_info.buffer_read_range(src, src_offset_bytes .. src_copy_end);
 
         self.command_buffer.copy_buffer_to_texture(
             self.handles.ref_buffer(src).clone(), src_offset_bytes,
@@ -366,7 +368,7 @@ impl<'a, B: Backend> GraphicsEncoder<'a, B> {
             });
         }
 
-        self.access_info.buffer_write(dst);
+        self.access_info.buffer_write_range(dst, dst_offset_bytes .. dst_copy_end);
 
         self.command_buffer.copy_texture_to_buffer(
             self.handles.ref_texture(src).clone(), src.get_info().kind,
@@ -456,7 +458,8 @@ impl<'a, B: Backend> GraphicsEncoder<'a, B> {
     fn draw_indexed<T>(&mut self, buf: &handle::Buffer<B::Resources, T>, ty: IndexType,
                     slice: &slice::Slice<B::Resources>, base: VertexCount,
                     instances: Option<command::InstanceParams>) {
-        self.access_info.buffer_read(buf.raw());
+        let stride = mem::size_of::<T>();
+        self.access_info.buffer_read_range(buf.raw(), slice.start as usize * stride .. slice.end as usize * stride);
         self.command_buffer.bind_index(self.handles.ref_buffer(buf.raw()).clone(), ty);
         self.command_buffer.call_draw_indexed(slice.start, slice.end - slice.start, base, instances);
     }
@@ -493,6 +496,53 @@ impl<'a, B: Backend> GraphicsEncoder<'a, B> {
         self.command_buffer.clear_depth_stencil(target, None, Some(stencil))
     }
 
+    /// Set the viewport used by the following draw calls, instead of the whole
+    /// extent of the pixel targets. Useful for split-screen rendering.
+    pub fn set_viewport(&mut self, viewport: command::Viewport) {
+        self.set_viewports(&[viewport])
+    }
+
+    /// Set an array of viewports used by the following draw calls. The first
+    /// viewport is used by default, the others can be selected by a geometry
+    /// shader. See `Capabilities::max_viewports` for the supported count.
+    pub fn set_viewports(&mut self, viewports: &[command::Viewport]) {
+        self.viewports.clear();
+        self.viewports.extend_from_slice(viewports);
+    }
+
+    /// Go back to the viewport covering the whole extent of the pixel targets.
+    pub fn reset_viewport(&mut self) {
+        self.viewports.clear();
+    }
+
+    /// Start counting the samples of the following draw calls that pass
+    /// the depth and stencil tests. Occlusion queries can't be nested.
+    pub fn begin_query(&mut self, query: &handle::Query<B::Resources>) {
+        let query = *self.handles.ref_query(query);
+        self.command_buffer.begin_query(query)
+    }
+
+    /// Stop counting the samples of the query started by `begin_query`.
+    /// The result can be read with `Device::get_query_result` after submission.
+    pub fn end_query(&mut self, query: &handle::Query<B::Resources>) {
+        let query = *self.handles.ref_query(query);
+        self.command_buffer.end_query(query)
+    }
+
+    /// Skip the following draw calls if no samples passed in an earlier query,
+    /// without waiting for the result on the CPU. The draw calls are executed
+    /// unconditionally if `Capabilities::conditional_render_supported` is false.
+    pub fn begin_conditional(&mut self, query: &handle::Query<B::Resources>,
+                             mode: command::ConditionalMode) {
+        let query = *self.handles.ref_query(query);
+        self.command_buffer.begin_conditional(query, mode)
+    }
+
+    /// End the block started by `begin_conditional`.
+    pub fn end_conditional(&mut self) {
+        self.command_buffer.end_conditional()
+    }
+
     /// Draws a `slice::Slice` using a pipeline state object, and its matching `Data` structure.
     pub fn draw<D: pso::PipelineData<B::Resources>>(&mut self, slice: &slice::Slice<B::Resources>,
                 pipeline: &pso::PipelineState<B::Resources, D::Meta>, user_data: &D)
@@ -502,8 +552,12 @@ impl<'a, B: Backend> GraphicsEncoder<'a, B> {
         self.raw_pso_data.clear();
         user_data.bake_to(&mut self.raw_pso_data, pipeline.get_meta(), &mut self.handles, &mut self.access_info);
         self.command_buffer.bind_pixel_targets(self.raw_pso_data.pixel_targets.clone());
+        if !self.viewports.is_empty() {
+            self.command_buffer.set_viewports(&self.viewports);
+        }
         self.command_buffer.bind_pipeline_state(pso.clone());
         self.command_buffer.bind_vertex_buffers(self.raw_pso_data.vertex_buffers.clone());
+        self.command_buffer.bind_stream_output(self.raw_pso_data.stream_output.clone());
         self.command_buffer.set_ref_values(self.raw_pso_data.ref_values);
         self.command_buffer.set_scissor(self.raw_pso_data.scissor);
         self.command_buffer.bind_constant_buffers(&self.raw_pso_data.constant_buffers);
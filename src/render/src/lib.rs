@@ -124,8 +124,8 @@ pub use core::{GeneralCommandPool, GraphicsCommandPool, ComputeCommandPool, Subp
 pub use core::{buffer, format, handle, texture, mapping, queue};
 pub use core::device::{Device, ResourceViewError, TargetViewError, CombinedError, WaitFor};
 pub use core::memory::{self, Bind, TRANSFER_SRC, TRANSFER_DST, RENDER_TARGET,
-                       DEPTH_STENCIL, SHADER_RESOURCE, UNORDERED_ACCESS};
-pub use core::command::{Buffer as CommandBuffer, InstanceParams};
+                       DEPTH_STENCIL, SHADER_RESOURCE, UNORDERED_ACCESS, STREAM_OUTPUT};
+pub use core::command::{Buffer as CommandBuffer, ConditionalMode, InstanceParams, Viewport};
 pub use core::shade::{ProgramInfo, UniformValue};
 
 pub use encoder::{CopyBufferResult, CopyBufferTextureResult, CopyError,
@@ -135,11 +135,13 @@ pub use slice::{Slice, IntoIndexBuffer, IndexBuffer};
 pub use swapchain::SwapChainExt;
 pub use pso::{PipelineState};
 pub use pso::buffer::{VertexBuffer, InstanceBuffer, RawVertexBuffer,
-                      ConstantBuffer, RawConstantBuffer, Global, RawGlobal};
+                      ConstantBuffer, RawConstantBuffer, Global, RawGlobal,
+                      StreamOutput};
 pub use pso::resource::{ShaderResource, RawShaderResource, UnorderedAccess,
                         Sampler, TextureSampler};
 pub use pso::target::{DepthStencilTarget, DepthTarget, StencilTarget,
-                      RenderTarget, RawRenderTarget, BlendTarget, BlendRef, Scissor};
+                      RenderTarget, RawRenderTarget, BlendTarget, BlendRef, Scissor,
+                      Multisample};
 pub use pso::bundle::{Bundle};
 
 /// Render commands encoder
@@ -189,7 +189,7 @@ macro_rules! gfx_pipeline_inner {
                         }
                     )*
                 }
-                // depth-stencil, scissor
+                // depth-stencil, scissor, multisample, stream output
                 for _ in 0 .. 1 {
                     $(
                         if let Some(d) = meta.$field.link_depth_stencil(&self.$field) {
@@ -200,6 +200,20 @@ macro_rules! gfx_pipeline_inner {
                             assert!(meta.$field.is_active());
                             desc.scissor = true;
                         }
+                        if let Some(ms) = meta.$field.link_multisample(&self.$field) {
+                            assert!(meta.$field.is_active());
+                            desc.multisample = ms;
+                        }
+                        match meta.$field.link_stream_output(&self.$field) {
+                            Some(Ok(d)) => {
+                                assert!(meta.$field.is_active());
+                                desc.stream_output = Some(d);
+                            },
+                            Some(Err(index)) => return Err(
+                                InitError::StreamOutput(index)
+                            ),
+                            None => (),
+                        }
                     )*
                 }
                 // done
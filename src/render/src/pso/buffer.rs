@@ -14,6 +14,7 @@
 
 //! Buffer components for a PSO.
 
+use std::cmp;
 use std::marker::PhantomData;
 use core::{ConstantBufferSlot, Resources, MAX_VERTEX_ATTRIBUTES};
 use core::{handle, pso, shade};
@@ -97,12 +98,13 @@ pub struct ConstantBuffer<T: Structure<shade::ConstFormat>>(
     PhantomData<T>
 );
 
-/// Raw constant buffer component.
+/// Raw constant buffer component. Only the size of the block in the shader
+/// is read from the buffer, if reported by the backend.
 ///
 /// - init: `&str` = name of the buffer
 /// - data: `RawBuffer`
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub struct RawConstantBuffer(Option<(Usage, ConstantBufferSlot)>);
+pub struct RawConstantBuffer(Option<(Usage, ConstantBufferSlot, usize)>);
 
 /// Global (uniform) constant component. Describes a free-standing value passed
 /// into the shader, which is not enclosed into any constant buffer.
@@ -125,6 +127,19 @@ pub struct Global<T: ToUniform>(
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct RawGlobal(Option<shade::Location>);
 
+/// Stream output component. Captures the vertices produced by the vertex
+/// or geometry shader into a buffer, interleaved by the layout of `T`.
+///
+/// - init: `&[&str]` = names of the captured varyings, by increasing offset
+/// - data: `Buffer<T>`
+#[derive(Derivative)]
+#[derivative(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct StreamOutput<T: Structure<Format>>(
+    bool,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    PhantomData<T>
+);
+
 fn match_attribute(attr: &shade::AttributeVar, fmt: Format) -> bool {
     use core::shade::{BaseType, ContainerType};
     use core::format::ChannelType;
@@ -241,13 +256,17 @@ impl<R: Resources> DataBind<R> for RawVertexBuffer {
                data: &Self::Data,
                man: &mut handle::Manager<R>,
                access: &mut AccessInfo<R>) {
-        let value = Some((man.ref_buffer(data).clone(), 0));
+        // the buffer is bound from its start
+        let offset = 0;
+        let value = Some((man.ref_buffer(data).clone(), offset));
         for i in 0 .. MAX_VERTEX_ATTRIBUTES {
             if (self.1 & (1<<i)) != 0 {
                 out.vertex_buffers.0[i] = value;
             }
         }
-        if self.1 != 0 { access.buffer_read(data); }
+        if self.1 != 0 {
+            access.buffer_read_range(data, offset .. data.get_info().size);
+        }
     }
 }
 
@@ -307,7 +326,7 @@ impl<'a> DataLink<'a> for RawConstantBuffer {
     fn link_constant_buffer<'b>(&mut self, cb: &'b shade::ConstantBufferVar, init: &Self::Init)
                             -> Option<Result<pso::ConstantBufferDesc, ElementError<&'b str>>> {
         if cb.name.as_str() == *init {
-            self.0 = Some((cb.usage, cb.slot));
+            self.0 = Some((cb.usage, cb.slot, cb.size));
             Some(Ok(cb.usage))
         } else {
             None
@@ -322,10 +341,56 @@ impl<R: Resources> DataBind<R> for RawConstantBuffer {
                data: &Self::Data,
                man: &mut handle::Manager<R>,
                access: &mut AccessInfo<R>) {
-        if let Some((usage, slot)) = self.0 {
+        if let Some((usage, slot, size)) = self.0 {
             let buf = man.ref_buffer(data).clone();
             out.constant_buffers.push(pso::ConstantBufferParam(buf, usage, slot));
-            access.buffer_read(data)
+            let buffer_size = data.get_info().size;
+            let end = if size == 0 { buffer_size } else { cmp::min(size, buffer_size) };
+            access.buffer_read_range(data, 0 .. end)
+        }
+    }
+}
+
+impl<'a, T: Structure<Format>> DataLink<'a> for StreamOutput<T> {
+    type Init = &'a [&'a str];
+    fn new() -> Self {
+        StreamOutput(false, PhantomData)
+    }
+    fn is_active(&self) -> bool {
+        self.0
+    }
+    fn link_stream_output(&mut self, init: &Self::Init) ->
+                          Option<Result<pso::StreamOutputDesc, usize>> {
+        use std::mem;
+        let mut varyings = [None; pso::MAX_STREAM_OUTPUT_VARYINGS];
+        let mut offset = 0;
+        for (i, name) in init.iter().enumerate() {
+            match (T::query(name), pso::VaryingName::new(name)) {
+                (Some(el), Some(var)) if el.offset >= offset && i < varyings.len() => {
+                    offset = el.offset;
+                    varyings[i] = Some((var, el));
+                },
+                _ => return Some(Err(i)),
+            }
+        }
+        self.0 = true;
+        Some(Ok(pso::StreamOutputDesc {
+            varyings: varyings,
+            stride: mem::size_of::<T>() as ElemStride,
+        }))
+    }
+}
+
+impl<R: Resources, T: Structure<Format>> DataBind<R> for StreamOutput<T> {
+    type Data = handle::Buffer<R, T>;
+    fn bind_to(&self,
+               out: &mut RawDataSet<R>,
+               data: &Self::Data,
+               man: &mut handle::Manager<R>,
+               access: &mut AccessInfo<R>) {
+        if self.0 {
+            out.stream_output = Some(man.ref_buffer(data.raw()).clone());
+            access.buffer_write(data.raw());
         }
     }
 }
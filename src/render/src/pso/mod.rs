@@ -48,7 +48,7 @@ use std::default::Default;
 use std::error::Error;
 use std::fmt;
 use core as c;
-pub use core::pso::Descriptor;
+pub use core::pso::{Descriptor, MultisampleInfo, DualSource, LogicOp};
 pub use core::command::AccessInfo;
 
 /// A complete set of raw data that needs to be specified at run-time
@@ -68,6 +68,7 @@ pub struct RawDataSet<R: c::Resources>{
     pub pixel_targets: c::pso::PixelTargetSet<R>,
     pub ref_values: c::state::RefValues,
     pub scissor: c::target::Rect,
+    pub stream_output: Option<R::Buffer>,
 }
 
 impl<R: c::Resources> RawDataSet<R> {
@@ -83,6 +84,7 @@ impl<R: c::Resources> RawDataSet<R> {
             pixel_targets: c::pso::PixelTargetSet::new(),
             ref_values: Default::default(),
             scissor: c::target::Rect{x:0, y:0, w:1, h:1},
+            stream_output: None,
         }
     }
     /// Clear all contained data.
@@ -96,6 +98,7 @@ impl<R: c::Resources> RawDataSet<R> {
         self.pixel_targets = c::pso::PixelTargetSet::new();
         self.ref_values = Default::default();
         self.scissor = c::target::Rect{x:0, y:0, w:1, h:1};
+        self.stream_output = None;
     }
 }
 
@@ -182,6 +185,9 @@ pub enum InitError<S> {
     Sampler(S, Option<()>),
     /// Pixel target mismatch.
     PixelExport(S, Option<c::format::Format>),
+    /// Stream output varying not found in the captured structure, out of order,
+    /// or beyond the `MAX_STREAM_OUTPUT_VARYINGS` and `MAX_VARYING_NAME` limits, by index.
+    StreamOutput(usize),
 }
 
 impl<'a> From<InitError<&'a str>> for InitError<String> {
@@ -195,6 +201,7 @@ impl<'a> From<InitError<&'a str>> for InitError<String> {
             UnorderedView(s, v) => UnorderedView(s.to_owned(), v),
             Sampler(s, v) => Sampler(s.to_owned(), v),
             PixelExport(s, v) => PixelExport(s.to_owned(), v),
+            StreamOutput(i) => StreamOutput(i),
         }
     }
 }
@@ -211,6 +218,7 @@ impl<S: fmt::Debug + fmt::Display> fmt::Display for InitError<S> {
             UnorderedView(ref name, opt) => write!(f, "{}: ({}, {:?})", desc, name, opt),
             Sampler(ref name, opt) => write!(f, "{}: ({}, {:?})", desc, name, opt),
             PixelExport(ref name, format) => write!(f, "{}: ({}, {:?})", desc, name, format),
+            StreamOutput(index) => write!(f, "{}: {}", desc, index),
         }
     }
 }
@@ -233,6 +241,7 @@ impl<S: fmt::Debug + fmt::Display> Error for InitError<S> {
             Sampler(..) => "Sampler mismatch",
             PixelExport(_, None) => "Pixel target not found",
             PixelExport(..) => "Pixel target mismatch",
+            StreamOutput(_) => "Stream output varying not found",
         }
     }
 
@@ -328,8 +337,14 @@ pub trait DataLink<'a>: Sized {
     /// Attempt to link with a sampler.
     fn link_sampler(&mut self, _: &c::shade::SamplerVar, _: &Self::Init)
                     -> Option<c::pso::SamplerDesc> { None }
+    /// Attempt to capture the vertex data. Returns the index of
+    /// the first varying that can't be captured on failure.
+    fn link_stream_output(&mut self, _: &Self::Init) ->
+                          Option<Result<c::pso::StreamOutputDesc, usize>> { None }
     /// Attempt to enable scissor test.
     fn link_scissor(&mut self) -> bool { false }
+    /// Attempt to link with the multisampling state.
+    fn link_multisample(&mut self, _: &Self::Init) -> Option<c::pso::MultisampleInfo> { None }
 }
 
 /// The "bind" logic portion of the PSO component.
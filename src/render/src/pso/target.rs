@@ -35,14 +35,19 @@ pub struct RenderTarget<T>(
 
 /// Render target component with active blending mode.
 ///
-/// - init: (`&str`, `ColorMask`, `Blend` = blending state)
+/// The blending state `B` is anything convertible into `ColorInfo`:
+/// a `Blend`, a (`Blend`, `DualSource`) pair for dual-source blending, where the
+/// second color output is declared with `layout(location = N, index = 1)` in GLSL,
+/// or a `LogicOp` replacing the blending.
+///
+/// - init: (`&str`, `ColorMask`, `B` = blending state)
 /// - data: `RenderTargetView<T>`
 #[derive(Derivative)]
 #[derivative(Clone, Debug, Eq, Hash, PartialEq)]
-pub struct BlendTarget<T>(
+pub struct BlendTarget<T, B = state::Blend>(
     RawRenderTarget,
     #[derivative(Hash = "ignore", PartialEq = "ignore")]
-    PhantomData<T>
+    PhantomData<(T, B)>
 );
 
 /// Raw (untyped) render target component with optional blending.
@@ -99,6 +104,13 @@ pub struct Scissor(bool);
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct BlendRef;
 
+/// Multisample component. Sets up alpha-to-coverage, the sample mask and sample shading.
+///
+/// - init: `MultisampleInfo`
+/// - data: `()`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Multisample(bool);
+
 impl<'a, T: format::RenderFormat> DataLink<'a> for RenderTarget<T> {
     type Init = &'a str;
     fn new() -> Self {
@@ -133,8 +145,11 @@ impl<R: Resources, T> DataBind<R> for RenderTarget<T> {
 }
 
 
-impl<'a, T: format::BlendFormat> DataLink<'a> for BlendTarget<T> {
-    type Init = (&'a str, state::ColorMask, state::Blend);
+impl<'a, T, B> DataLink<'a> for BlendTarget<T, B> where
+    T: format::BlendFormat,
+    B: Copy + Into<pso::ColorInfo>,
+{
+    type Init = (&'a str, state::ColorMask, B);
     fn new() -> Self {
         BlendTarget(RawRenderTarget(None), PhantomData)
     }
@@ -143,11 +158,15 @@ impl<'a, T: format::BlendFormat> DataLink<'a> for BlendTarget<T> {
     }
     fn link_output(&mut self, out: &OutputVar, init: &Self::Init) ->
                    Option<Result<pso::ColorTargetDesc, format::Format>> {
-        self.0.link_output(out, &(init.0, T::get_format(), init.1, Some(init.2)))
+        let info = pso::ColorInfo {
+            mask: init.1,
+            .. init.2.into()
+        };
+        self.0.link_info(out, init.0, (T::get_format(), info))
     }
 }
 
-impl<R: Resources, T> DataBind<R> for BlendTarget<T> {
+impl<R: Resources, T, B> DataBind<R> for BlendTarget<T, B> {
     type Data = handle::RenderTargetView<R, T>;
     fn bind_to(&self,
                out: &mut RawDataSet<R>,
@@ -158,6 +177,18 @@ impl<R: Resources, T> DataBind<R> for BlendTarget<T> {
     }
 }
 
+impl RawRenderTarget {
+    fn link_info(&mut self, out: &OutputVar, name: &str, desc: pso::ColorTargetDesc)
+                 -> Option<Result<pso::ColorTargetDesc, format::Format>> {
+        if out.name.is_empty() || &out.name == name {
+            self.0 = Some(out.slot);
+            Some(Ok(desc))
+        }else {
+            None
+        }
+    }
+}
+
 impl<'a> DataLink<'a> for RawRenderTarget {
     type Init = (&'a str, format::Format, state::ColorMask, Option<state::Blend>);
     fn new() -> Self {
@@ -168,17 +199,14 @@ impl<'a> DataLink<'a> for RawRenderTarget {
     }
     fn link_output(&mut self, out: &OutputVar, init: &Self::Init) ->
                    Option<Result<pso::ColorTargetDesc, format::Format>> {
-        if out.name.is_empty() || &out.name == init.0 {
-            self.0 = Some(out.slot);
-            let desc = (init.1, pso::ColorInfo {
+        let info = match init.3 {
+            Some(blend) => pso::ColorInfo {
                 mask: init.2,
-                color: init.3.map(|b| b.color),
-                alpha: init.3.map(|b| b.alpha),
-            });
-            Some(Ok(desc))
-        }else {
-            None
-        }
+                .. blend.into()
+            },
+            None => init.2.into(),
+        };
+        self.link_info(out, init.0, (init.1, info))
     }
 }
 
@@ -296,3 +324,22 @@ impl<R: Resources> DataBind<R> for BlendRef {
         out.ref_values.blend = *data;
     }
 }
+
+impl<'a> DataLink<'a> for Multisample {
+    type Init = pso::MultisampleInfo;
+    fn new() -> Self { Multisample(false) }
+    fn is_active(&self) -> bool { self.0 }
+    fn link_multisample(&mut self, init: &Self::Init) -> Option<pso::MultisampleInfo> {
+        self.0 = true;
+        Some(*init)
+    }
+}
+
+impl<R: Resources> DataBind<R> for Multisample {
+    type Data = ();
+    fn bind_to(&self,
+               _: &mut RawDataSet<R>,
+               _: &Self::Data,
+               _: &mut handle::Manager<R>,
+               _: &mut AccessInfo<R>) {}
+}
@@ -40,16 +40,43 @@ macro_rules! impl_uniforms {
 
 impl_uniforms! {
     i32 = I32,
+    u32 = U32,
     f32 = F32,
+    f64 = F64,
+    bool = Bool,
     [i32; 2] = I32Vector2,
     [i32; 3] = I32Vector3,
     [i32; 4] = I32Vector4,
+    [u32; 2] = U32Vector2,
+    [u32; 3] = U32Vector3,
+    [u32; 4] = U32Vector4,
     [f32; 2] = F32Vector2,
     [f32; 3] = F32Vector3,
     [f32; 4] = F32Vector4,
+    [f64; 2] = F64Vector2,
+    [f64; 3] = F64Vector3,
+    [f64; 4] = F64Vector4,
+    [bool; 2] = BoolVector2,
+    [bool; 3] = BoolVector3,
+    [bool; 4] = BoolVector4,
     [[f32; 2]; 2] = F32Matrix2,
     [[f32; 3]; 3] = F32Matrix3,
     [[f32; 4]; 4] = F32Matrix4,
+    [[f32; 3]; 2] = F32Matrix2x3,
+    [[f32; 4]; 2] = F32Matrix2x4,
+    [[f32; 2]; 3] = F32Matrix3x2,
+    [[f32; 4]; 3] = F32Matrix3x4,
+    [[f32; 2]; 4] = F32Matrix4x2,
+    [[f32; 3]; 4] = F32Matrix4x3,
+    [[f64; 2]; 2] = F64Matrix2,
+    [[f64; 3]; 3] = F64Matrix3,
+    [[f64; 4]; 4] = F64Matrix4,
+    [[f64; 3]; 2] = F64Matrix2x3,
+    [[f64; 4]; 2] = F64Matrix2x4,
+    [[f64; 2]; 3] = F64Matrix3x2,
+    [[f64; 4]; 3] = F64Matrix3x4,
+    [[f64; 2]; 4] = F64Matrix4x2,
+    [[f64; 3]; 4] = F64Matrix4x3,
 }
 
 #[cfg(feature = "mint")]
@@ -62,6 +89,14 @@ impl_uniforms! {
     mint::ColumnMatrix2<f32> = F32Matrix2,
     mint::ColumnMatrix3<f32> = F32Matrix3,
     mint::ColumnMatrix4<f32> = F32Matrix4,
+    mint::Point2<f64> = F64Vector2,
+    mint::Point3<f64> = F64Vector3,
+    mint::Vector2<f64> = F64Vector2,
+    mint::Vector3<f64> = F64Vector3,
+    mint::Vector4<f64> = F64Vector4,
+    mint::ColumnMatrix2<f64> = F64Matrix2,
+    mint::ColumnMatrix3<f64> = F64Matrix3,
+    mint::ColumnMatrix4<f64> = F64Matrix4,
 }
 
 /// Program linking error
@@ -28,7 +28,7 @@ impl<R: Resources> Tracker<R> {
 	pub fn new() -> Self {
 		Tracker {
 			frame_handles: handle::Manager::new(),
-            max_resource_count: Some(999999),
+            max_resource_count: Some(handle::MAX_FRAME_RESOURCES),
 		}
 	}
 	/// TODO
@@ -36,7 +36,8 @@ impl<R: Resources> Tracker<R> {
 		self.frame_handles.extend(man);
         match self.max_resource_count {
             Some(c) if self.frame_handles.count() > c => {
-                error!("Way too many resources in the current frame. Did you call Device::cleanup()?");
+                error!("Way too many resources in the current frame ({}). Did you call Device::cleanup()?",
+                       self.frame_handles.counts());
                 self.max_resource_count = None;
             },
             _ => (),
@@ -0,0 +1,101 @@
+// Copyright 2017 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The subset of EGL 1.4, and of its extensions, used to create an
+//! off-screen context.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use std::os::raw::{c_char, c_uint, c_void};
+
+pub type EGLBoolean = c_uint;
+pub type EGLenum = c_uint;
+pub type EGLint = i32;
+pub type EGLConfig = *mut c_void;
+pub type EGLContext = *mut c_void;
+pub type EGLDisplay = *mut c_void;
+pub type EGLSurface = *mut c_void;
+pub type EGLNativeDisplayType = *mut c_void;
+
+pub const FALSE: EGLBoolean = 0;
+
+pub const DEFAULT_DISPLAY: EGLNativeDisplayType = 0 as EGLNativeDisplayType;
+pub const NO_CONTEXT: EGLContext = 0 as EGLContext;
+pub const NO_DISPLAY: EGLDisplay = 0 as EGLDisplay;
+pub const NO_SURFACE: EGLSurface = 0 as EGLSurface;
+
+pub const ALPHA_SIZE: EGLint = 0x3021;
+pub const BLUE_SIZE: EGLint = 0x3022;
+pub const GREEN_SIZE: EGLint = 0x3023;
+pub const RED_SIZE: EGLint = 0x3024;
+pub const DEPTH_SIZE: EGLint = 0x3025;
+pub const STENCIL_SIZE: EGLint = 0x3026;
+pub const SAMPLES: EGLint = 0x3031;
+pub const SAMPLE_BUFFERS: EGLint = 0x3032;
+pub const SURFACE_TYPE: EGLint = 0x3033;
+pub const NONE: EGLint = 0x3038;
+pub const RENDERABLE_TYPE: EGLint = 0x3040;
+pub const VENDOR: EGLint = 0x3053;
+pub const EXTENSIONS: EGLint = 0x3055;
+pub const HEIGHT: EGLint = 0x3056;
+pub const WIDTH: EGLint = 0x3057;
+
+pub const PBUFFER_BIT: EGLint = 0x0001;
+pub const OPENGL_ES2_BIT: EGLint = 0x0004;
+pub const OPENGL_BIT: EGLint = 0x0008;
+
+pub const OPENGL_ES_API: EGLenum = 0x30A0;
+pub const OPENGL_API: EGLenum = 0x30A2;
+
+// EGL_KHR_create_context
+pub const OPENGL_ES3_BIT_KHR: EGLint = 0x0040;
+pub const CONTEXT_MAJOR_VERSION_KHR: EGLint = 0x3098;
+pub const CONTEXT_MINOR_VERSION_KHR: EGLint = 0x30FB;
+pub const CONTEXT_OPENGL_PROFILE_MASK_KHR: EGLint = 0x30FD;
+pub const CONTEXT_OPENGL_CORE_PROFILE_BIT_KHR: EGLint = 0x0001;
+
+// EGL_KHR_gl_colorspace
+pub const GL_COLORSPACE_KHR: EGLint = 0x309D;
+pub const GL_COLORSPACE_SRGB_KHR: EGLint = 0x3089;
+
+// EGL_MESA_platform_surfaceless
+pub const PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+
+/// `eglGetPlatformDisplayEXT` of EGL_EXT_platform_base, which is only
+/// reachable through `eglGetProcAddress`.
+pub type GetPlatformDisplayEXT = extern "system" fn(platform: EGLenum, native_display: *mut c_void,
+                                                     attrib_list: *const EGLint) -> EGLDisplay;
+
+#[link(name = "EGL")]
+extern "system" {
+    pub fn eglGetError() -> EGLint;
+    pub fn eglGetDisplay(display_id: EGLNativeDisplayType) -> EGLDisplay;
+    pub fn eglInitialize(dpy: EGLDisplay, major: *mut EGLint, minor: *mut EGLint) -> EGLBoolean;
+    pub fn eglTerminate(dpy: EGLDisplay) -> EGLBoolean;
+    pub fn eglQueryString(dpy: EGLDisplay, name: EGLint) -> *const c_char;
+    pub fn eglBindAPI(api: EGLenum) -> EGLBoolean;
+    pub fn eglChooseConfig(dpy: EGLDisplay, attrib_list: *const EGLint, configs: *mut EGLConfig,
+                           config_size: EGLint, num_config: *mut EGLint) -> EGLBoolean;
+    pub fn eglGetConfigAttrib(dpy: EGLDisplay, config: EGLConfig, attribute: EGLint,
+                              value: *mut EGLint) -> EGLBoolean;
+    pub fn eglCreateContext(dpy: EGLDisplay, config: EGLConfig, share_context: EGLContext,
+                            attrib_list: *const EGLint) -> EGLContext;
+    pub fn eglDestroyContext(dpy: EGLDisplay, ctx: EGLContext) -> EGLBoolean;
+    pub fn eglCreatePbufferSurface(dpy: EGLDisplay, config: EGLConfig,
+                                   attrib_list: *const EGLint) -> EGLSurface;
+    pub fn eglDestroySurface(dpy: EGLDisplay, surface: EGLSurface) -> EGLBoolean;
+    pub fn eglMakeCurrent(dpy: EGLDisplay, draw: EGLSurface, read: EGLSurface,
+                          ctx: EGLContext) -> EGLBoolean;
+    pub fn eglGetProcAddress(procname: *const c_char) -> *const c_void;
+}
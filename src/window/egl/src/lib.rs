@@ -0,0 +1,452 @@
+// Copyright 2017 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Off-screen OpenGL contexts created with EGL, without any window system.
+//!
+//! The display of the Mesa surfaceless platform is preferred when it's
+//! available, so neither X11 nor Wayland are needed. The default framebuffer
+//! of the context is a pbuffer of the requested size and formats.
+//!
+//! # Example
+//!
+//! ```no_run
+//! extern crate gfx_core;
+//! extern crate gfx_window_egl;
+//!
+//! use gfx_core::Headless;
+//! use gfx_core::format::{DepthStencil, Formatted, Rgba8};
+//!
+//! fn main() {
+//!     let mut headless = gfx_window_egl::Builder::new(256, 256)
+//!         .with_formats(Rgba8::get_format(), DepthStencil::get_format())
+//!         .build()
+//!         .expect("Failed to build the EGL context");
+//!     let adapters = headless.get_adapters();
+//!     let (color_view, depth_view) = headless.get_main_targets::<Rgba8, DepthStencil>().unwrap();
+//!
+//!     // some code...
+//! }
+//! ```
+
+#![cfg(all(unix, not(any(target_os = "macos", target_os = "ios"))))]
+#![deny(missing_docs)]
+
+#[macro_use]
+extern crate log;
+extern crate gfx_core as core;
+extern crate gfx_device_gl as device_gl;
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_void;
+use std::{mem, ptr};
+use core::{handle, texture};
+use core::format::{ChannelType, DepthFormat, Format, RenderFormat, SurfaceType};
+use core::memory::Typed;
+use device_gl::Resources as R;
+
+mod egl;
+
+/// Client API of the context.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Api {
+    /// OpenGL of the given version, using the core profile from 3.2.
+    OpenGl(u8, u8),
+    /// OpenGL ES of the given version.
+    OpenGlEs(u8, u8),
+}
+
+/// Error of the context creation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InitError {
+    /// No EGL display is available.
+    Display,
+    /// The display failed to initialize, with the EGL error code.
+    Initialize(i32),
+    /// The client API is not supported by the implementation.
+    Api(Api),
+    /// The format can't be used for the default framebuffer.
+    Format(Format),
+    /// No configuration of the display matches the formats and the samples.
+    Config,
+    /// The context failed to be created, with the EGL error code.
+    Context(i32),
+    /// The pbuffer failed to be created, with the EGL error code.
+    Surface(i32),
+    /// The context failed to be made current, with the EGL error code.
+    MakeCurrent(i32),
+}
+
+/// Sizes of the red, green, blue and alpha channels of a color format.
+fn color_bits(format: Format) -> Option<(i32, i32, i32, i32)> {
+    use core::format::SurfaceType as S;
+    Some(match format.0 {
+        S::R4_G4_B4_A4 => (4, 4, 4, 4),
+        S::R5_G5_B5_A1 => (5, 5, 5, 1),
+        S::R5_G6_B5 => (5, 6, 5, 0),
+        S::R8_G8_B8_A8 | S::B8_G8_R8_A8 => (8, 8, 8, 8),
+        S::R10_G10_B10_A2 => (10, 10, 10, 2),
+        _ => return None,
+    })
+}
+
+/// Sizes of the depth and the stencil of a depth-stencil format.
+fn depth_stencil_bits(format: Format) -> Option<(i32, i32)> {
+    use core::format::SurfaceType as S;
+    Some(match format.0 {
+        S::D16 => (16, 0),
+        S::X8_D24 => (24, 0),
+        S::D24_S8 => (24, 8),
+        S::D32 => (32, 0),
+        S::D32_S8 => (32, 8),
+        S::S8 => (0, 8),
+        _ => return None,
+    })
+}
+
+fn get_error() -> i32 {
+    unsafe { egl::eglGetError() }
+}
+
+/// Query a string of the display, or of the client for `EGL_NO_DISPLAY`.
+fn query_string(display: egl::EGLDisplay, name: egl::EGLint) -> String {
+    let string = unsafe { egl::eglQueryString(display, name) };
+    if string.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(string) }.to_string_lossy().into_owned()
+    }
+}
+
+fn get_config_attrib(display: egl::EGLDisplay, config: egl::EGLConfig, attribute: egl::EGLint) -> Option<egl::EGLint> {
+    let mut value = 0;
+    if unsafe { egl::eglGetConfigAttrib(display, config, attribute, &mut value) } == egl::FALSE {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn has_extension(extensions: &str, name: &str) -> bool {
+    extensions.split(' ').any(|ext| ext == name)
+}
+
+fn get_proc_address(symbol: &str) -> *const c_void {
+    let name = CString::new(symbol).unwrap();
+    unsafe { egl::eglGetProcAddress(name.as_ptr()) }
+}
+
+/// Get the display of the surfaceless platform if supported,
+/// falling back to the default one.
+fn get_display() -> egl::EGLDisplay {
+    let client_extensions = query_string(egl::NO_DISPLAY, egl::EXTENSIONS);
+    if has_extension(&client_extensions, "EGL_EXT_platform_base") &&
+       has_extension(&client_extensions, "EGL_MESA_platform_surfaceless") {
+        let address = get_proc_address("eglGetPlatformDisplayEXT");
+        if !address.is_null() {
+            let get_platform_display: egl::GetPlatformDisplayEXT = unsafe { mem::transmute(address) };
+            let display = get_platform_display(egl::PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null());
+            if display != egl::NO_DISPLAY {
+                return display
+            }
+            warn!("Failed to get the surfaceless display, falling back to the default one");
+        }
+    }
+    unsafe { egl::eglGetDisplay(egl::DEFAULT_DISPLAY) }
+}
+
+/// Builder of an off-screen context.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    width: texture::Size,
+    height: texture::Size,
+    samples: texture::NumSamples,
+    color_format: Format,
+    ds_format: Format,
+    api: Api,
+}
+
+impl Builder {
+    /// Start building a context whose default framebuffer has the given size.
+    /// It defaults to RGBA8 colors, a 24-bit depth with an 8-bit stencil,
+    /// no multisampling and OpenGL 3.2.
+    pub fn new(width: texture::Size, height: texture::Size) -> Builder {
+        Builder {
+            width: width,
+            height: height,
+            samples: 0,
+            color_format: Format(SurfaceType::R8_G8_B8_A8, ChannelType::Unorm),
+            ds_format: Format(SurfaceType::D24_S8, ChannelType::Unorm),
+            api: Api::OpenGl(3, 2),
+        }
+    }
+
+    /// Set the color and depth-stencil formats of the default framebuffer.
+    pub fn with_formats(mut self, color_format: Format, ds_format: Format) -> Builder {
+        self.color_format = color_format;
+        self.ds_format = ds_format;
+        self
+    }
+
+    /// Set the number of samples of the default framebuffer.
+    pub fn with_samples(mut self, samples: texture::NumSamples) -> Builder {
+        self.samples = samples;
+        self
+    }
+
+    /// Set the client API of the context.
+    pub fn with_api(mut self, api: Api) -> Builder {
+        self.api = api;
+        self
+    }
+
+    /// Create the context and its pbuffer, leaving the context current.
+    pub fn build(self) -> Result<Headless, InitError> {
+        let (red, green, blue, alpha) = try!(color_bits(self.color_format)
+            .ok_or(InitError::Format(self.color_format)));
+        let (depth, stencil) = try!(depth_stencil_bits(self.ds_format)
+            .ok_or(InitError::Format(self.ds_format)));
+
+        let display = get_display();
+        if display == egl::NO_DISPLAY {
+            return Err(InitError::Display)
+        }
+        let (mut major, mut minor) = (0, 0);
+        if unsafe { egl::eglInitialize(display, &mut major, &mut minor) } == egl::FALSE {
+            return Err(InitError::Initialize(get_error()))
+        }
+        // owns the display from now on, terminating it on errors
+        let mut headless = Headless {
+            display: display,
+            context: egl::NO_CONTEXT,
+            surface: egl::NO_SURFACE,
+            dimensions: (self.width, self.height, 1, self.samples.into()),
+            color_format: self.color_format,
+            ds_format: self.ds_format,
+        };
+        info!("EGL {}.{}: {}", major, minor, query_string(display, egl::VENDOR));
+        let extensions = query_string(display, egl::EXTENSIONS);
+        let create_context_supported = (major, minor) >= (1, 5) ||
+            has_extension(&extensions, "EGL_KHR_create_context");
+
+        let (api, renderable_type) = match self.api {
+            Api::OpenGl(..) => (egl::OPENGL_API, egl::OPENGL_BIT),
+            Api::OpenGlEs(2, _) => (egl::OPENGL_ES_API, egl::OPENGL_ES2_BIT),
+            Api::OpenGlEs(3, _) if create_context_supported => (egl::OPENGL_ES_API, egl::OPENGL_ES3_BIT_KHR),
+            _ => return Err(InitError::Api(self.api)),
+        };
+        if unsafe { egl::eglBindAPI(api) } == egl::FALSE {
+            return Err(InitError::Api(self.api))
+        }
+
+        let mut config_attributes = vec![
+            egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+            egl::RENDERABLE_TYPE, renderable_type,
+            egl::RED_SIZE, red,
+            egl::GREEN_SIZE, green,
+            egl::BLUE_SIZE, blue,
+            egl::ALPHA_SIZE, alpha,
+            egl::DEPTH_SIZE, depth,
+            egl::STENCIL_SIZE, stencil,
+        ];
+        if self.samples > 1 {
+            config_attributes.extend_from_slice(&[
+                egl::SAMPLE_BUFFERS, 1,
+                egl::SAMPLES, self.samples as egl::EGLint,
+            ]);
+        }
+        config_attributes.push(egl::NONE);
+        // the sizes are minimums for `eglChooseConfig`, so all the matching
+        // configurations are fetched to find the exact formats
+        let mut num_configs = 0;
+        let counted = unsafe {
+            egl::eglChooseConfig(display, config_attributes.as_ptr(), ptr::null_mut(), 0, &mut num_configs)
+        };
+        if counted == egl::FALSE || num_configs == 0 {
+            return Err(InitError::Config)
+        }
+        let mut configs = vec![ptr::null_mut(); num_configs as usize];
+        let chosen = unsafe {
+            egl::eglChooseConfig(display, config_attributes.as_ptr(), configs.as_mut_ptr(),
+                                 num_configs, &mut num_configs)
+        };
+        if chosen == egl::FALSE {
+            return Err(InitError::Config)
+        }
+        configs.truncate(num_configs as usize);
+        let samples = if self.samples > 1 { self.samples as egl::EGLint } else { 0 };
+        let expected = [
+            (egl::RED_SIZE, red),
+            (egl::GREEN_SIZE, green),
+            (egl::BLUE_SIZE, blue),
+            (egl::ALPHA_SIZE, alpha),
+            (egl::DEPTH_SIZE, depth),
+            (egl::STENCIL_SIZE, stencil),
+            (egl::SAMPLES, samples),
+        ];
+        let config = match configs.into_iter().find(|&config| {
+            expected.iter().all(|&(attribute, value)| {
+                get_config_attrib(display, config, attribute) == Some(value)
+            })
+        }) {
+            Some(config) => config,
+            None => return Err(InitError::Config),
+        };
+
+        let mut context_attributes = Vec::new();
+        let (version_major, version_minor) = match self.api {
+            Api::OpenGl(major, minor) | Api::OpenGlEs(major, minor) => (major, minor),
+        };
+        if create_context_supported {
+            context_attributes.extend_from_slice(&[
+                egl::CONTEXT_MAJOR_VERSION_KHR, version_major as egl::EGLint,
+                egl::CONTEXT_MINOR_VERSION_KHR, version_minor as egl::EGLint,
+            ]);
+            if let Api::OpenGl(major, minor) = self.api {
+                if (major, minor) >= (3, 2) {
+                    context_attributes.extend_from_slice(&[
+                        egl::CONTEXT_OPENGL_PROFILE_MASK_KHR, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT_KHR,
+                    ]);
+                }
+            }
+        } else if let Api::OpenGlEs(major, _) = self.api {
+            // EGL_CONTEXT_CLIENT_VERSION
+            context_attributes.extend_from_slice(&[egl::CONTEXT_MAJOR_VERSION_KHR, major as egl::EGLint]);
+        }
+        context_attributes.push(egl::NONE);
+        headless.context = unsafe {
+            egl::eglCreateContext(display, config, egl::NO_CONTEXT, context_attributes.as_ptr())
+        };
+        if headless.context == egl::NO_CONTEXT {
+            return Err(InitError::Context(get_error()))
+        }
+
+        let mut surface_attributes = vec![
+            egl::WIDTH, self.width as egl::EGLint,
+            egl::HEIGHT, self.height as egl::EGLint,
+        ];
+        if self.color_format.1 == ChannelType::Srgb {
+            if !has_extension(&extensions, "EGL_KHR_gl_colorspace") {
+                return Err(InitError::Format(self.color_format))
+            }
+            surface_attributes.extend_from_slice(&[egl::GL_COLORSPACE_KHR, egl::GL_COLORSPACE_SRGB_KHR]);
+        }
+        surface_attributes.push(egl::NONE);
+        headless.surface = unsafe {
+            egl::eglCreatePbufferSurface(display, config, surface_attributes.as_ptr())
+        };
+        if headless.surface == egl::NO_SURFACE {
+            return Err(InitError::Surface(get_error()))
+        }
+
+        try!(unsafe { headless.make_current() });
+        Ok(headless)
+    }
+}
+
+/// An off-screen EGL context, with a pbuffer as the default framebuffer.
+pub struct Headless {
+    display: egl::EGLDisplay,
+    context: egl::EGLContext,
+    surface: egl::EGLSurface,
+    dimensions: texture::Dimensions,
+    color_format: Format,
+    ds_format: Format,
+}
+
+impl Headless {
+    /// Make the context current on the calling thread.
+    pub unsafe fn make_current(&self) -> Result<(), InitError> {
+        if egl::eglMakeCurrent(self.display, self.surface, self.surface, self.context) == egl::FALSE {
+            Err(InitError::MakeCurrent(get_error()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the dimensions of the default framebuffer.
+    pub fn get_dimensions(&self) -> texture::Dimensions {
+        self.dimensions
+    }
+
+    /// Get the formats of the color and the depth-stencil of the default framebuffer.
+    pub fn get_formats(&self) -> (Format, Format) {
+        (self.color_format, self.ds_format)
+    }
+
+    /// Get the target views of the default framebuffer.
+    pub fn get_main_targets_raw(&self) -> (handle::RawRenderTargetView<R>, handle::RawDepthStencilView<R>) {
+        device_gl::create_main_targets_raw(self.dimensions, self.color_format.0, self.ds_format.0)
+    }
+
+    /// Get the target views of the default framebuffer, checking their formats.
+    pub fn get_main_targets<Cf, Df>(&self)
+                                    -> Result<(handle::RenderTargetView<R, Cf>, handle::DepthStencilView<R, Df>), InitError>
+    where
+        Cf: RenderFormat,
+        Df: DepthFormat,
+    {
+        if Cf::get_format() != self.color_format {
+            return Err(InitError::Format(Cf::get_format()))
+        }
+        if Df::get_format() != self.ds_format {
+            return Err(InitError::Format(Df::get_format()))
+        }
+        let (color_view, ds_view) = self.get_main_targets_raw();
+        Ok((Typed::new(color_view), Typed::new(ds_view)))
+    }
+}
+
+impl core::Headless<device_gl::Backend> for Headless {
+    type Adapter = device_gl::Adapter;
+
+    fn get_adapters(&mut self) -> Vec<device_gl::Adapter> {
+        unsafe { self.make_current().unwrap() };
+        let adapter = device_gl::Adapter::new(get_proc_address);
+        vec![adapter]
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        unsafe {
+            egl::eglMakeCurrent(self.display, egl::NO_SURFACE, egl::NO_SURFACE, egl::NO_CONTEXT);
+            if self.surface != egl::NO_SURFACE {
+                egl::eglDestroySurface(self.display, self.surface);
+            }
+            if self.context != egl::NO_CONTEXT {
+                egl::eglDestroyContext(self.display, self.context);
+            }
+            egl::eglTerminate(self.display);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headless() {
+        use core::Headless;
+        let mut headless = match Builder::new(64, 32).build() {
+            Ok(headless) => headless,
+            // the test machines may lack a display and a driver to initialize it
+            Err(InitError::Display) | Err(InitError::Initialize(_)) => return,
+            Err(err) => panic!("Failed to build the EGL context: {:?}", err),
+        };
+        assert_eq!(headless.get_dimensions(), (64, 32, 1, texture::AaMode::Single));
+        let adapters = headless.get_adapters();
+        assert_eq!(adapters.len(), 1);
+    }
+}
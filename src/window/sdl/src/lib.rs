@@ -91,8 +91,8 @@ fn sdl2_pixel_format_from_gfx(format: Format) -> Option<PixelFormatEnum> {
             Some(SdlFmt::ARGB2101010)
         }
         R4_G4 | R8 | R8_G8 | R11_G11_B10 | R16 | R16_G16 | R16_G16_B16 |
-        R16_G16_B16_A16 | R32 | R32_G32 | R32_G32_B32 | R32_G32_B32_A32 | D16 | D24 |
-        D24_S8 | D32 | D32_S8 => None,
+        R16_G16_B16_A16 | R32 | R32_G32 | R32_G32_B32 | R32_G32_B32_A32 | D16 | X8_D24 |
+        D24_S8 | D32 | D32_S8 | S8 => None,
     }
 }
 